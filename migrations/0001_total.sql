@@ -0,0 +1,7 @@
+-- Daily totals, the only table before migrations were introduced
+CREATE TABLE IF NOT EXISTS total (
+    chat_id BIGINT,
+    date TEXT,
+    total_seconds INT,
+    CONSTRAINT id_date UNIQUE(chat_id, date)
+);
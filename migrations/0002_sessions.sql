@@ -0,0 +1,12 @@
+-- start_trigger is NULL for sessions that were already running when sessions started being recorded,
+-- user_id is NULL for sessions of a whole chat
+CREATE TABLE IF NOT EXISTS sessions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    chat_id BIGINT NOT NULL,
+    start_timestamp BIGINT NOT NULL,
+    end_timestamp BIGINT,
+    seconds INT,
+    start_trigger TEXT,
+    stop_trigger TEXT,
+    user_id BIGINT
+);
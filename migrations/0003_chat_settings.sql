@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS chat_settings (
+    chat_id BIGINT PRIMARY KEY,
+    utc_offset_minutes INT NOT NULL DEFAULT 0,
+    day_start_minutes INT NOT NULL DEFAULT 0
+);
@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS member_total (
+    chat_id BIGINT,
+    user_id BIGINT,
+    date TEXT,
+    total_seconds INT,
+    CONSTRAINT id_user_date UNIQUE(chat_id, user_id, date)
+);
+
+CREATE TABLE IF NOT EXISTS members (
+    chat_id BIGINT,
+    user_id BIGINT,
+    name TEXT NOT NULL,
+    PRIMARY KEY(chat_id, user_id)
+);
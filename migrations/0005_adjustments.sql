@@ -0,0 +1,11 @@
+-- Manual corrections of a day's total, user_id is NULL when made on behalf of a channel
+CREATE TABLE IF NOT EXISTS adjustments (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    chat_id BIGINT NOT NULL,
+    user_id BIGINT,
+    user_name TEXT NOT NULL,
+    date TEXT NOT NULL,
+    seconds INT NOT NULL,
+    reason TEXT,
+    created_at BIGINT NOT NULL
+);
@@ -0,0 +1,2 @@
+-- Set by /undo, undone sessions no longer count towards the totals
+ALTER TABLE sessions ADD COLUMN undone_at BIGINT;
@@ -0,0 +1,9 @@
+-- What the bot last saw of each chat, so rankings don't have to ask Telegram.
+-- active is 0 once the bot has left or was removed from the chat
+CREATE TABLE IF NOT EXISTS chats (
+    chat_id BIGINT PRIMARY KEY,
+    title TEXT,
+    username TEXT,
+    kind TEXT NOT NULL,
+    active INTEGER NOT NULL DEFAULT 1
+);
@@ -0,0 +1,2 @@
+-- Least standing a day needs to count towards a streak
+ALTER TABLE chat_settings ADD COLUMN streak_minimum_seconds INT NOT NULL DEFAULT 600;
@@ -0,0 +1,15 @@
+-- kind is 'day' or 'week'
+CREATE TABLE IF NOT EXISTS goals (
+    chat_id BIGINT NOT NULL,
+    kind TEXT NOT NULL,
+    seconds INT NOT NULL,
+    PRIMARY KEY(chat_id, kind)
+);
+
+-- Goals already celebrated, by the first day of the day or week they were reached in
+CREATE TABLE IF NOT EXISTS goals_reached (
+    chat_id BIGINT NOT NULL,
+    kind TEXT NOT NULL,
+    period_start TEXT NOT NULL,
+    PRIMARY KEY(chat_id, kind, period_start)
+);
@@ -0,0 +1,9 @@
+-- Digests posted on a schedule: minutes is the local time of day, last_sent the local date of the
+-- last one so a restart neither skips nor repeats it
+CREATE TABLE IF NOT EXISTS digests (
+    chat_id BIGINT NOT NULL,
+    kind TEXT NOT NULL,
+    minutes INT NOT NULL,
+    last_sent TEXT,
+    PRIMARY KEY (chat_id, kind)
+);
@@ -0,0 +1,2 @@
+-- First day of the chat's weeks, 1 for Monday to 7 for Sunday
+ALTER TABLE chat_settings ADD COLUMN week_start INT NOT NULL DEFAULT 1;
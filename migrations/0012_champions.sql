@@ -0,0 +1,10 @@
+-- The chat with the best average per day of every closed week, month and year. Periods are
+-- calendar periods with Monday weeks over each chat's local dates, recorded once the last local
+-- day of the period is over in every timezone.
+CREATE TABLE IF NOT EXISTS champions (
+    kind TEXT NOT NULL,
+    period_start TEXT NOT NULL,
+    chat_id BIGINT NOT NULL,
+    seconds INT NOT NULL,
+    PRIMARY KEY (kind, period_start)
+);
@@ -0,0 +1,2 @@
+-- Whether the chat gets the final leaderboard when a week, month or year closes
+ALTER TABLE chat_settings ADD COLUMN announcements INT NOT NULL DEFAULT 1;
@@ -0,0 +1,4 @@
+-- The chat's day boundary when the session was credited, so /undo takes it off the same days.
+-- NULL for sessions recorded before, those fall back to the chat's current boundary
+ALTER TABLE sessions ADD COLUMN utc_offset_minutes INT;
+ALTER TABLE sessions ADD COLUMN day_start_minutes INT;
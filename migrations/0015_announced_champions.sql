@@ -0,0 +1,3 @@
+-- Whether the champion's final leaderboard was posted. Champions recorded before were announced
+-- right away
+ALTER TABLE champions ADD COLUMN announced INT NOT NULL DEFAULT 1;
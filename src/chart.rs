@@ -0,0 +1,242 @@
+use std::{collections::HashMap, error::Error};
+
+use chrono::{Datelike, Days, NaiveDate, Weekday};
+use tiny_skia::{Color, Paint, Pixmap, Rect, Transform};
+
+use crate::{period::start_of_week, stats::Stats, time::format_short_duration};
+
+const WIDTH: u32 = 800;
+const HEIGHT: u32 = 400;
+const MARGIN: f32 = 20.0;
+/// At most this many grid lines, one every hour or every few hours
+const MAX_GRID_LINES: i64 = 12;
+/// Longest range drawn, a leap year
+pub const MAX_DAYS: i64 = 366;
+const CELL: f32 = 12.0;
+const CELL_GAP: f32 = 2.0;
+/// Heatmap colours from an empty day to the busiest ones
+const HEAT: [(u8, u8, u8); 5] = [(235, 237, 240), (155, 233, 168), (64, 196, 99), (48, 161, 78), (33, 110, 57)];
+
+/// Every day from `start` to `end` with its total, days nobody stood on are zero.
+pub fn fill_days(days: &[(NaiveDate, i64)], start: NaiveDate, end: NaiveDate) -> Vec<(NaiveDate, i64)> {
+    let mut totals: HashMap<NaiveDate, i64> = HashMap::new();
+    for (date, seconds) in days {
+        *totals.entry(*date).or_default() += seconds;
+    }
+    start.iter_days()
+        .take_while(|date| *date <= end)
+        .map(|date| (date, totals.get(&date).copied().unwrap_or(0)))
+        .collect()
+}
+
+/// Hours between the grid lines
+pub fn grid_hours(max_seconds: i64) -> i64 {
+    let hours = (max_seconds + 3599) / 3600;
+    ((hours + MAX_GRID_LINES - 1) / MAX_GRID_LINES).max(1)
+}
+
+fn paint(color: Color) -> Paint<'static> {
+    let mut paint = Paint::default();
+    paint.set_color(color);
+    paint
+}
+
+fn fill(pixmap: &mut Pixmap, x: f32, y: f32, width: f32, height: f32, color: Color) {
+    if let Some(rect) = Rect::from_xywh(x, y, width, height) {
+        pixmap.fill_rect(rect, &paint(color), Transform::identity(), None);
+    }
+}
+
+/// PNG of one bar per day with a grid line every `grid_hours` hours. The best day is highlighted,
+/// weekends are a lighter shade.
+pub fn bar_chart(days: &[(NaiveDate, i64)]) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    let mut pixmap = Pixmap::new(WIDTH, HEIGHT).expect("Chart size is not zero");
+    pixmap.fill(Color::WHITE);
+
+    let (width, height) = (WIDTH as f32 - 2.0 * MARGIN, HEIGHT as f32 - 2.0 * MARGIN);
+    let bottom = MARGIN + height;
+    let best = days.iter().map(|(_, seconds)| *seconds).max().unwrap_or_default();
+    let step = grid_hours(best) * 3600;
+    let top = ((best + step - 1) / step).max(1) * step;
+    let y = |seconds: i64| bottom - height * seconds as f32 / top as f32;
+
+    for line in (step..=top).step_by(step as usize) {
+        fill(&mut pixmap, MARGIN, y(line), width, 1.0, Color::from_rgba8(220, 220, 220, 255));
+    }
+
+    let slot = width / days.len().max(1) as f32;
+    let bar = (slot * 0.8).max(1.0);
+    for (index, (date, seconds)) in days.iter().enumerate() {
+        let color = match date.weekday() {
+            _ if *seconds == best => Color::from_rgba8(240, 170, 20, 255),
+            Weekday::Sat | Weekday::Sun => Color::from_rgba8(120, 170, 230, 255),
+            _ => Color::from_rgba8(40, 110, 200, 255),
+        };
+        let x = MARGIN + slot * index as f32 + (slot - bar) / 2.0;
+        fill(&mut pixmap, x, y(*seconds), bar, bottom - y(*seconds), color);
+    }
+    fill(&mut pixmap, MARGIN, bottom, width, 1.0, Color::BLACK);
+
+    Ok(pixmap.encode_png()?)
+}
+
+/// Text sent with the chart: the dates, total, average and best day, and the grid scale
+pub fn caption(title: &str, days: &[(NaiveDate, i64)]) -> String {
+    let (Some((first, _)), Some((last, _))) = (days.first(), days.last()) else {
+        return title.to_string();
+    };
+    let stats = Stats::new(days);
+    let (best_day, best) = stats.best.unwrap_or((*first, 0));
+    [
+        format!("{title} ({first} — {last})"),
+        format!("Всего: {}, в среднем {} в день", format_short_duration(stats.total), format_short_duration(stats.average)),
+        format!("Лучший день: {best_day} — {}", format_short_duration(best)),
+        format!("Линии — каждые {}ч", grid_hours(best)),
+    ].join("\n")
+}
+
+/// Heatmap shade of a day: 0 for none, up to 4 for days close to `max`
+pub fn heat_level(seconds: i64, max: i64) -> usize {
+    match seconds {
+        ..=0 => 0,
+        _ => ((seconds * 4 + max - 1) / max.max(1)).clamp(1, 4) as usize,
+    }
+}
+
+/// PNG of contribution style calendars, a column per week starting on `week_start` and a row per
+/// weekday, one calendar under another. Shades are relative to the busiest day of all of them so
+/// they can be compared.
+pub fn heatmap(calendars: &[Vec<(NaiveDate, i64)>], week_start: Weekday) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    let first = calendars.iter().filter_map(|days| days.first()).map(|(date, _)| *date).min().unwrap_or_default();
+    let last = calendars.iter().filter_map(|days| days.last()).map(|(date, _)| *date).max().unwrap_or_default();
+    let week_start = start_of_week(first, week_start);
+    let weeks = (last - week_start).num_days() / 7 + 1;
+    let max = calendars.iter().flatten().map(|(_, seconds)| *seconds).max().unwrap_or_default();
+
+    let step = CELL + CELL_GAP;
+    let calendar_height = 7.0 * step;
+    let width = 2.0 * MARGIN + weeks as f32 * step;
+    let height = 2.0 * MARGIN + calendars.len().max(1) as f32 * (calendar_height + CELL) - CELL;
+    let mut pixmap = Pixmap::new(width as u32, height as u32).expect("Heatmap size is not zero");
+    pixmap.fill(Color::WHITE);
+
+    for (index, days) in calendars.iter().enumerate() {
+        let top = MARGIN + index as f32 * (calendar_height + CELL);
+        for (date, seconds) in days {
+            let offset = (*date - week_start).num_days();
+            let (r, g, b) = HEAT[heat_level(*seconds, max)];
+            fill(&mut pixmap,
+                 MARGIN + (offset / 7) as f32 * step,
+                 top + (offset % 7) as f32 * step,
+                 CELL,
+                 CELL,
+                 Color::from_rgba8(r, g, b, 255));
+        }
+    }
+
+    Ok(pixmap.encode_png()?)
+}
+
+/// The 365 days up to `today`
+pub fn year_start(today: NaiveDate) -> NaiveDate {
+    today - Days::new(364)
+}
+
+fn first_day(days: &[(NaiveDate, i64)], start: Option<NaiveDate>) -> Option<NaiveDate> {
+    start.or_else(|| days.first().map(|(date, _)| *date))
+}
+
+/// The chart for days starting at `start`, or at the first day with standing when there's no start
+pub fn range_days(days: &[(NaiveDate, i64)], start: Option<NaiveDate>, end: NaiveDate) -> Vec<(NaiveDate, i64)> {
+    match first_day(days, start) {
+        Some(start) if start <= end => fill_days(days, start, end),
+        _ => Vec::new(),
+    }
+}
+
+/// Whether [`range_days`] would give more than [`MAX_DAYS`] days
+pub fn is_too_long(days: &[(NaiveDate, i64)], start: Option<NaiveDate>, end: NaiveDate) -> bool {
+    first_day(days, start).is_some_and(|start| (end - start).num_days() >= MAX_DAYS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 5, d).unwrap()
+    }
+
+    #[test]
+    fn test_fill_days() {
+        assert_eq!(fill_days(&[(date(2), 60), (date(4), 120)], date(1), date(4)),
+                   vec![(date(1), 0), (date(2), 60), (date(3), 0), (date(4), 120)]);
+        assert_eq!(range_days(&[(date(2), 60)], None, date(3)), vec![(date(2), 60), (date(3), 0)]);
+        assert!(range_days(&[], None, date(3)).is_empty());
+        assert_eq!(fill_days(&[(date(2), 60), (date(2), 30)], date(2), date(2)), vec![(date(2), 90)]);
+    }
+
+    #[test]
+    fn test_too_long() {
+        let year_ago = date(1) - Days::new(365);
+        assert!(!is_too_long(&[], None, date(1)));
+        assert!(!is_too_long(&[], Some(year_ago), date(1)));
+        assert!(is_too_long(&[], Some(year_ago - Days::new(1)), date(1)));
+        assert!(is_too_long(&[(year_ago - Days::new(1), 60)], None, date(1)));
+    }
+
+    #[test]
+    fn test_grid_hours() {
+        assert_eq!(grid_hours(0), 1);
+        assert_eq!(grid_hours(5400), 1);
+        assert_eq!(grid_hours(12 * 3600), 1);
+        assert_eq!(grid_hours(12 * 3600 + 1), 2);
+        assert_eq!(grid_hours(30 * 3600), 3);
+    }
+
+    #[test]
+    fn test_bar_chart() {
+        let png = bar_chart(&fill_days(&[(date(2), 3600), (date(4), 5400)], date(1), date(7))).unwrap();
+        assert_eq!(&png[1..4], b"PNG");
+        let pixmap = Pixmap::decode_png(&png).unwrap();
+        assert_eq!((pixmap.width(), pixmap.height()), (WIDTH, HEIGHT));
+        // The best day's bar reaches the 2 hour grid line's height at 75%
+        let slot = (WIDTH as f32 - 2.0 * MARGIN) / 7.0;
+        let x = (MARGIN + slot * 3.5) as u32;
+        let pixel = |y: u32| pixmap.pixel(x, y).unwrap();
+        assert_eq!((pixel(HEIGHT - 30).red(), pixel(HEIGHT - 30).green()), (240, 170));
+        assert_eq!(pixel(30).red(), 255);
+    }
+
+    #[test]
+    fn test_heatmap() {
+        assert_eq!(heat_level(0, 3600), 0);
+        assert_eq!(heat_level(1, 3600), 1);
+        assert_eq!(heat_level(1800, 3600), 2);
+        assert_eq!(heat_level(3600, 3600), 4);
+
+        // 2024-05-01 is a Wednesday, its week starts on Sunday the 28th
+        let days = fill_days(&[(date(1), 3600), (date(2), 900)], date(1), date(7));
+        let pixmap = Pixmap::decode_png(&heatmap(&[days.clone(), days], Weekday::Sun).unwrap()).unwrap();
+        let step = CELL + CELL_GAP;
+        assert_eq!(pixmap.width(), (2.0 * MARGIN + 2.0 * step) as u32);
+        let color = |x: f32, y: f32| {
+            let pixel = pixmap.pixel((x + CELL / 2.0) as u32, (y + CELL / 2.0) as u32).unwrap();
+            (pixel.red(), pixel.green(), pixel.blue())
+        };
+        assert_eq!(color(MARGIN, MARGIN + 3.0 * step), HEAT[4]);
+        assert_eq!(color(MARGIN, MARGIN + 4.0 * step), HEAT[1]);
+        assert_eq!(color(MARGIN + step, MARGIN), HEAT[0]);
+        // The second calendar below the first
+        assert_eq!(color(MARGIN, MARGIN + 7.0 * step + CELL + 3.0 * step), HEAT[4]);
+        // Days before the range are left blank
+        assert_eq!(color(MARGIN, MARGIN), (255, 255, 255));
+    }
+
+    #[test]
+    fn test_caption() {
+        let days = fill_days(&[(date(2), 3600), (date(4), 5400)], date(1), date(5));
+        assert_eq!(caption("График за последние 5 дн.", &days),
+                   "График за последние 5 дн. (2024-05-01 — 2024-05-05)\nВсего: 2ч 30м, в среднем 30м в день\nЛучший день: 2024-05-04 — 1ч 30м\nЛинии — каждые 1ч");
+    }
+}
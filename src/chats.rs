@@ -0,0 +1,168 @@
+use std::{collections::HashMap, sync::{Arc, Mutex}};
+
+use teloxide::{prelude::*, types::{Chat, ChatMemberUpdated, UpdateKind}, ApiError, RequestError};
+
+use crate::{store::{ChatInfo, Store, StoreResult}, HandlerResult};
+
+/// Chats as last written to the store, so unchanged chats aren't written again on every update
+pub type KnownChats = Arc<Mutex<HashMap<i64, ChatInfo>>>;
+
+pub fn chat_info(chat: &Chat, active: bool) -> ChatInfo {
+    let kind = if chat.is_private() {
+        "private"
+    } else if chat.is_group() {
+        "group"
+    } else if chat.is_supergroup() {
+        "supergroup"
+    } else {
+        "channel"
+    };
+    let title = chat.title().map(str::to_string).or_else(|| {
+        let name = [chat.first_name(), chat.last_name()].into_iter().flatten().collect::<Vec<_>>().join(" ");
+        (!name.is_empty()).then_some(name)
+    });
+    ChatInfo {
+        chat_id: chat.id.0,
+        title,
+        username: chat.username().map(str::to_string),
+        kind: kind.to_string(),
+        active,
+    }
+}
+
+/// Refreshes the cache from every update that has a chat. Membership changes are handled by
+/// [`my_chat_member`], failures are only logged so the update is still handled.
+pub async fn remember_chat(update: Update, total_manager: Store, known_chats: KnownChats) {
+    if matches!(update.kind, UpdateKind::MyChatMember(_)) {
+        return;
+    }
+    if let Some(chat) = update.chat() {
+        if let Err(e) = save_if_changed(&total_manager, &known_chats, chat_info(chat, true)).await {
+            log::warn!("Failed to cache chat {}: {:?}", chat.id, e);
+        }
+    }
+}
+
+/// The bot was added to, removed from or left a chat
+pub async fn my_chat_member(update: ChatMemberUpdated, total_manager: Store, known_chats: KnownChats) -> HandlerResult {
+    save_if_changed(&total_manager, &known_chats, chat_info(&update.chat, update.new_chat_member.kind.is_present())).await?;
+    Ok(())
+}
+
+/// Writes the chat unless it is the same as the last time, returns whether it was written
+async fn save_if_changed(total_manager: &Store, known_chats: &KnownChats, info: ChatInfo) -> StoreResult<bool> {
+    if known_chats.lock().unwrap().get(&info.chat_id) == Some(&info) {
+        return Ok(false);
+    }
+    total_manager.save_chat(&info).await?;
+    known_chats.lock().unwrap().insert(info.chat_id, info);
+    Ok(true)
+}
+
+/// The bot can no longer write to the chat, so trying again later won't help
+pub fn is_unreachable(error: &RequestError) -> bool {
+    matches!(error, RequestError::Api(
+        ApiError::BotBlocked
+            | ApiError::BotKicked
+            | ApiError::BotKickedFromSupergroup
+            | ApiError::ChatNotFound
+            | ApiError::GroupDeactivated
+            | ApiError::UserDeactivated
+            | ApiError::CantInitiateConversation
+            | ApiError::NotEnoughRightsToPostMessages
+    ))
+}
+
+/// Asks Telegram about a chat missing from the cache and caches the answer. If Telegram refuses,
+/// the chat is cached as left; on network errors nothing is cached.
+async fn fetch_chat(bot: &Bot, total_manager: &Store, chat_id: i64) -> StoreResult<Option<ChatInfo>> {
+    let info = match bot.get_chat(ChatId(chat_id)).await {
+        Ok(chat) => chat_info(&chat, true),
+        Err(RequestError::Api(e)) => {
+            log::warn!("Chat {chat_id} is gone: {e}");
+            ChatInfo { chat_id, title: None, username: None, kind: "unknown".to_string(), active: false }
+        }
+        Err(e) => {
+            log::warn!("Failed to get chat {chat_id}: {e}");
+            return Ok(None);
+        }
+    };
+    total_manager.save_chat(&info).await?;
+    Ok(Some(info))
+}
+
+/// An active cached chat by "@username", "username" or id
+pub async fn find_chat(total_manager: &Store, text: &str) -> StoreResult<Option<ChatInfo>> {
+    let username = text.trim_start_matches('@');
+    Ok(total_manager.get_chats().await?.into_iter()
+        .find(|chat| chat.active && (chat.username.as_deref() == Some(username) || text.parse() == Ok(chat.chat_id))))
+}
+
+/// Names of chats for leaderboards from the cache, leaving out chats the bot is no longer in.
+pub async fn chat_names(bot: &Bot, total_manager: &Store, chat_ids: Vec<i64>) -> StoreResult<HashMap<i64, String>> {
+    let chats: HashMap<i64, ChatInfo> = total_manager.get_chats().await?
+        .into_iter()
+        .map(|chat| (chat.chat_id, chat))
+        .collect();
+
+    let mut names = HashMap::new();
+    for chat_id in chat_ids {
+        let chat = match chats.get(&chat_id) {
+            Some(chat) => Some(chat.clone()),
+            None => fetch_chat(bot, total_manager, chat_id).await?,
+        };
+        match chat {
+            Some(chat) if !chat.active => {}
+            Some(chat) => {
+                names.insert(chat_id, chat.name());
+            }
+            None => {
+                names.insert(chat_id, "Нет имени".to_string());
+            }
+        }
+    }
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::memory_store::MemoryStore;
+
+    #[tokio::test]
+    async fn test_chat_names_from_cache() {
+        let store: Store = Arc::new(MemoryStore::default());
+        store.save_chat(&ChatInfo { chat_id: -1, title: Some("Standing".to_string()), username: None, kind: "supergroup".to_string(), active: true }).await.unwrap();
+        store.save_chat(&ChatInfo { chat_id: -2, title: None, username: Some("stand".to_string()), kind: "channel".to_string(), active: true }).await.unwrap();
+        store.save_chat(&ChatInfo { chat_id: -3, title: Some("Left".to_string()), username: None, kind: "group".to_string(), active: false }).await.unwrap();
+
+        // Never asks Telegram for cached chats, the token is fake
+        let names = chat_names(&Bot::new("0:fake"), &store, vec![-1, -2, -3]).await.unwrap();
+        assert_eq!(names, HashMap::from([(-1, "Standing".to_string()), (-2, "@stand".to_string())]));
+    }
+
+    #[test]
+    fn test_unreachable() {
+        assert!(is_unreachable(&RequestError::Api(ApiError::BotBlocked)));
+        assert!(is_unreachable(&RequestError::Api(ApiError::ChatNotFound)));
+        assert!(!is_unreachable(&RequestError::Api(ApiError::MessageNotModified)));
+        assert!(!is_unreachable(&RequestError::RetryAfter(teloxide::types::Seconds::from_seconds(5))));
+    }
+
+    #[tokio::test]
+    async fn test_saving_only_changes() {
+        let store: Store = Arc::new(MemoryStore::default());
+        let known_chats = KnownChats::default();
+        let mut chat = ChatInfo { chat_id: -1, title: Some("Standing".to_string()), username: None, kind: "supergroup".to_string(), active: true };
+
+        assert!(save_if_changed(&store, &known_chats, chat.clone()).await.unwrap());
+        assert!(!save_if_changed(&store, &known_chats, chat.clone()).await.unwrap());
+        chat.title = Some("Sitting".to_string());
+        assert!(save_if_changed(&store, &known_chats, chat.clone()).await.unwrap());
+        chat.active = false;
+        assert!(save_if_changed(&store, &known_chats, chat.clone()).await.unwrap());
+        assert_eq!(store.get_chats().await.unwrap(), vec![chat]);
+    }
+}
@@ -0,0 +1,85 @@
+use std::{collections::HashMap, sync::{Arc, Mutex}};
+
+use chrono::NaiveDate;
+use teloxide::types::ChatId;
+
+use crate::{chart::fill_days, period::Period, stats::Stats, streak, time::format_short_duration};
+
+/// Periods of /compare waiting for the other chat to be picked with the keyboard button
+pub type PendingCompares = Arc<Mutex<HashMap<ChatId, Period>>>;
+
+/// One of the compared chats over the period
+pub struct Side {
+    pub name: String,
+    /// Every day of the period, zero-filled
+    pub days: Vec<(NaiveDate, i64)>,
+    /// Current streak
+    pub streak: u32,
+}
+
+impl Side {
+    pub fn new(name: String, days: &[(NaiveDate, i64)], start: NaiveDate, end: NaiveDate, streak: u32) -> Self {
+        Side { name, days: fill_days(days, start, end), streak }
+    }
+}
+
+/// Days the first chat stood longer, days the second did, and days both stood equally
+pub fn tally(a: &[(NaiveDate, i64)], b: &[(NaiveDate, i64)]) -> (usize, usize, usize) {
+    let b: HashMap<NaiveDate, i64> = b.iter().copied().collect();
+    a.iter().fold((0, 0, 0), |(wins, losses, draws), (date, seconds)| {
+        let other = b.get(date).copied().unwrap_or_default();
+        match seconds.cmp(&other) {
+            std::cmp::Ordering::Greater => (wins + 1, losses, draws),
+            std::cmp::Ordering::Less => (wins, losses + 1, draws),
+            std::cmp::Ordering::Equal if *seconds > 0 => (wins, losses, draws + 1),
+            std::cmp::Ordering::Equal => (wins, losses, draws),
+        }
+    })
+}
+
+/// The two chats line by line: "Всего: 10ч — 8ч"
+pub fn render(period: &str, a: &Side, b: &Side) -> String {
+    let (a_stats, b_stats) = (Stats::new(&a.days), Stats::new(&b.days));
+    let best = |stats: &Stats| stats.best.map_or("—".to_string(), |(date, seconds)| format!("{} ({date})", format_short_duration(seconds)));
+    let streak = |days: u32| format!("{days} {}", streak::days_word(days));
+    let (wins, losses, draws) = tally(&a.days, &b.days);
+    [
+        format!("{} vs {} {period}", a.name, b.name),
+        format!("Всего: {} — {}", format_short_duration(a_stats.total), format_short_duration(b_stats.total)),
+        format!("В среднем за день: {} — {}", format_short_duration(a_stats.average), format_short_duration(b_stats.average)),
+        format!("Серия: {} — {}", streak(a.streak), streak(b.streak)),
+        format!("Лучший день: {} — {}", best(&a_stats), best(&b_stats)),
+        format!("Дней со стоянием: {} — {}", a_stats.active_days, b_stats.active_days),
+        format!("Победы по дням: {wins} — {losses}, ничьих: {draws}"),
+    ].join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 5, d).unwrap()
+    }
+
+    #[test]
+    fn test_tally() {
+        let a = fill_days(&[(date(1), 600), (date(2), 300), (date(3), 900)], date(1), date(5));
+        let b = fill_days(&[(date(1), 300), (date(2), 300), (date(4), 60)], date(1), date(5));
+        assert_eq!(tally(&a, &b), (2, 1, 1));
+        assert_eq!(tally(&b, &a), (1, 2, 1));
+    }
+
+    #[test]
+    fn test_render() {
+        let a = Side::new("Утренние".to_string(), &[(date(1), 3600), (date(2), 1800)], date(1), date(3), 2);
+        let b = Side::new("Вечерние".to_string(), &[(date(2), 5400)], date(1), date(3), 1);
+        assert_eq!(render("за этот месяц", &a, &b), "Утренние vs Вечерние за этот месяц\n\
+                                                    Всего: 1ч 30м — 1ч 30м\n\
+                                                    В среднем за день: 30м — 30м\n\
+                                                    Серия: 2 дня — 1 день\n\
+                                                    Лучший день: 1ч (2024-05-01) — 1ч 30м (2024-05-02)\n\
+                                                    Дней со стоянием: 2 — 1\n\
+                                                    Победы по дням: 1 — 1, ничьих: 0");
+    }
+}
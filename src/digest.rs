@@ -0,0 +1,210 @@
+use chrono::{DateTime, Days, NaiveDate, Timelike, Weekday};
+use teloxide::{prelude::*, types::{ChatId, ParseMode}};
+use tokio::time::{sleep, Duration};
+
+use crate::{chats, goal, period::{start_of_week, DateRange, Period}, store::{StandingStore, Store, StoreResult}, time::{format_short_duration, DayBoundary}};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DigestKind {
+    Day,
+    Week,
+}
+
+impl DigestKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DigestKind::Day => "day",
+            DigestKind::Week => "week",
+        }
+    }
+
+    pub fn parse(text: &str) -> Option<Self> {
+        match text {
+            "day" => Some(DigestKind::Day),
+            "week" => Some(DigestKind::Week),
+            _ => None,
+        }
+    }
+}
+
+/// A chat's scheduled digest
+#[derive(Clone, Debug, PartialEq)]
+pub struct Digest {
+    pub chat_id: i64,
+    pub kind: DigestKind,
+    /// Local time of day
+    pub minutes: i32,
+    /// Local date of the last digest sent
+    pub last_sent: Option<NaiveDate>,
+}
+
+impl Digest {
+    /// The chat's day the digest is due for at `now`, None if it isn't due. Daily digests are due
+    /// once their time has passed, weekly ones on the last day of the week. Times are counted from
+    /// the start of the chat's day, so a 01:00 digest in a chat whose day starts at 04:00 is sent
+    /// at the end of the day before.
+    pub fn due(&self, boundary: DayBoundary, week_start: Weekday, now: i64) -> Option<NaiveDate> {
+        let local = DateTime::from_timestamp(now + boundary.shift_seconds(), 0)?.naive_utc();
+        let date = boundary.date_of(now);
+        let minutes = (local.hour() * 60 + local.minute()) as i32;
+        let due_minutes = (self.minutes - boundary.day_start_minutes).rem_euclid(24 * 60);
+        let day_of_week = self.kind == DigestKind::Day || start_of_week(date, week_start) + Days::new(6) == date;
+        (day_of_week && minutes >= due_minutes && self.last_sent.is_none_or(|sent| sent < date)).then_some(date)
+    }
+}
+
+/// Position of the chat among the chats that stood in the range and their number
+async fn rank(store: &dyn StandingStore, chat_id: ChatId, range: DateRange) -> StoreResult<Option<(usize, usize)>> {
+    let inactive: Vec<i64> = store.get_chats().await?.into_iter()
+        .filter(|chat| !chat.active)
+        .map(|chat| chat.chat_id)
+        .collect();
+    let totals = store.get_chat_totals(range).await?;
+    let Some(seconds) = totals.iter()
+        .find(|total| total.chat_id == chat_id.0 && total.seconds > 0)
+        .map(|total| total.seconds) else {
+        return Ok(None);
+    };
+    let totals: Vec<i64> = totals.into_iter()
+        .filter(|total| total.seconds > 0 && !inactive.contains(&total.chat_id))
+        .map(|total| total.seconds)
+        .collect();
+    let position = totals.iter().filter(|other| **other > seconds).count() + 1;
+    Ok(Some((position, totals.len())))
+}
+
+async fn total(store: &dyn StandingStore, chat_id: ChatId, range: DateRange) -> StoreResult<i64> {
+    Ok(store.get_daily_totals(chat_id, range).await?.iter().map(|(_, seconds)| seconds).sum())
+}
+
+/// The digest's message for the chat's local day `today`
+pub async fn render(store: &dyn StandingStore, chat_id: ChatId, kind: DigestKind, today: NaiveDate) -> StoreResult<String> {
+    let week_start = store.get_week_start(chat_id).await?;
+    let (title, range, label, other_label, other_range) = match kind {
+        DigestKind::Day => ("Итоги дня", DateRange { start: Some(today), end: Some(today) },
+                            "Сегодня", "С начала недели", Period::Week.resolve(today, week_start)),
+        DigestKind::Week => ("Итоги недели", Period::Week.resolve(today, week_start),
+                             "За неделю", "Прошлая неделя", Period::LastWeek.resolve(today, week_start)),
+    };
+    let mut lines = vec![
+        format!("<b>{title}</b>"),
+        format!("{label}: {}", format_short_duration(total(store, chat_id, range).await?)),
+    ];
+    if let Some((position, chats)) = rank(store, chat_id, range).await? {
+        lines.push(format!("Место среди чатов: {position} из {chats}"));
+    }
+    lines.push(format!("{other_label}: {}", format_short_duration(total(store, chat_id, other_range).await?)));
+    lines.extend(goal::progress_lines(store, chat_id, today).await?);
+    Ok(lines.join("\n"))
+}
+
+/// Sends the digests due at `now` and records them as sent. Chats the bot has left are skipped,
+/// a digest that failed to send for another chat is tried again the next minute.
+async fn send_due(bot: &Bot, store: &dyn StandingStore, now: i64) -> StoreResult<()> {
+    let inactive: Vec<i64> = store.get_chats().await?.into_iter()
+        .filter(|chat| !chat.active)
+        .map(|chat| chat.chat_id)
+        .collect();
+    for digest in store.get_digests().await? {
+        if inactive.contains(&digest.chat_id) {
+            continue;
+        }
+        if let Err(err) = send_if_due(bot, store, &digest, now).await {
+            log::warn!("Failed to send the digest to {}: {:?}", digest.chat_id, err);
+        }
+    }
+    Ok(())
+}
+
+async fn send_if_due(bot: &Bot, store: &dyn StandingStore, digest: &Digest, now: i64) -> StoreResult<()> {
+    let chat_id = ChatId(digest.chat_id);
+    let boundary = store.get_day_boundary(chat_id).await?;
+    let Some(date) = digest.due(boundary, store.get_week_start(chat_id).await?, now) else {
+        return Ok(());
+    };
+    let text = render(store, chat_id, digest.kind, boundary.date_of(now)).await?;
+    match bot.send_message(chat_id, text).parse_mode(ParseMode::Html).await {
+        Ok(_) => {}
+        // Marked as sent so a chat that can't be reached isn't retried every minute
+        Err(err) if chats::is_unreachable(&err) => log::warn!("Chat {} can't get the digest: {err}", digest.chat_id),
+        Err(err) => return Err(err.into()),
+    }
+    store.mark_digest_sent(chat_id, digest.kind, date).await?;
+    Ok(())
+}
+
+/// Checks for due digests every minute
+pub async fn send_periodically(bot: Bot, store: Store) {
+    loop {
+        if let Err(err) = send_due(&bot, store.as_ref(), chrono::Utc::now().timestamp()).await {
+            log::warn!("Failed to send digests: {:?}", err);
+        }
+        sleep(Duration::from_secs(60)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{goal::GoalKind, memory_store::MemoryStore, store::{ChatInfo, MergePolicy}};
+
+    fn date(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 5, d).unwrap()
+    }
+
+    #[test]
+    fn test_due() {
+        let boundary = DayBoundary { utc_offset_minutes: 360, day_start_minutes: 0 };
+        let evening = 1715774400; // 2024-05-15 12:00 UTC, 18:00 local
+        let digest = |kind, last_sent| Digest { chat_id: 1, kind, minutes: 21 * 60, last_sent };
+
+        assert_eq!(digest(DigestKind::Day, None).due(boundary, Weekday::Sun, evening), None);
+        assert_eq!(digest(DigestKind::Day, None).due(boundary, Weekday::Sun, evening + 3 * 3600), Some(date(15)));
+        assert_eq!(digest(DigestKind::Day, Some(date(14))).due(boundary, Weekday::Sun, evening + 4 * 3600), Some(date(15)));
+        assert_eq!(digest(DigestKind::Day, Some(date(15))).due(boundary, Weekday::Sun, evening + 4 * 3600), None);
+        // Past local midnight it waits for the next evening
+        assert_eq!(digest(DigestKind::Day, Some(date(14))).due(boundary, Weekday::Sun, evening + 7 * 3600), None);
+
+        // Weekly on the last day of the week only
+        assert_eq!(digest(DigestKind::Week, None).due(boundary, Weekday::Sun, evening + 3 * 3600), None);
+        assert_eq!(digest(DigestKind::Week, None).due(boundary, Weekday::Sun, evening + 3 * 86400 + 3 * 3600), Some(date(18)));
+        assert_eq!(digest(DigestKind::Week, None).due(boundary, Weekday::Mon, evening + 3 * 86400 + 3 * 3600), None);
+        assert_eq!(digest(DigestKind::Week, None).due(boundary, Weekday::Mon, evening + 4 * 86400 + 3 * 3600), Some(date(19)));
+
+        // The day starts at 04:00, a 01:00 digest closes the day that began the evening before
+        let late = DayBoundary { utc_offset_minutes: 360, day_start_minutes: 4 * 60 };
+        let night = |kind, last_sent| Digest { chat_id: 1, kind, minutes: 60, last_sent };
+        assert_eq!(night(DigestKind::Day, Some(date(14))).due(late, Weekday::Sun, evening + 6 * 3600), None);
+        assert_eq!(night(DigestKind::Day, Some(date(14))).due(late, Weekday::Sun, evening + 7 * 3600), Some(date(15)));
+        assert_eq!(night(DigestKind::Day, Some(date(15))).due(late, Weekday::Sun, evening + 9 * 3600), None);
+        // 21:00 still comes before the day ends at 04:00
+        assert_eq!(digest(DigestKind::Day, Some(date(14))).due(late, Weekday::Sun, evening + 3 * 3600), Some(date(15)));
+    }
+
+    #[tokio::test]
+    async fn test_render() {
+        let store = MemoryStore::default();
+        store.import_daily_totals(ChatId(1), &[(date(13), 3600), (date(15), 4800)], MergePolicy::Add).await.unwrap();
+        store.import_daily_totals(ChatId(2), &[(date(15), 7200)], MergePolicy::Add).await.unwrap();
+        store.import_daily_totals(ChatId(3), &[(date(15), 9000)], MergePolicy::Add).await.unwrap();
+        store.save_chat(&ChatInfo { chat_id: 3, title: None, username: None, kind: "group".to_string(), active: false }).await.unwrap();
+        store.set_goal(ChatId(1), GoalKind::Day, Some(7200)).await.unwrap();
+
+        assert_eq!(render(&store, ChatId(1), DigestKind::Day, date(15)).await.unwrap(),
+                   "<b>Итоги дня</b>\nСегодня: 1ч 20м\nМесто среди чатов: 2 из 2\nС начала недели: 2ч 20м\nЦель дня: 1ч 20м из 2ч (67%)");
+        assert_eq!(render(&store, ChatId(1), DigestKind::Week, date(18)).await.unwrap(),
+                   "<b>Итоги недели</b>\nЗа неделю: 2ч 20м\nМесто среди чатов: 1 из 2\nПрошлая неделя: 0м\nЦель дня: 0м из 2ч (0%)");
+        assert_eq!(render(&store, ChatId(4), DigestKind::Day, date(15)).await.unwrap(),
+                   "<b>Итоги дня</b>\nСегодня: 0м\nС начала недели: 0м");
+    }
+
+    #[tokio::test]
+    async fn test_failed_digest_is_retried() {
+        let store = MemoryStore::default();
+        store.set_digest(ChatId(1), DigestKind::Day, Some(21 * 60)).await.unwrap();
+
+        // The token is fake, so sending fails without Telegram saying the chat is gone
+        send_due(&Bot::new("0:fake"), &store, 1715806800).await.unwrap(); // 2024-05-15 21:00 UTC
+        assert_eq!(store.get_digests().await.unwrap()[0].last_sent, None);
+    }
+}
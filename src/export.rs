@@ -0,0 +1,111 @@
+use chrono::{DateTime, FixedOffset, NaiveDate};
+use serde_json::json;
+
+use crate::{store::SessionRecord, time::DayBoundary};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    pub fn parse(text: &str) -> Option<Self> {
+        match text {
+            "csv" => Some(ExportFormat::Csv),
+            "json" => Some(ExportFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Session timestamps as RFC 3339 in the chat's UTC offset, so spreadsheets show local time
+fn local_time(timestamp: i64, boundary: DayBoundary) -> String {
+    let offset = FixedOffset::east_opt(boundary.utc_offset_minutes * 60).unwrap_or(FixedOffset::east_opt(0).unwrap());
+    DateTime::from_timestamp(timestamp, 0)
+        .map(|time| time.with_timezone(&offset).to_rfc3339())
+        .unwrap_or_default()
+}
+
+pub fn days_to_csv(days: &[(NaiveDate, i64)]) -> String {
+    let mut csv = String::from("date,seconds\n");
+    for (date, seconds) in days {
+        csv.push_str(&format!("{date},{seconds}\n"));
+    }
+    csv
+}
+
+pub fn sessions_to_csv(sessions: &[SessionRecord], boundary: DayBoundary) -> String {
+    let mut csv = String::from("id,user_id,start,end,seconds,start_trigger,stop_trigger\n");
+    for session in sessions {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{}\n",
+            session.id,
+            session.user_id.map(|id| id.to_string()).unwrap_or_default(),
+            local_time(session.start_timestamp, boundary),
+            session.end_timestamp.map(|end| local_time(end, boundary)).unwrap_or_default(),
+            session.seconds.map(|seconds| seconds.to_string()).unwrap_or_default(),
+            session.start_trigger.as_deref().unwrap_or_default(),
+            session.stop_trigger.as_deref().unwrap_or_default(),
+        ));
+    }
+    csv
+}
+
+pub fn to_json(chat_id: i64, days: &[(NaiveDate, i64)], sessions: &[SessionRecord], boundary: DayBoundary) -> String {
+    let days: Vec<_> = days.iter()
+        .map(|(date, seconds)| json!({ "date": date.to_string(), "seconds": seconds }))
+        .collect();
+    let sessions: Vec<_> = sessions.iter()
+        .map(|session| json!({
+            "id": session.id,
+            "user_id": session.user_id,
+            "start": local_time(session.start_timestamp, boundary),
+            "end": session.end_timestamp.map(|end| local_time(end, boundary)),
+            "seconds": session.seconds,
+            "start_trigger": session.start_trigger,
+            "stop_trigger": session.stop_trigger,
+        }))
+        .collect();
+    serde_json::to_string_pretty(&json!({ "chat_id": chat_id, "days": days, "sessions": sessions })).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session() -> SessionRecord {
+        SessionRecord {
+            id: 1,
+            user_id: None,
+            start_timestamp: 1714584600, // 2024-05-01 17:30 UTC
+            end_timestamp: Some(1714588200),
+            seconds: Some(3600),
+            start_trigger: Some("sticker".to_string()),
+            stop_trigger: Some("sit_sticker".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_csv() {
+        let days = [(NaiveDate::from_ymd_opt(2024, 5, 1).unwrap(), 1800), (NaiveDate::from_ymd_opt(2024, 5, 2).unwrap(), 1800)];
+        assert_eq!(days_to_csv(&days), "date,seconds\n2024-05-01,1800\n2024-05-02,1800\n");
+
+        let boundary = DayBoundary { utc_offset_minutes: 360, day_start_minutes: 0 };
+        assert_eq!(
+            sessions_to_csv(&[session()], boundary),
+            "id,user_id,start,end,seconds,start_trigger,stop_trigger\n1,,2024-05-01T23:30:00+06:00,2024-05-02T00:30:00+06:00,3600,sticker,sit_sticker\n"
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        let days = [(NaiveDate::from_ymd_opt(2024, 5, 1).unwrap(), 1800)];
+        let exported: serde_json::Value = serde_json::from_str(&to_json(5, &days, &[session()], DayBoundary::default())).unwrap();
+        assert_eq!(exported["chat_id"], 5);
+        assert_eq!(exported["days"][0]["date"], "2024-05-01");
+        assert_eq!(exported["days"][0]["seconds"], 1800);
+        assert_eq!(exported["sessions"][0]["start"], "2024-05-01T17:30:00+00:00");
+        assert_eq!(exported["sessions"][0]["user_id"], serde_json::Value::Null);
+    }
+}
@@ -0,0 +1,127 @@
+use chrono::{NaiveDate, Weekday};
+use teloxide::{prelude::*, types::ChatId};
+
+use crate::{period::{DateRange, Period}, store::{StandingStore, StoreResult}, time::format_short_duration};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GoalKind {
+    Day,
+    Week,
+}
+
+impl GoalKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GoalKind::Day => "day",
+            GoalKind::Week => "week",
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            GoalKind::Day => "Цель дня",
+            GoalKind::Week => "Цель недели",
+        }
+    }
+
+    /// The days the goal is counted over
+    fn range(&self, today: NaiveDate, week_start: Weekday) -> DateRange {
+        match self {
+            GoalKind::Day => DateRange { start: Some(today), end: Some(today) },
+            GoalKind::Week => Period::Week.resolve(today, week_start),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Goals {
+    pub day: Option<i64>,
+    pub week: Option<i64>,
+}
+
+impl Goals {
+    fn iter(&self) -> impl Iterator<Item = (GoalKind, i64)> {
+        [(GoalKind::Day, self.day), (GoalKind::Week, self.week)].into_iter()
+            .filter_map(|(kind, goal)| Some((kind, goal?)))
+    }
+}
+
+/// "1ч 20м из 2ч (67%)"
+pub fn progress(done: i64, goal: i64) -> String {
+    let percent = (done * 100 + goal / 2) / goal.max(1);
+    format!("{} из {} ({percent}%)", format_short_duration(done), format_short_duration(goal))
+}
+
+/// Progress towards the chat's goals as of `today`, one line per goal
+pub async fn progress_lines(store: &dyn StandingStore, chat_id: ChatId, today: NaiveDate) -> StoreResult<Vec<String>> {
+    let mut lines = Vec::new();
+    for (kind, goal) in store.get_goals(chat_id).await?.iter() {
+        let done = done(store, chat_id, kind, today).await?;
+        lines.push(format!("{}: {}", kind.label(), progress(done, goal)));
+    }
+    Ok(lines)
+}
+
+/// Celebration messages for goals reached as of `today` that weren't celebrated yet in their day
+/// or week. Each is returned only once.
+pub async fn celebrations(store: &dyn StandingStore, chat_id: ChatId, today: NaiveDate) -> StoreResult<Vec<String>> {
+    let mut messages = Vec::new();
+    for (kind, goal) in store.get_goals(chat_id).await?.iter() {
+        let done = done(store, chat_id, kind, today).await?;
+        let start = kind.range(today, store.get_week_start(chat_id).await?).start.unwrap_or(today);
+        if done >= goal && store.mark_goal_reached(chat_id, kind, start).await? {
+            messages.push(format!("🎉 {} выполнена: {}!", kind.label(), format_short_duration(goal)));
+        }
+    }
+    Ok(messages)
+}
+
+/// Congratulates the chat on the goals its last session reached
+pub async fn announce(bot: &Bot, store: &dyn StandingStore, chat_id: ChatId, end_timestamp: i64) -> StoreResult<()> {
+    let today = store.get_day_boundary(chat_id).await?.date_of(end_timestamp);
+    for message in celebrations(store, chat_id, today).await? {
+        bot.send_message(chat_id, message).await?;
+    }
+    Ok(())
+}
+
+async fn done(store: &dyn StandingStore, chat_id: ChatId, kind: GoalKind, today: NaiveDate) -> StoreResult<i64> {
+    let range = kind.range(today, store.get_week_start(chat_id).await?);
+    Ok(store.get_daily_totals(chat_id, range).await?.iter().map(|(_, seconds)| seconds).sum())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{memory_store::MemoryStore, store::MergePolicy};
+
+    #[test]
+    fn test_progress() {
+        assert_eq!(progress(4800, 7200), "1ч 20м из 2ч (67%)");
+        assert_eq!(progress(9000, 7200), "2ч 30м из 2ч (125%)");
+        assert_eq!(progress(0, 36000), "0м из 10ч (0%)");
+    }
+
+    #[tokio::test]
+    async fn test_celebrations() {
+        let store = MemoryStore::default();
+        let today = NaiveDate::from_ymd_opt(2024, 5, 15).unwrap();
+        store.set_goal(ChatId(1), GoalKind::Day, Some(3600)).await.unwrap();
+        store.set_goal(ChatId(1), GoalKind::Week, Some(7200)).await.unwrap();
+
+        store.import_daily_totals(ChatId(1), &[(today, 1800)], MergePolicy::Add).await.unwrap();
+        assert!(celebrations(&store, ChatId(1), today).await.unwrap().is_empty());
+        assert_eq!(progress_lines(&store, ChatId(1), today).await.unwrap(), vec!["Цель дня: 30м из 1ч (50%)", "Цель недели: 30м из 2ч (25%)"]);
+
+        store.import_daily_totals(ChatId(1), &[(today, 1800)], MergePolicy::Add).await.unwrap();
+        assert_eq!(celebrations(&store, ChatId(1), today).await.unwrap(), vec!["🎉 Цель дня выполнена: 1ч!"]);
+        // Only once a day
+        store.import_daily_totals(ChatId(1), &[(today, 600)], MergePolicy::Add).await.unwrap();
+        assert!(celebrations(&store, ChatId(1), today).await.unwrap().is_empty());
+
+        // The week goal counts the days before
+        let tomorrow = today.succ_opt().unwrap();
+        store.import_daily_totals(ChatId(1), &[(tomorrow, 3600)], MergePolicy::Add).await.unwrap();
+        assert_eq!(celebrations(&store, ChatId(1), tomorrow).await.unwrap(), vec!["🎉 Цель дня выполнена: 1ч!", "🎉 Цель недели выполнена: 2ч!"]);
+    }
+}
@@ -0,0 +1,385 @@
+use std::collections::HashMap;
+
+use chrono::{Days, Months, NaiveDate, Weekday};
+use teloxide::{prelude::*, types::ParseMode, utils::html};
+use tokio::time::{sleep, Duration};
+
+use crate::{chats, leaderboard::{self, Standing}, period::{DateRange, Period}, store::{StandingStore, Store, StoreResult}, time::{format_short_duration, DayBoundary}};
+
+/// Champions use one calendar for every chat: Monday weeks over the chats' local dates
+const WEEK_START: Weekday = Weekday::Mon;
+
+/// The boundary whose days end last: the westernmost UTC offset with the latest day start. A
+/// period is only closed once it is over there, so every chat's last local day is counted.
+const LATEST_BOUNDARY: DayBoundary = DayBoundary { utc_offset_minutes: -(14 * 60 + 59), day_start_minutes: 23 * 60 + 59 };
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TitleKind {
+    Week,
+    Month,
+    Year,
+}
+
+impl TitleKind {
+    pub const ALL: [TitleKind; 3] = [TitleKind::Week, TitleKind::Month, TitleKind::Year];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TitleKind::Week => "week",
+            TitleKind::Month => "month",
+            TitleKind::Year => "year",
+        }
+    }
+
+    pub fn parse(text: &str) -> Option<Self> {
+        match text {
+            "week" => Some(TitleKind::Week),
+            "month" => Some(TitleKind::Month),
+            "year" => Some(TitleKind::Year),
+            _ => None,
+        }
+    }
+
+    /// The last closed period on `today`
+    pub fn last_closed(&self, today: NaiveDate) -> DateRange {
+        let period = match self {
+            TitleKind::Week => Period::LastWeek,
+            TitleKind::Month => Period::LastMonth,
+            TitleKind::Year => Period::LastYear,
+        };
+        period.resolve(today, WEEK_START)
+    }
+
+    /// The period that begins on `start`
+    pub fn range(&self, start: NaiveDate) -> DateRange {
+        let end = match self {
+            TitleKind::Week => start + Days::new(6),
+            TitleKind::Month => start + Months::new(1) - Days::new(1),
+            TitleKind::Year => start + Months::new(12) - Days::new(1),
+        };
+        DateRange { start: Some(start), end: Some(end) }
+    }
+
+    /// "Чемпион недели"
+    fn title(&self) -> &'static str {
+        match self {
+            TitleKind::Week => "Чемпион недели",
+            TitleKind::Month => "Чемпион месяца",
+            TitleKind::Year => "Чемпион года",
+        }
+    }
+
+    fn heading(&self) -> &'static str {
+        match self {
+            TitleKind::Week => "Чемпионы недель:",
+            TitleKind::Month => "Чемпионы месяцев:",
+            TitleKind::Year => "Чемпионы лет:",
+        }
+    }
+
+    /// "недель: 2"
+    fn count(&self, count: usize) -> String {
+        match self {
+            TitleKind::Week => format!("недель: {count}"),
+            TitleKind::Month => format!("месяцев: {count}"),
+            TitleKind::Year => format!("лет: {count}"),
+        }
+    }
+
+    /// "неделя с 2024-04-29", "2024-05" or "2024"
+    pub fn describe_start(&self, start: NaiveDate) -> String {
+        match self {
+            TitleKind::Week => format!("неделя с {start}"),
+            TitleKind::Month => start.format("%Y-%m").to_string(),
+            TitleKind::Year => start.format("%Y").to_string(),
+        }
+    }
+}
+
+/// The chat with the best average per day of a closed week, month or year
+#[derive(Clone, Debug, PartialEq)]
+pub struct Champion {
+    pub kind: TitleKind,
+    /// First day of the period
+    pub start: NaiveDate,
+    pub chat_id: i64,
+    /// Average per day
+    pub seconds: i64,
+}
+
+/// Chats the bot is still in ranked by their average per day over a closed period, best first.
+/// Ties go to the lower chat id.
+pub async fn standings(store: &dyn StandingStore, range: DateRange) -> StoreResult<Vec<Standing>> {
+    let names: HashMap<i64, String> = store.get_chats().await?.into_iter()
+        .filter(|chat| chat.active)
+        .map(|chat| (chat.chat_id, chat.name()))
+        .collect();
+    let end = range.end.unwrap_or_default();
+    let mut standings: Vec<Standing> = store.get_chat_totals(range).await?.into_iter()
+        .filter_map(|total| Some(Standing { id: total.chat_id, name: names.get(&total.chat_id)?.clone(), seconds: total.average(range, end) }))
+        .filter(|standing| standing.seconds > 0)
+        .collect();
+    leaderboard::sort(&mut standings);
+    Ok(standings)
+}
+
+/// Records the champions of the periods closed by `today` that have none yet, going back to the
+/// first day anyone stood on the first time and to the latest recorded champion after that. Only
+/// the champions of periods that ended yesterday are left to be announced, older ones are
+/// recorded as announced. Returns the new champions, oldest first.
+pub async fn record_closed(store: &dyn StandingStore, today: NaiveDate) -> StoreResult<Vec<Champion>> {
+    let Some(first) = store.get_chat_totals(DateRange::default()).await?.iter().map(|total| total.first).min() else {
+        return Ok(Vec::new());
+    };
+    let champions = store.get_champions().await?;
+    let mut recorded = Vec::new();
+    for kind in TitleKind::ALL {
+        let latest = champions.iter().filter(|champion| champion.kind == kind).map(|champion| champion.start).max();
+        let mut range = kind.last_closed(today);
+        while let (Some(start), Some(end)) = (range.start, range.end) {
+            if end < first || latest.is_some_and(|latest| start <= latest) {
+                break;
+            }
+            if let Some(best) = standings(store, range).await?.first() {
+                let champion = Champion { kind, start, chat_id: best.id, seconds: best.seconds };
+                store.add_champion(&champion, end.succ_opt() != Some(today)).await?;
+                recorded.push(champion);
+            }
+            range = kind.last_closed(start);
+        }
+    }
+    recorded.sort_by_key(|champion| champion.start);
+    Ok(recorded)
+}
+
+/// Titles per chat, most first, then the latest champions of each kind
+pub fn render(champions: &[Champion], names: &HashMap<i64, String>) -> String {
+    let name = |chat_id: i64| names.get(&chat_id).cloned().unwrap_or_else(|| "Нет имени".to_string());
+
+    let mut titles: Vec<(i64, [usize; 3])> = Vec::new();
+    for champion in champions {
+        let index = TitleKind::ALL.iter().position(|kind| *kind == champion.kind).unwrap_or_default();
+        match titles.iter_mut().find(|(chat_id, _)| *chat_id == champion.chat_id) {
+            Some((_, counts)) => counts[index] += 1,
+            None => {
+                let mut counts = [0; 3];
+                counts[index] = 1;
+                titles.push((champion.chat_id, counts));
+            }
+        }
+    }
+    titles.sort_by_key(|(chat_id, counts)| (std::cmp::Reverse(counts.iter().sum::<usize>()), *chat_id));
+
+    let mut lines = vec!["🏆 Зал славы".to_string(), "Титулы:".to_string()];
+    for (position, (chat_id, counts)) in titles.iter().enumerate() {
+        let details: Vec<String> = TitleKind::ALL.iter().zip(counts)
+            .filter(|(_, count)| **count > 0)
+            .map(|(kind, count)| kind.count(*count))
+            .collect();
+        lines.push(format!("{}. {} — {} ({})", position + 1, name(*chat_id), counts.iter().sum::<usize>(), details.join(", ")));
+    }
+    for kind in TitleKind::ALL.iter().rev() {
+        let mut latest: Vec<&Champion> = champions.iter().filter(|champion| champion.kind == *kind).collect();
+        if latest.is_empty() {
+            continue;
+        }
+        latest.sort_by_key(|champion| std::cmp::Reverse(champion.start));
+        lines.push(format!("\n{}", kind.heading()));
+        lines.extend(latest.iter().take(10).map(|champion| format!("{} — {}, {} в день",
+                                                                  kind.describe_start(champion.start),
+                                                                  name(champion.chat_id),
+                                                                  format_short_duration(champion.seconds))));
+    }
+    lines.join("\n")
+}
+
+/// The final leaderboard of the closed period with the champion. `previous` is the leaderboard
+/// of the period before it.
+pub fn announcement(champion: &Champion, standings: &[Standing], previous: &[Standing]) -> String {
+    let title = format!("Итоги: {}", champion.kind.describe_start(champion.start));
+    let name = standings.iter().find(|standing| standing.id == champion.chat_id).map_or("Нет имени", |standing| &standing.name);
+    format!("{}\n\n🏆 {}: <b>{}</b>", leaderboard::render(&title, standings, Some(previous)), champion.kind.title(), html::escape(name))
+}
+
+/// Posts the final leaderboard to every chat on it that hasn't turned announcements off. A chat
+/// that can't be reached doesn't stop the others. Any other failure to send is returned once
+/// every chat was tried, so the champion is announced again later.
+pub async fn announce(bot: &Bot, store: &dyn StandingStore, champion: &Champion) -> StoreResult<()> {
+    let previous = standings(store, champion.kind.last_closed(champion.start)).await?;
+    let current = standings(store, champion.kind.range(champion.start)).await?;
+    let text = announcement(champion, &current, &previous);
+    let mut failed = None;
+    for standing in &current {
+        let chat_id = ChatId(standing.id);
+        match store.get_announcements(chat_id).await {
+            Ok(true) => {}
+            Ok(false) => continue,
+            Err(err) => {
+                log::warn!("Failed to check announcements of {}: {:?}", chat_id, err);
+                continue;
+            }
+        }
+        match bot.send_message(chat_id, &text).parse_mode(ParseMode::Html).await {
+            Ok(_) => {}
+            Err(err) if chats::is_unreachable(&err) => log::warn!("Chat {} can't get the champion: {err}", chat_id),
+            Err(err) => failed = Some(err),
+        }
+    }
+    match failed {
+        Some(err) => Err(err.into()),
+        None => Ok(()),
+    }
+}
+
+/// Announces the champions that haven't been yet and marks them announced. A champion that
+/// failed is tried again the next time.
+async fn announce_pending(bot: &Bot, store: &dyn StandingStore) -> StoreResult<()> {
+    for champion in store.get_unannounced_champions().await? {
+        if let Err(err) = announce(bot, store, &champion).await {
+            log::warn!("Failed to announce the champion of the {} from {}: {:?}", champion.kind.as_str(), champion.start, err);
+            continue;
+        }
+        store.mark_champion_announced(champion.kind, champion.start).await?;
+    }
+    Ok(())
+}
+
+/// Records the champions once a day, when the date changes at [`LATEST_BOUNDARY`], and every
+/// minute announces the champions that are still to be announced, so nothing is lost on a restart.
+pub async fn record_periodically(bot: Bot, store: Store) {
+    let mut checked = None;
+    loop {
+        let today = LATEST_BOUNDARY.date_of(chrono::Utc::now().timestamp());
+        if checked != Some(today) {
+            match record_closed(store.as_ref(), today).await {
+                Ok(_) => checked = Some(today),
+                Err(err) => log::warn!("Failed to record champions: {:?}", err),
+            }
+        }
+        if let Err(err) = announce_pending(&bot, store.as_ref()).await {
+            log::warn!("Failed to announce champions: {:?}", err);
+        }
+        sleep(Duration::from_secs(60)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{memory_store::MemoryStore, store::{ChatInfo, MergePolicy}};
+    use teloxide::types::ChatId;
+
+    fn date(m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, m, d).unwrap()
+    }
+
+    async fn chat(store: &MemoryStore, chat_id: i64, title: &str, active: bool, days: &[(NaiveDate, i64)]) {
+        store.save_chat(&ChatInfo { chat_id, title: Some(title.to_string()), username: None, kind: "group".to_string(), active }).await.unwrap();
+        store.import_daily_totals(ChatId(chat_id), days, MergePolicy::Add).await.unwrap();
+    }
+
+    #[test]
+    fn test_ranges() {
+        // 2024-05-15 is a Wednesday
+        assert_eq!(TitleKind::Week.last_closed(date(5, 15)), DateRange { start: Some(date(5, 6)), end: Some(date(5, 12)) });
+        // Closed on the first day of the next one
+        assert_eq!(TitleKind::Week.last_closed(date(5, 13)), DateRange { start: Some(date(5, 6)), end: Some(date(5, 12)) });
+        assert_eq!(TitleKind::Month.last_closed(date(3, 1)), DateRange { start: Some(date(2, 1)), end: Some(date(2, 29)) });
+        assert_eq!(TitleKind::Year.last_closed(date(5, 15)).start, NaiveDate::from_ymd_opt(2023, 1, 1));
+        assert_eq!(TitleKind::Week.range(date(5, 6)), TitleKind::Week.last_closed(date(5, 13)));
+        assert_eq!(TitleKind::Month.range(date(2, 1)), TitleKind::Month.last_closed(date(3, 1)));
+        assert_eq!(TitleKind::Year.range(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap()), TitleKind::Year.last_closed(date(5, 15)));
+    }
+
+    #[test]
+    fn test_latest_boundary() {
+        // Sunday 2024-05-12 is over everywhere 38 hours 58 minutes after it is over in UTC
+        let over = date(5, 13).and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp() + (38 * 60 + 58) * 60;
+        assert_eq!(LATEST_BOUNDARY.date_of(over - 1), date(5, 12));
+        assert_eq!(LATEST_BOUNDARY.date_of(over), date(5, 13));
+    }
+
+    #[tokio::test]
+    async fn test_record_closed() {
+        let store = MemoryStore::default();
+        assert!(record_closed(&store, date(5, 15)).await.unwrap().is_empty());
+
+        chat(&store, -1, "Утренние", true, &[(date(4, 29), 7000), (date(5, 7), 700)]).await;
+        chat(&store, -2, "Вечерние", true, &[(date(5, 8), 1400)]).await;
+        // Left chats can't win
+        chat(&store, -3, "Ушедшие", false, &[(date(5, 8), 9999)]).await;
+
+        let champions = record_closed(&store, date(5, 13)).await.unwrap();
+        assert_eq!(champions, vec![
+            Champion { kind: TitleKind::Month, start: date(4, 1), chat_id: -1, seconds: 7000 / 30 },
+            Champion { kind: TitleKind::Week, start: date(4, 29), chat_id: -1, seconds: 1000 },
+            Champion { kind: TitleKind::Week, start: date(5, 6), chat_id: -2, seconds: 200 },
+        ]);
+        assert!(record_closed(&store, date(5, 15)).await.unwrap().is_empty());
+        // Only the week that ended yesterday is announced, not the backfilled periods
+        assert_eq!(store.get_unannounced_champions().await.unwrap(), vec![
+            Champion { kind: TitleKind::Week, start: date(5, 6), chat_id: -2, seconds: 200 },
+        ]);
+        // Sending fails with the fake token without Telegram saying the chat is gone, so the
+        // champion is tried again
+        announce_pending(&Bot::new("0:fake"), &store).await.unwrap();
+        assert_eq!(store.get_unannounced_champions().await.unwrap().len(), 1);
+        store.mark_champion_announced(TitleKind::Week, date(5, 6)).await.unwrap();
+
+        // Only the week that closed since, recorded periods aren't looked at again
+        store.import_daily_totals(ChatId(-1), &[(date(5, 1), 7000)], MergePolicy::Add).await.unwrap();
+        store.import_daily_totals(ChatId(-2), &[(date(5, 14), 1400)], MergePolicy::Add).await.unwrap();
+        assert_eq!(record_closed(&store, date(5, 20)).await.unwrap(), vec![
+            Champion { kind: TitleKind::Week, start: date(5, 13), chat_id: -2, seconds: 200 },
+        ]);
+        assert_eq!(store.get_champions().await.unwrap().len(), 4);
+        // The week that ended on 2024-05-19 is left to be announced
+        assert_eq!(store.get_unannounced_champions().await.unwrap(), vec![
+            Champion { kind: TitleKind::Week, start: date(5, 13), chat_id: -2, seconds: 200 },
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_standings_names() {
+        let store = MemoryStore::default();
+        store.save_chat(&ChatInfo { chat_id: -1, title: None, username: Some("stand".to_string()), kind: "channel".to_string(), active: true }).await.unwrap();
+        store.import_daily_totals(ChatId(-1), &[(date(5, 6), 700)], MergePolicy::Add).await.unwrap();
+
+        let standings = standings(&store, TitleKind::Week.range(date(5, 6))).await.unwrap();
+        assert_eq!(standings, vec![Standing { id: -1, name: "@stand".to_string(), seconds: 100 }]);
+    }
+
+    #[test]
+    fn test_render() {
+        let names = HashMap::from([(-1, "Утренние".to_string()), (-2, "Вечерние".to_string())]);
+        let champions = [
+            Champion { kind: TitleKind::Week, start: date(4, 29), chat_id: -1, seconds: 1000 },
+            Champion { kind: TitleKind::Week, start: date(5, 6), chat_id: -2, seconds: 200 },
+            Champion { kind: TitleKind::Month, start: date(4, 1), chat_id: -1, seconds: 3600 },
+        ];
+        assert_eq!(render(&champions, &names), "🏆 Зал славы\n\
+                                                Титулы:\n\
+                                                1. Утренние — 2 (недель: 1, месяцев: 1)\n\
+                                                2. Вечерние — 1 (недель: 1)\n\
+                                                \n\
+                                                Чемпионы месяцев:\n\
+                                                2024-04 — Утренние, 1ч в день\n\
+                                                \n\
+                                                Чемпионы недель:\n\
+                                                неделя с 2024-05-06 — Вечерние, 3м в день\n\
+                                                неделя с 2024-04-29 — Утренние, 16м в день");
+    }
+
+    #[test]
+    fn test_announcement() {
+        let champion = Champion { kind: TitleKind::Week, start: date(5, 6), chat_id: -2, seconds: 1200 };
+        let standings = [
+            Standing { id: -2, name: "<Вечерние>".to_string(), seconds: 1200 },
+            Standing { id: -1, name: "Утренние".to_string(), seconds: 600 },
+        ];
+        let previous = [Standing { id: -1, name: "Утренние".to_string(), seconds: 300 }];
+        let text = announcement(&champion, &standings, &previous);
+        assert!(text.starts_with("<b>Итоги: неделя с 2024-05-06</b>\n🥇 1. &lt;Вечерние&gt;"), "{text}");
+        assert!(text.ends_with("\n\n🏆 Чемпион недели: <b>&lt;Вечерние&gt;</b>"), "{text}");
+    }
+}
@@ -0,0 +1,244 @@
+use std::{collections::{BTreeMap, HashMap}, sync::{Arc, Mutex}};
+
+use chrono::NaiveDate;
+use serde::Deserialize;
+use teloxide::types::ChatId;
+
+use crate::{period::DateRange, store::{MergePolicy, StandingStore, StoreResult}, time::{total_seconds_to_hms, SECONDS_PER_DAY}};
+
+/// Parsed import waiting for /import confirm
+#[derive(Clone, Debug, PartialEq)]
+pub struct PendingImport {
+    pub days: Vec<(NaiveDate, i64)>,
+    pub policy: MergePolicy,
+}
+
+pub type PendingImports = Arc<Mutex<HashMap<ChatId, PendingImport>>>;
+
+#[derive(Deserialize)]
+struct JsonDay {
+    date: String,
+    seconds: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonImport {
+    Export { days: Vec<JsonDay> },
+    Days(Vec<JsonDay>),
+}
+
+/// Parses `(date, seconds)` rows from a CSV (as written by /export, `,` or `;` separated, header
+/// optional) or JSON (the /export file or a plain array of `{"date", "seconds"}`) file.
+/// Repeated dates are summed and no day may exceed 24 hours, the result is sorted by date.
+pub fn parse_days(contents: &str) -> Result<Vec<(NaiveDate, i64)>, String> {
+    let rows = if contents.trim_start().starts_with(['{', '[']) {
+        let days = match serde_json::from_str::<JsonImport>(contents).map_err(|e| format!("JSON: {e}"))? {
+            JsonImport::Export { days } => days,
+            JsonImport::Days(days) => days,
+        };
+        days.into_iter()
+            .map(|day| NaiveDate::parse_from_str(&day.date, "%Y-%m-%d").map(|date| (date, day.seconds)).map_err(|_| format!("не понял дату {}", day.date)))
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        parse_csv(contents)?
+    };
+
+    let mut days: BTreeMap<NaiveDate, i64> = BTreeMap::new();
+    for (date, seconds) in rows {
+        if seconds < 0 {
+            return Err(format!("{date}: отрицательное время"));
+        }
+        let total = days.entry(date).or_default();
+        *total = total.checked_add(seconds).filter(|total| *total <= SECONDS_PER_DAY)
+            .ok_or_else(|| format!("{date}: больше суток"))?;
+    }
+    Ok(days.into_iter().collect())
+}
+
+fn parse_csv(contents: &str) -> Result<Vec<(NaiveDate, i64)>, String> {
+    let mut rows = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let mut fields = line.split([',', ';']).map(str::trim);
+        let (Some(date), Some(seconds)) = (fields.next(), fields.next()) else {
+            return Err(format!("строка {}: нужно два поля, дата и секунды", index + 1));
+        };
+        let Ok(date) = NaiveDate::parse_from_str(date, "%Y-%m-%d") else {
+            if index == 0 {
+                continue; // header
+            }
+            return Err(format!("строка {}: не понял дату {date}", index + 1));
+        };
+        let seconds = seconds.parse::<i64>().map_err(|_| format!("строка {}: не понял секунды {seconds}", index + 1))?;
+        rows.push((date, seconds));
+    }
+    Ok(rows)
+}
+
+/// What an import would change, computed before anything is written
+#[derive(Debug, PartialEq)]
+pub struct ImportSummary {
+    pub days: usize,
+    pub first: Option<NaiveDate>,
+    pub last: Option<NaiveDate>,
+    pub new_days: usize,
+    pub changed_days: usize,
+    pub unchanged_days: usize,
+    pub seconds_before: i64,
+    pub seconds_after: i64,
+}
+
+impl ImportSummary {
+    /// `existing` are the chat's current rows for the imported dates
+    pub fn new(import: &PendingImport, existing: &[(NaiveDate, i64)]) -> Self {
+        let existing: HashMap<NaiveDate, i64> = existing.iter().copied().collect();
+        let mut summary = ImportSummary {
+            days: import.days.len(),
+            first: import.days.first().map(|(date, _)| *date),
+            last: import.days.last().map(|(date, _)| *date),
+            new_days: 0,
+            changed_days: 0,
+            unchanged_days: 0,
+            seconds_before: existing.values().sum(),
+            seconds_after: 0,
+        };
+        for (date, seconds) in &import.days {
+            let before = existing.get(date).copied();
+            let after = match import.policy {
+                MergePolicy::Replace => *seconds,
+                MergePolicy::Add => before.unwrap_or(0) + seconds,
+            };
+            match before {
+                None => summary.new_days += 1,
+                Some(before) if before == after => summary.unchanged_days += 1,
+                Some(_) => summary.changed_days += 1,
+            }
+            summary.seconds_after += after;
+        }
+        summary
+    }
+
+    pub fn describe(&self, policy: MergePolicy) -> String {
+        let range = match (self.first, self.last) {
+            (Some(first), Some(last)) => format!(" ({first} — {last})"),
+            _ => String::new(),
+        };
+        format!(
+            "Импорт: {} дней{range}, режим: {}\nНовых дней: {}, изменится: {}, без изменений: {}\nЗа эти дни было: {}\nСтанет: {}",
+            self.days,
+            policy.name(),
+            self.new_days,
+            self.changed_days,
+            self.unchanged_days,
+            total_seconds_to_hms(self.seconds_before),
+            total_seconds_to_hms(self.seconds_after),
+        )
+    }
+}
+
+pub async fn summarize(store: &dyn StandingStore, chat_id: ChatId, import: &PendingImport) -> StoreResult<ImportSummary> {
+    let range = DateRange { start: import.days.first().map(|(date, _)| *date), end: import.days.last().map(|(date, _)| *date) };
+    let existing: Vec<(NaiveDate, i64)> = store.get_daily_totals(chat_id, range).await?
+        .into_iter()
+        .filter(|(date, _)| import.days.binary_search_by_key(date, |(date, _)| *date).is_ok())
+        .collect();
+    Ok(ImportSummary::new(import, &existing))
+}
+
+/// `standing_bot import <chat_id> <file> [add|replace] [--apply]`: prints the summary and writes
+/// only when --apply is given
+pub async fn run_cli(store: &dyn StandingStore, args: &[String]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    const USAGE: &str = "usage: standing_bot import <chat_id> <file> [add|replace] [--apply]";
+    let mut positional = Vec::new();
+    let mut policy = MergePolicy::Add;
+    let mut apply = false;
+    for arg in args {
+        if arg == "--apply" {
+            apply = true;
+        } else if let Some(parsed) = MergePolicy::parse(arg) {
+            policy = parsed;
+        } else {
+            positional.push(arg);
+        }
+    }
+    let [chat_id, path] = positional.as_slice() else {
+        return Err(USAGE.into());
+    };
+    let chat_id = ChatId(chat_id.parse().map_err(|_| USAGE)?);
+    let days = parse_days(&std::fs::read_to_string(path)?)?;
+    let import = PendingImport { days, policy };
+
+    println!("{}", summarize(store, chat_id, &import).await?.describe(policy));
+    if apply {
+        store.import_daily_totals(chat_id, &import.days, policy).await?;
+        println!("Записано.");
+    } else {
+        println!("Ничего не записано, добавьте --apply чтобы записать.");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory_store::MemoryStore;
+
+    fn date(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 5, d).unwrap()
+    }
+
+    #[test]
+    fn test_parse_csv() {
+        assert_eq!(parse_days("date,seconds\n2024-05-02,100\n2024-05-01,50\n\n2024-05-02,20\n").unwrap(), vec![(date(1), 50), (date(2), 120)]);
+        assert_eq!(parse_days("2024-05-01; 60").unwrap(), vec![(date(1), 60)]);
+        assert!(parse_days("2024-05-01,abc").is_err());
+        assert!(parse_days("2024-05-01,10\nyesterday,10").is_err());
+        assert!(parse_days("2024-05-01,-10").is_err());
+        assert!(parse_days("2024-05-01,86401").is_err());
+        assert!(parse_days("2024-05-01,86000\n2024-05-01,1000").is_err());
+        assert!(parse_days("2024-05-01,9223372036854775807\n2024-05-01,1").is_err());
+        assert_eq!(parse_days("2024-05-01,86400").unwrap(), vec![(date(1), 86400)]);
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let export = r#"{"chat_id": 1, "days": [{"date": "2024-05-01", "seconds": 30}], "sessions": []}"#;
+        assert_eq!(parse_days(export).unwrap(), vec![(date(1), 30)]);
+        assert_eq!(parse_days(r#"[{"date": "2024-05-02", "seconds": 40}]"#).unwrap(), vec![(date(2), 40)]);
+        assert!(parse_days(r#"[{"date": "2024-05-02"}]"#).is_err());
+    }
+
+    #[test]
+    fn test_summary() {
+        let existing = [(date(1), 100), (date(2), 200)];
+        let days = vec![(date(1), 100), (date(2), 50), (date(3), 10)];
+
+        let replace = ImportSummary::new(&PendingImport { days: days.clone(), policy: MergePolicy::Replace }, &existing);
+        assert_eq!((replace.new_days, replace.changed_days, replace.unchanged_days), (1, 1, 1));
+        assert_eq!((replace.seconds_before, replace.seconds_after), (300, 160));
+
+        let add = ImportSummary::new(&PendingImport { days, policy: MergePolicy::Add }, &existing);
+        assert_eq!((add.new_days, add.changed_days, add.unchanged_days), (1, 2, 0));
+        assert_eq!((add.seconds_before, add.seconds_after), (300, 460));
+        assert_eq!((add.first, add.last), (Some(date(1)), Some(date(3))));
+    }
+
+    #[tokio::test]
+    async fn test_import() {
+        let store = MemoryStore::default();
+        store.import_daily_totals(ChatId(1), &[(date(1), 100), (date(2), 200)], MergePolicy::Add).await.unwrap();
+
+        let import = PendingImport { days: vec![(date(2), 50), (date(3), 10)], policy: MergePolicy::Replace };
+        let summary = summarize(&store, ChatId(1), &import).await.unwrap();
+        assert_eq!(summary.seconds_before, 200);
+        // Nothing is written by the summary
+        assert_eq!(store.get_daily_totals(ChatId(1), DateRange::default()).await.unwrap(), vec![(date(1), 100), (date(2), 200)]);
+
+        store.import_daily_totals(ChatId(1), &import.days, import.policy).await.unwrap();
+        assert_eq!(store.get_daily_totals(ChatId(1), DateRange::default()).await.unwrap(), vec![(date(1), 100), (date(2), 50), (date(3), 10)]);
+    }
+}
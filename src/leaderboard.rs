@@ -0,0 +1,128 @@
+use teloxide::utils::html;
+
+use crate::time::total_seconds_to_hms;
+
+const MEDALS: [&str; 3] = ["🥇", "🥈", "🥉"];
+
+/// One row of a leaderboard: a chat or a member and their time
+#[derive(Clone, Debug, PartialEq)]
+pub struct Standing {
+    pub id: i64,
+    pub name: String,
+    pub seconds: i64,
+}
+
+/// Sorts best first, ties keep the order of ids so the same data always renders the same.
+pub fn sort(standings: &mut [Standing]) {
+    standings.sort_by_key(|standing| (std::cmp::Reverse(standing.seconds), standing.id));
+}
+
+/// Positions of sorted standings, equal times share a position: 1, 2, 2, 4.
+fn positions(standings: &[Standing]) -> Vec<usize> {
+    let mut positions: Vec<usize> = Vec::with_capacity(standings.len());
+    for (index, standing) in standings.iter().enumerate() {
+        let position = match index {
+            0 => 1,
+            _ if standings[index - 1].seconds == standing.seconds => positions[index - 1],
+            _ => index + 1,
+        };
+        positions.push(position);
+    }
+    positions
+}
+
+fn position_change(position: usize, id: i64, previous: &[Standing]) -> String {
+    let previous_positions = positions(previous);
+    match previous.iter().position(|standing| standing.id == id) {
+        None => " 🆕".to_string(),
+        Some(index) => {
+            let before = previous_positions[index];
+            match before.cmp(&position) {
+                std::cmp::Ordering::Greater => format!(" ▲{}", before - position),
+                std::cmp::Ordering::Less => format!(" ▼{}", position - before),
+                std::cmp::Ordering::Equal => String::new(),
+            }
+        }
+    }
+}
+
+/// Renders standings as an HTML message: position, medal for the top three, each row's gap to the
+/// leader and to the row above, and with `previous` (the same leaderboard for the previous period)
+/// the change in position.
+pub fn render(title: &str, standings: &[Standing], previous: Option<&[Standing]>) -> String {
+    let mut standings = standings.to_vec();
+    sort(&mut standings);
+    // Nobody to compare with, everyone would be new
+    let previous = previous.filter(|previous| !previous.is_empty()).map(|previous| {
+        let mut previous = previous.to_vec();
+        sort(&mut previous);
+        previous
+    });
+    let positions = positions(&standings);
+
+    let mut lines = vec![format!("<b>{}</b>", html::escape(title))];
+    for (index, standing) in standings.iter().enumerate() {
+        let position = positions[index];
+        let medal = MEDALS.get(position - 1).map(|medal| format!("{medal} ")).unwrap_or_default();
+        let change = previous.as_ref().map(|previous| position_change(position, standing.id, previous)).unwrap_or_default();
+        lines.push(format!("{medal}{position}. {} — <b>{}</b>{change}", html::escape(&standing.name), total_seconds_to_hms(standing.seconds)));
+
+        let leader_gap = standings[0].seconds - standing.seconds;
+        if leader_gap > 0 {
+            let above = &standings[index - 1];
+            let next_gap = above.seconds - standing.seconds;
+            let mut gaps = format!("      −{} до лидера", total_seconds_to_hms(leader_gap));
+            if next_gap > 0 && next_gap != leader_gap {
+                gaps.push_str(&format!(", −{} до {}-го места", total_seconds_to_hms(next_gap), positions[index - 1]));
+            }
+            lines.push(gaps);
+        }
+    }
+    if previous.is_some() {
+        lines.push("\n▲▼ — изменение места с прошлого периода".to_string());
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn standing(id: i64, name: &str, seconds: i64) -> Standing {
+        Standing { id, name: name.to_string(), seconds }
+    }
+
+    #[test]
+    fn test_positions() {
+        let mut standings = vec![standing(1, "a", 60), standing(2, "b", 120), standing(3, "c", 60), standing(4, "d", 10)];
+        sort(&mut standings);
+        assert_eq!(standings.iter().map(|standing| standing.id).collect::<Vec<_>>(), vec![2, 1, 3, 4]);
+        assert_eq!(positions(&standings), vec![1, 2, 2, 4]);
+    }
+
+    #[test]
+    fn test_render() {
+        let standings = [standing(1, "<Chat>", 3600), standing(2, "Second", 7200), standing(3, "Third", 1800), standing(4, "Fourth", 1800)];
+        assert_eq!(render("Топ", &standings, None), "<b>Топ</b>
+🥇 1. Second — <b>2 часов 0 минут 0 секунд</b>
+🥈 2. &lt;Chat&gt; — <b>1 часов 0 минут 0 секунд</b>
+      −1 часов 0 минут 0 секунд до лидера
+🥉 3. Third — <b>0 часов 30 минут 0 секунд</b>
+      −1 часов 30 минут 0 секунд до лидера, −0 часов 30 минут 0 секунд до 2-го места
+🥉 3. Fourth — <b>0 часов 30 минут 0 секунд</b>
+      −1 часов 30 минут 0 секунд до лидера");
+    }
+
+    #[test]
+    fn test_position_change() {
+        let standings = [standing(1, "One", 300), standing(2, "Two", 200), standing(3, "Three", 100)];
+        let previous = [standing(2, "Two", 50), standing(1, "One", 10)];
+        let rendered = render("Топ", &standings, Some(&previous));
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert!(lines[1].ends_with("</b> ▲1"));
+        assert!(lines[2].ends_with("</b> ▼1"));
+        assert!(lines[4].ends_with("</b> 🆕"));
+        assert_eq!(lines.last(), Some(&"▲▼ — изменение места с прошлого периода"));
+        assert_eq!(render("Топ", &standings, Some(&[])), render("Топ", &standings, None));
+    }
+}
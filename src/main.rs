@@ -1,20 +1,46 @@
 mod periodic_updates;
+mod store;
+#[cfg(test)]
+mod memory_store;
 mod total_management;
 mod time;
 mod sticker_handling;
 mod message_handling;
 mod openrouter;
-
-use std::{ops::Deref, sync::Arc};
+mod period;
+mod chats;
+mod export;
+mod leaderboard;
+mod streak;
+mod goal;
+mod digest;
+mod chart;
+mod stats;
+mod records;
+mod compare;
+mod patterns;
+mod hall_of_fame;
+mod import;
+
+use std::collections::HashMap;
 
 use periodic_updates::update_periodically;
 
-use serde::Serialize;
-use sqlx::{Error, Pool, SqlitePool};
-use sticker_handling::{get_total, send_and_update_total};
+use sticker_handling::finish_session_and_send_total;
+#[cfg(not(debug_assertions))]
+use teloxide::update_listeners::webhooks;
 use teloxide::{
-    dispatching::{dialogue::{self, serializer::Json, ErasedStorage, SqliteStorage, Storage}, MessageFilterExt, UpdateHandler}, prelude::*, types::{ButtonRequest, ChatMemberStatus, KeyboardButton, KeyboardButtonRequestChat, KeyboardMarkup, MessageChatShared, MessageKind, RequestId}, update_listeners::webhooks, utils::command::BotCommands
+    dispatching::{dialogue::{self, serializer::Json, ErasedStorage, SqliteStorage, Storage}, MessageFilterExt, UpdateHandler}, net::Download, prelude::*, types::{ButtonRequest, InputFile, KeyboardButton, KeyboardButtonRequestChat, KeyboardMarkup, KeyboardRemove, MessageChatShared, MessageKind, RequestId}, utils::{command::BotCommands, html}
 };
+use chats::KnownChats;
+use compare::PendingCompares;
+use digest::DigestKind;
+use export::ExportFormat;
+use goal::GoalKind;
+use import::{PendingImport, PendingImports};
+use leaderboard::Standing;
+use period::{DateRange, Period};
+use store::{Adjustment, ChatTotal, MergePolicy, Store, StoreResult, StopTrigger};
 use total_management::Total;
 
 type MyDialogue = Dialogue<State, ErasedStorage<State>>;
@@ -54,8 +80,8 @@ enum Command {
     Start,
     /// [минуты] ОТМЕНИТЬ СТОЯНИЕ
     Cancel(String),
-    /// РАНКИНГ
-    Rankings,
+    /// [week | last-week | 2026-09 | 2026 | 30d | 2026-01-01..2026-03-31] РАНКИНГ
+    Rankings(String),
     /// РАНКИНГ ПО МЕСЯЦАМ
     #[command(alias = "avgmonth")]
     RankingsMonth,
@@ -65,8 +91,8 @@ enum Command {
     /// РАНКИНГ ПО ГОДАМ
     #[command(alias = "avgyear")]
     RankingsYear,
-    /// ОБЩЕЕ ВРЕМЯ
-    Total,
+    /// [week | last-week | 2026-09 | 2026 | 30d | 2026-01-01..2026-03-31] ОБЩЕЕ ВРЕМЯ
+    Total(String),
     /// ОБЩЕЕ ВРЕМЯ ЗА МЕСЯЦ
     #[command(alias = "month")]
     TotalMonth,
@@ -75,7 +101,47 @@ enum Command {
     TotalWeek,
     /// ОБЩЕЕ ВРЕМЯ ЗА ГОД
     #[command(alias = "year")]
-    TotalYear
+    TotalYear,
+    /// [week|month|7d|30d] ТОП УЧАСТНИКОВ ЧАТА
+    Top(String),
+    /// [min 30m] СЕРИЯ ДНЕЙ ПОДРЯД
+    Streak(String),
+    /// РЕКОРДЫ: САМОЕ ДОЛГОЕ СТОЯНИЕ, ЛУЧШИЕ ДЕНЬ, НЕДЕЛЯ И МЕСЯЦ
+    Records,
+    /// ЗАЛ СЛАВЫ: ЧЕМПИОНЫ ПРОШЛЫХ НЕДЕЛЬ, МЕСЯЦЕВ И ЛЕТ
+    HallOfFame,
+    /// [on | off] ИТОГИ СРЕДИ ЧАТОВ В КОНЦЕ НЕДЕЛИ, МЕСЯЦА И ГОДА
+    Announcements(String),
+    /// [2h | week 10h | off | week off] ЦЕЛЬ НА ДЕНЬ ИЛИ НЕДЕЛЮ
+    Goal(String),
+    /// [21:00 | week 21:00 | off | week off] ИТОГИ ДНЯ ИЛИ НЕДЕЛИ ПО РАСПИСАНИЮ
+    Digest(String),
+    /// [week|month|year|30d|2024-01-01..2024-03-31] СТАТИСТИКА: СРЕДНЕЕ, МЕДИАНА, ЛУЧШИЙ ДЕНЬ
+    Stats(String),
+    /// [week|month|year|30d] КОГДА СТОИМ: ПО ЧАСАМ И ДНЯМ НЕДЕЛИ
+    Patterns(String),
+    /// [7d|30d|year] ГРАФИК ПО ДНЯМ
+    Chart(String),
+    /// [@чат | id чата] [week|month|year|30d] СРАВНЕНИЕ С ДРУГИМ ЧАТОМ
+    Compare(String),
+    /// [@чат | id чата] ТЕПЛОВАЯ КАРТА ЗА ГОД, МОЖНО СРАВНИТЬ С ДРУГИМ ЧАТОМ
+    Heatmap(String),
+    /// [csv|json] [all|week|month|year|30d|2024-01-01..2024-03-31] ВЫГРУЗКА ИСТОРИИ ЧАТА
+    Export(String),
+    /// [confirm|cancel] ИМПОРТ ИСТОРИИ: CSV ИЛИ JSON ФАЙЛ С ПОДПИСЬЮ /import [add|replace]
+    Import(String),
+    /// [reopen] ОТМЕНИТЬ ПОСЛЕДНЕЕ СТОЯНИЕ (С reopen ОНО ПРОДОЛЖИТСЯ)
+    Undo(String),
+    /// [+15m | -1h] [вчера | 2026-10-01] [причина] ПОПРАВИТЬ ВРЕМЯ ЗА ДЕНЬ
+    Adjust(String),
+    /// ПОСЛЕДНИЕ ПОПРАВКИ ВРЕМЕНИ
+    Adjustments,
+    /// [+6 | +05:30] ЧАСОВОЙ ПОЯС ЧАТА
+    Timezone(String),
+    /// [04:00] НАЧАЛО ДНЯ
+    DayStart(String),
+    /// [monday | sunday] ПЕРВЫЙ ДЕНЬ НЕДЕЛИ
+    WeekStart(String)
 }
 
 #[tokio::main]
@@ -83,16 +149,32 @@ async fn main() {
     pretty_env_logger::init();
     log::info!("Starting bot...");
 
-    let bot = Bot::from_env();
     let path = "dialogues.sqlite";
 
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("import") {
+        let total_manager = Total::connect(path).await.expect("Failed to open the database");
+        if let Err(e) = import::run_cli(total_manager.as_ref(), &args[1..]).await {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let bot = Bot::from_env();
+
     let storage: MyStorage = SqliteStorage::open(path, Json).await.unwrap().erase();
 
-    let total_manager = Total::create_table(path).await.unwrap();
+    let total_manager: Store = Total::connect(path).await.expect("Failed to open the database");
     let tx = update_periodically(bot.clone()).await;
+    tokio::spawn(digest::send_periodically(bot.clone(), total_manager.clone()));
+    tokio::spawn(hall_of_fame::record_periodically(bot.clone(), total_manager.clone()));
+    let pending_imports = PendingImports::default();
+    let pending_compares = PendingCompares::default();
+    let known_chats = KnownChats::default();
 
     let mut dispatcher = Dispatcher::builder(bot.clone(), schema())
-        .dependencies(dptree::deps![storage,tx,total_manager.clone()])
+        .dependencies(dptree::deps![storage,tx,total_manager.clone(),pending_imports,pending_compares,known_chats])
         .enable_ctrlc_handler()
         .build();
 
@@ -122,28 +204,56 @@ fn schema() -> UpdateHandler<Box<dyn std::error::Error + Send + Sync + 'static>>
 
     let command_handler = teloxide::filter_command::<Command, _>()
         .branch(case![Command::Help].endpoint(help))
-        .branch(case![Command::Rankings].endpoint(rankings))
-        .branch(case![Command::RankingsMonth].endpoint(rankings_month))
-        .branch(case![Command::RankingsWeek].endpoint(rankings_week))
-        .branch(case![Command::RankingsYear].endpoint(rankings_year))
+        .branch(case![Command::Rankings(period)].endpoint(rankings))
+        .branch(case![Command::RankingsMonth].endpoint(|bot: Bot, msg: Message, total_manager: Store| rankings(bot, msg, "month".to_string(), total_manager)))
+        .branch(case![Command::RankingsWeek].endpoint(|bot: Bot, msg: Message, total_manager: Store| rankings(bot, msg, "week".to_string(), total_manager)))
+        .branch(case![Command::RankingsYear].endpoint(|bot: Bot, msg: Message, total_manager: Store| rankings(bot, msg, "year".to_string(), total_manager)))
         .branch(case![Command::Start].endpoint(start))
         .branch(case![Command::Cancel(time)].endpoint(cancel))
-        .branch(case![Command::Total].endpoint(total))
-        .branch(case![Command::TotalMonth].endpoint(total_month))
-        .branch(case![Command::TotalWeek].endpoint(total_week))
-        .branch(case![Command::TotalYear].endpoint(total_year));
+        .branch(case![Command::Total(period)].endpoint(total))
+        .branch(case![Command::TotalMonth].endpoint(|bot: Bot, msg: Message, total_manager: Store| total(bot, msg, "month".to_string(), total_manager)))
+        .branch(case![Command::TotalWeek].endpoint(|bot: Bot, msg: Message, total_manager: Store| total(bot, msg, "week".to_string(), total_manager)))
+        .branch(case![Command::TotalYear].endpoint(|bot: Bot, msg: Message, total_manager: Store| total(bot, msg, "year".to_string(), total_manager)))
+        .branch(case![Command::Top(period)].endpoint(top))
+        .branch(case![Command::Streak(args)].endpoint(streak))
+        .branch(case![Command::Records].endpoint(records))
+        .branch(case![Command::HallOfFame].endpoint(hall_of_fame))
+        .branch(case![Command::Announcements(value)].endpoint(announcements))
+        .branch(case![Command::Goal(args)].endpoint(goal))
+        .branch(case![Command::Digest(args)].endpoint(digest))
+        .branch(case![Command::Stats(args)].endpoint(stats))
+        .branch(case![Command::Patterns(args)].endpoint(patterns))
+        .branch(case![Command::Chart(args)].endpoint(chart))
+        .branch(case![Command::Compare(args)].endpoint(compare))
+        .branch(case![Command::Heatmap(args)].endpoint(heatmap))
+        .branch(case![Command::Export(args)].endpoint(export))
+        .branch(case![Command::Import(args)].endpoint(import))
+        .branch(case![Command::Undo(args)].endpoint(undo))
+        .branch(case![Command::Adjust(args)].endpoint(adjust))
+        .branch(case![Command::Adjustments].endpoint(adjustments))
+        .branch(case![Command::Timezone(offset)].endpoint(timezone))
+        .branch(case![Command::DayStart(time)].endpoint(day_start))
+        .branch(case![Command::WeekStart(day)].endpoint(week_start));
+
+    let import_handler = Message::filter_document()
+        .filter(|msg: Message| msg.caption().is_some_and(|caption| caption.starts_with("/import")))
+        .endpoint(import_document);
 
     let message_handler = Update::filter_message()
         .inspect(|u: Update| {
             log::info!("{u:#?}");
         })
         .branch(command_handler.clone())
+        .branch(import_handler.clone())
         .branch(dptree::filter_map(|message: Message| {
             match message.kind {
                 MessageKind::ChatShared(x) => Some(x),
                 _ => None,
             }
         }).endpoint(chat_shared))
+        .branch(Message::filter_sticker()
+                .filter(|msg: Message| msg.chat.is_group() || msg.chat.is_supergroup())
+                .endpoint(sticker_handling::member_sticker_handler))
         .branch(case![State::StandingChoice { chat_id }]
                 .endpoint(message_handling::standing_choice))
         .branch(case![State::ReceiveStandingCommand { chat_id , timestamp }].endpoint(message_handling::receive_sit_command))
@@ -151,6 +261,7 @@ fn schema() -> UpdateHandler<Box<dyn std::error::Error + Send + Sync + 'static>>
 
     let channel_handler = Update::filter_channel_post()
         .branch(command_handler.clone())
+        .branch(import_handler)
         .branch(
             Message::filter_sticker()
                 .inspect(|u: Message| {
@@ -160,288 +271,645 @@ fn schema() -> UpdateHandler<Box<dyn std::error::Error + Send + Sync + 'static>>
                 .endpoint(sticker_handling::start_standing_handler))
         .branch(Message::filter_text().branch(case![State::ReceiveStandingCommand { chat_id , timestamp }].endpoint(message_handling::stop_standing)));
 
-    dialogue::enter::<Update, ErasedStorage<State>, State, _>()
-        .branch(channel_handler)
-        .branch(message_handler)
+    dptree::entry()
+        .inspect_async(chats::remember_chat)
+        .branch(Update::filter_my_chat_member().endpoint(chats::my_chat_member))
+        .branch(dialogue::enter::<Update, ErasedStorage<State>, State, _>()
+                .branch(channel_handler)
+                .branch(message_handler))
 }
 
-async fn rankings(bot: Bot, msg: Message, total_manager: Arc<Total>) -> HandlerResult {
-    let averages = total_manager.get_average_total_per_day_by_chat().await?;
-    let mut messages = Vec::new();
-
-    let mut winning_chat: Option<(i64, i64)> = None;
-
-    for (chat_id, average) in averages.iter() {
-        let chat = bot.get_chat(ChatId(*chat_id)).await?;
-        let chat_name = chat.title().unwrap_or_else(|| chat.username().unwrap_or("Нет имени"));
-        let average_seconds = average.unwrap_or(0) as i64;
-        messages.push(format!("Чат: {}, Среднее стояние: \n<b>{}</b>", chat_name, time::total_seconds_to_hms(average_seconds)));
+async fn rankings(bot: Bot, msg: Message, period: String, total_manager: Store) -> HandlerResult {
+    send_chat_totals(&bot, &msg, &period, total_manager, true).await
+}
 
-        if let Some((_, current_winning_average)) = winning_chat {
-            if average_seconds > current_winning_average {
-                winning_chat = Some((*chat_id, average_seconds));
-            }
-        } else {
-            winning_chat = Some((*chat_id, average_seconds));
-        }
-    }
+async fn total(bot: Bot, msg: Message, period: String, total_manager: Store) -> HandlerResult {
+    send_chat_totals(&bot, &msg, &period, total_manager, false).await
+}
 
-    if let Some((chat_id, average)) = winning_chat {
-        let chat = bot.get_chat(ChatId(chat_id)).await?;
-        let chat_name = chat.title().unwrap_or_else(|| chat.username().unwrap_or("Нет имени"));
-        messages.push(format!("
-🏆 <b>Победитель:</b> {} со средним стоянием: <b>{}</b> 🏆", chat_name, time::total_seconds_to_hms(average)));
+/// Every chat's standing over the period, as the average per day for /rankings or the sum for
+/// /total. The period is resolved in the calendar of the chat that asked.
+async fn send_chat_totals(bot: &Bot, msg: &Message, period: &str, total_manager: Store, average: bool) -> HandlerResult {
+    let Some(period) = Period::parse(period) else {
+        bot.send_message(msg.chat.id, "Не понял период. Примеры: week, last-week, 2026-09, 2026, 30d, 2026-01-01..2026-03-31").await?;
+        return Ok(());
+    };
+    let boundary = total_manager.get_day_boundary(msg.chat.id).await?;
+    let today = boundary.date_of(msg.date.timestamp());
+    let week_start = total_manager.get_week_start(msg.chat.id).await?;
+    let range = period.resolve(today, week_start);
+    let totals = total_manager.get_chat_totals(range).await?;
+    if totals.is_empty() {
+        bot.send_message(msg.chat.id, format!("Никто не стоял {}.", period.describe())).await?;
+        return Ok(());
     }
-
-    bot.send_message(msg.chat.id, messages.join("\n"))
+    let value = |chat_total: ChatTotal, range: DateRange| if average { chat_total.average(range, today) } else { chat_total.seconds };
+
+    let chat_ids: Vec<i64> = totals.iter().map(|chat_total| chat_total.chat_id).collect();
+    let mut names = chats::chat_names(bot, &total_manager, chat_ids).await?;
+    let standings: Vec<Standing> = totals.iter()
+        .filter_map(|chat_total| Some(Standing { id: chat_total.chat_id, name: names.remove(&chat_total.chat_id)?, seconds: value(*chat_total, range) }))
+        .collect();
+    // Chats the bot has left aren't in the standings, so they don't take places before either
+    let inactive: Vec<i64> = total_manager.get_chats().await?.into_iter()
+        .filter(|chat| !chat.active)
+        .map(|chat| chat.chat_id)
+        .collect();
+    let previous = match period.previous(today, week_start) {
+        Some(range) => Some(total_manager.get_chat_totals(range).await?.iter()
+            .filter(|chat_total| !inactive.contains(&chat_total.chat_id))
+            .map(|chat_total| Standing { id: chat_total.chat_id, name: String::new(), seconds: value(*chat_total, range) })
+            .collect::<Vec<_>>()),
+        None => None,
+    };
+
+    let title = if average { "Среднее стояние за день" } else { "Всего постояли" };
+    bot.send_message(msg.chat.id, leaderboard::render(&format!("{title} {}", period.describe()), &standings, previous.as_deref()))
        .parse_mode(teloxide::types::ParseMode::Html)
        .await?;
 
     Ok(())
 }
 
-async fn rankings_month(bot: Bot, msg: Message, total_manager: Arc<Total>) -> HandlerResult {
-    let averages = total_manager.get_average_total_per_month_by_chat().await?;
-    let mut messages = Vec::new();
-
-    let mut winning_chat: Option<(i64, i64)> = None;
-
-    for (chat_id, average) in averages.iter() {
-        let chat = bot.get_chat(ChatId(*chat_id)).await?;
-        let chat_name = chat.title().unwrap_or_else(|| chat.username().unwrap_or("Нет имени"));
-        let average_seconds = average.unwrap_or(0) as i64;
-        messages.push(format!("Чат: {}, Среднее стояние в месяце: \n<b>{}</b>", chat_name, time::total_seconds_to_hms(average_seconds)));
-
-        if let Some((_, current_winning_average)) = winning_chat {
-            if average_seconds > current_winning_average {
-                winning_chat = Some((*chat_id, average_seconds));
-            }
-        } else {
-            winning_chat = Some((*chat_id, average_seconds));
-        }
+async fn top(bot: Bot, msg: Message, period: String, total_manager: Store) -> HandlerResult {
+    let chat_id = msg.chat.id;
+    let Some(period) = Period::parse(period.trim()) else {
+        bot.send_message(chat_id, "Не понял период. Примеры: week, month, 7d, 30d, 2026-01-01..2026-03-31").await?;
+        return Ok(());
+    };
+    let today = total_manager.get_day_boundary(chat_id).await?.date_of(msg.date.timestamp());
+    let range = period.resolve(today, total_manager.get_week_start(chat_id).await?);
+    let totals = total_manager.get_member_totals(chat_id, range).await?;
+    if totals.is_empty() {
+        let text = match period {
+            Period::All => "В этом чате ещё никто не стоял.".to_string(),
+            period => format!("Никто не стоял {}.", period.describe()),
+        };
+        bot.send_message(chat_id, text).await?;
+        return Ok(());
     }
 
-    if let Some((chat_id, average)) = winning_chat {
-        let chat = bot.get_chat(ChatId(chat_id)).await?;
-        let chat_name = chat.title().unwrap_or_else(|| chat.username().unwrap_or("Нет имени"));
-        messages.push(format!("
-🏆 <b>Победитель месяца:</b> {} со средним стоянием: <b>{}</b> 🏆", chat_name, time::total_seconds_to_hms(average)));
-    }
+    let standings: Vec<Standing> = totals.into_iter()
+        .map(|(user_id, name, seconds)| Standing { id: user_id, name, seconds })
+        .collect();
 
-    bot.send_message(msg.chat.id, messages.join("\n"))
+    let title = match period {
+        Period::All => "Топ чата".to_string(),
+        period => format!("Топ чата {}", period.describe()),
+    };
+    bot.send_message(chat_id, leaderboard::render(&title, &standings, None))
        .parse_mode(teloxide::types::ParseMode::Html)
        .await?;
 
     Ok(())
 }
 
-async fn rankings_week(bot: Bot, msg: Message, total_manager: Arc<Total>) -> HandlerResult {
-    let averages = total_manager.get_average_total_per_week_by_chat().await?;
-    let mut messages = Vec::new();
-
-    let mut winning_chat: Option<(i64, i64)> = None;
-
-    for (chat_id, average) in averages.iter() {
-        let chat = bot.get_chat(ChatId(*chat_id)).await?;
-        let chat_name = chat.title().unwrap_or_else(|| chat.username().unwrap_or("Нет имени"));
-        let average_seconds = average.unwrap_or(0) as i64;
-        messages.push(format!("Чат: {}, Среднее стояние в неделе: \n<b>{}</b>", chat_name, time::total_seconds_to_hms(average_seconds)));
-
-        if let Some((_, current_winning_average)) = winning_chat {
-            if average_seconds > current_winning_average {
-                winning_chat = Some((*chat_id, average_seconds));
+async fn streak(bot: Bot, msg: Message, args: String, total_manager: Store) -> HandlerResult {
+    let chat_id = msg.chat.id;
+    let args: Vec<&str> = args.split_whitespace().collect();
+    match args.as_slice() {
+        [] => {}
+        ["min", duration] => {
+            if !is_admin(&bot, &msg).await? {
+                bot.send_message(chat_id, "Менять минимум может только админ чата.").await?;
+                return Ok(());
             }
-        } else {
-            winning_chat = Some((*chat_id, average_seconds));
+            let Some(seconds) = time::parse_duration(duration).filter(|seconds| *seconds > 0) else {
+                bot.send_message(chat_id, "Не понял. Пример: /streak min 30m").await?;
+                return Ok(());
+            };
+            total_manager.set_streak_minimum(chat_id, seconds).await?;
+        }
+        _ => {
+            bot.send_message(chat_id, "Не понял. Пример: /streak или /streak min 30m").await?;
+            return Ok(());
         }
     }
 
-    if let Some((chat_id, average)) = winning_chat {
-        let chat = bot.get_chat(ChatId(chat_id)).await?;
-        let chat_name = chat.title().unwrap_or_else(|| chat.username().unwrap_or("Нет имени"));
-        messages.push(format!("
-🏆 <b>Победитель недели:</b> {} со средним стоянием: <b>{}</b> 🏆", chat_name, time::total_seconds_to_hms(average)));
+    let today = total_manager.get_day_boundary(chat_id).await?.date_of(msg.date.timestamp());
+    let (days, minimum) = streak::history(total_manager.as_ref(), chat_id, None).await?;
+    let streaks = streak::streaks(&days, minimum, today);
+    let mut lines = vec![
+        format!("Серия чата: {} {} подряд", streaks.current, streak::days_word(streaks.current)),
+        format!("Рекорд: {} {}", streaks.longest, streak::days_word(streaks.longest)),
+    ];
+    if let Some(user) = msg.from.as_ref().filter(|_| msg.chat.is_group() || msg.chat.is_supergroup()) {
+        let (days, _) = streak::history(total_manager.as_ref(), chat_id, Some(user.id)).await?;
+        let member = streak::streaks(&days, minimum, today);
+        lines.push(format!("Серия {}: {} {} подряд, рекорд: {} {}",
+                           user.full_name(),
+                           member.current,
+                           streak::days_word(member.current),
+                           member.longest,
+                           streak::days_word(member.longest)));
     }
+    lines.push(format!("День засчитывается от {}", time::total_seconds_to_hms(minimum)));
+    bot.send_message(chat_id, lines.join("\n")).await?;
+    Ok(())
+}
 
-    bot.send_message(msg.chat.id, messages.join("\n"))
-       .parse_mode(teloxide::types::ParseMode::Html)
-       .await?;
+async fn records(bot: Bot, msg: Message, total_manager: Store) -> HandlerResult {
+    let chat_id = msg.chat.id;
+    let (days, sessions) = records::history(total_manager.as_ref(), chat_id, None).await?;
+    let week_start = total_manager.get_week_start(chat_id).await?;
+    let chat_records = records::records(&days, &sessions, week_start);
+    if chat_records.is_empty() {
+        bot.send_message(chat_id, "Рекордов пока нет.").await?;
+        return Ok(());
+    }
 
+    let mut lines = vec!["Рекорды чата:".to_string()];
+    lines.extend(chat_records.iter().map(records::Record::describe));
+    if let Some(user) = msg.from.as_ref().filter(|_| msg.chat.is_group() || msg.chat.is_supergroup()) {
+        let (days, sessions) = records::history(total_manager.as_ref(), chat_id, Some(user.id)).await?;
+        let member_records = records::records(&days, &sessions, week_start);
+        if !member_records.is_empty() {
+            lines.push(format!("\nРекорды {}:", user.full_name()));
+            lines.extend(member_records.iter().map(records::Record::describe));
+        }
+    }
+    bot.send_message(chat_id, lines.join("\n")).await?;
     Ok(())
 }
 
-async fn rankings_year(bot: Bot, msg: Message, total_manager: Arc<Total>) -> HandlerResult {
-    let averages = total_manager.get_average_total_per_year_by_chat().await?;
-    let mut messages = Vec::new();
+async fn goal(bot: Bot, msg: Message, args: String, total_manager: Store) -> HandlerResult {
+    let chat_id = msg.chat.id;
+    let args: Vec<&str> = args.split_whitespace().collect();
+    let (kind, value) = match args.as_slice() {
+        [] => (GoalKind::Day, None),
+        ["week"] => (GoalKind::Week, None),
+        ["day", value] => (GoalKind::Day, Some(*value)),
+        ["week", value] => (GoalKind::Week, Some(*value)),
+        [value] => (GoalKind::Day, Some(*value)),
+        _ => {
+            bot.send_message(chat_id, "Не понял. Пример: /goal 2h или /goal week 10h").await?;
+            return Ok(());
+        }
+    };
 
-    let mut winning_chat: Option<(i64, i64)> = None;
+    if let Some(value) = value {
+        if !is_admin(&bot, &msg).await? {
+            bot.send_message(chat_id, "Менять цели может только админ чата.").await?;
+            return Ok(());
+        }
+        let seconds = match value {
+            "off" => None,
+            value => match time::parse_duration(value).filter(|seconds| *seconds > 0) {
+                Some(seconds) => Some(seconds),
+                None => {
+                    bot.send_message(chat_id, "Не понял. Пример: /goal 2h или /goal week 10h").await?;
+                    return Ok(());
+                }
+            },
+        };
+        total_manager.set_goal(chat_id, kind, seconds).await?;
+    }
 
-    for (chat_id, average) in averages.iter() {
-        let chat = bot.get_chat(ChatId(*chat_id)).await?;
-        let chat_name = chat.title().unwrap_or_else(|| chat.username().unwrap_or("Нет имени"));
-        let average_seconds = average.unwrap_or(0) as i64;
-        messages.push(format!("Чат: {}, Среднее стояние в году: \n<b>{}</b>", chat_name, time::total_seconds_to_hms(average_seconds)));
+    let today = total_manager.get_day_boundary(chat_id).await?.date_of(msg.date.timestamp());
+    let lines = goal::progress_lines(total_manager.as_ref(), chat_id, today).await?;
+    if lines.is_empty() {
+        bot.send_message(chat_id, "Целей нет. Пример: /goal 2h или /goal week 10h").await?;
+    } else {
+        bot.send_message(chat_id, lines.join("\n")).await?;
+    }
+    Ok(())
+}
 
-        if let Some((_, current_winning_average)) = winning_chat {
-            if average_seconds > current_winning_average {
-                winning_chat = Some((*chat_id, average_seconds));
-            }
-        } else {
-            winning_chat = Some((*chat_id, average_seconds));
+async fn digest(bot: Bot, msg: Message, args: String, total_manager: Store) -> HandlerResult {
+    let chat_id = msg.chat.id;
+    let usage = "Не понял. Пример: /digest 21:00 или /digest week 21:00";
+    let args: Vec<&str> = args.split_whitespace().collect();
+    let parsed = match args.as_slice() {
+        [] => Some((DigestKind::Day, None)),
+        [value] => Some((DigestKind::Day, Some(*value))),
+        [kind, value] => DigestKind::parse(kind).map(|kind| (kind, Some(*value))),
+        _ => None,
+    };
+    let Some((kind, value)) = parsed else {
+        bot.send_message(chat_id, usage).await?;
+        return Ok(());
+    };
+
+    if let Some(value) = value {
+        if !is_admin(&bot, &msg).await? {
+            bot.send_message(chat_id, "Менять расписание может только админ чата.").await?;
+            return Ok(());
         }
+        let minutes = match value {
+            "off" => None,
+            value => match time::parse_time_of_day(value) {
+                Some(minutes) => Some(minutes),
+                None => {
+                    bot.send_message(chat_id, usage).await?;
+                    return Ok(());
+                }
+            },
+        };
+        total_manager.set_digest(chat_id, kind, minutes).await?;
+    }
+
+    let lines: Vec<String> = total_manager.get_digests().await?.into_iter()
+        .filter(|digest| digest.chat_id == chat_id.0)
+        .map(|digest| match digest.kind {
+            DigestKind::Day => format!("Итоги дня: каждый день в {}", time::format_time_of_day(digest.minutes)),
+            DigestKind::Week => format!("Итоги недели: в последний день недели в {}", time::format_time_of_day(digest.minutes)),
+        })
+        .collect();
+    if lines.is_empty() {
+        bot.send_message(chat_id, "Итоги по расписанию выключены. Пример: /digest 21:00 или /digest week 21:00").await?;
+    } else {
+        bot.send_message(chat_id, lines.join("\n")).await?;
     }
+    Ok(())
+}
+
+async fn stats(bot: Bot, msg: Message, args: String, total_manager: Store) -> HandlerResult {
+    let chat_id = msg.chat.id;
+    let Some(period) = Period::parse(args.trim()) else {
+        bot.send_message(chat_id, "Не понял период. Примеры: week, month, 2026, 30d, 2026-01-01..2026-03-31").await?;
+        return Ok(());
+    };
+
+    let today = total_manager.get_day_boundary(chat_id).await?.date_of(msg.date.timestamp());
+    let range = period.resolve(today, total_manager.get_week_start(chat_id).await?);
+    let days = total_manager.get_daily_totals(chat_id, range).await?;
+    let end = range.end.map_or(today, |end| end.min(today));
+    if chart::is_too_long(&days, range.start, end) {
+        bot.send_message(chat_id, format!("Слишком длинный период, статистика считается максимум за {} дней.", chart::MAX_DAYS)).await?;
+        return Ok(());
+    }
+    let days = chart::range_days(&days, range.start, end);
+    let (Some((first, _)), Some((last, _))) = (days.first(), days.last()) else {
+        bot.send_message(chat_id, format!("Никто не стоял {}.", period.describe())).await?;
+        return Ok(());
+    };
+
+    let mut lines = vec![format!("Статистика {} ({first} — {last})", period.describe())];
+    lines.extend(stats::Stats::new(&days).describe());
+    bot.send_message(chat_id, lines.join("\n")).await?;
+    Ok(())
+}
 
-    if let Some((chat_id, average)) = winning_chat {
-        let chat = bot.get_chat(ChatId(chat_id)).await?;
-        let chat_name = chat.title().unwrap_or_else(|| chat.username().unwrap_or("Нет имени"));
-        messages.push(format!("
-🏆 <b>Победитель года:</b> {} со средним стоянием: <b>{}</b> 🏆", chat_name, time::total_seconds_to_hms(average)));
+async fn patterns(bot: Bot, msg: Message, args: String, total_manager: Store) -> HandlerResult {
+    let chat_id = msg.chat.id;
+    let Some(period) = Period::parse(args.trim()) else {
+        bot.send_message(chat_id, "Не понял период. Примеры: week, month, 2026, 30d, 2026-01-01..2026-03-31").await?;
+        return Ok(());
+    };
+
+    let boundary = total_manager.get_day_boundary(chat_id).await?;
+    let range = period.resolve(boundary.date_of(msg.date.timestamp()), total_manager.get_week_start(chat_id).await?);
+    let sessions = total_manager.get_sessions(chat_id, range).await?;
+    let patterns = patterns::Patterns::new(&sessions, boundary.utc_offset_minutes);
+    if patterns.is_empty() {
+        bot.send_message(chat_id, format!("Никто не стоял {}.", period.describe())).await?;
+        return Ok(());
     }
 
-    bot.send_message(msg.chat.id, messages.join("\n"))
+    bot.send_message(chat_id, format!("Когда стоим {}, время {}:\n<pre>{}</pre>",
+                                      period.describe(),
+                                      time::format_utc_offset(boundary.utc_offset_minutes),
+                                      patterns.render()))
        .parse_mode(teloxide::types::ParseMode::Html)
        .await?;
-
     Ok(())
 }
 
-async fn total(bot: Bot, msg: Message, total_manager: Arc<Total>) -> HandlerResult {
-    let totals = total_manager.get_total_seconds_grouped_by_chat().await?;
-    let mut messages = Vec::new();
-
-    let mut winning_chat: Option<(i64, i64)> = None;
+/// Champions of the closed periods. Chats that left keep their titles.
+async fn hall_of_fame(bot: Bot, msg: Message, total_manager: Store) -> HandlerResult {
+    let champions = total_manager.get_champions().await?;
+    if champions.is_empty() {
+        bot.send_message(msg.chat.id, "Чемпионов пока нет: они появятся, когда закончится неделя.").await?;
+        return Ok(());
+    }
 
-    for (chat_id, total) in totals.iter() {
-        let chat = bot.get_chat(ChatId(*chat_id)).await?;
-        let chat_name = chat.title().unwrap_or_else(|| chat.username().unwrap_or("Нет имени"));
-        let total_seconds = total.unwrap_or(0) as i64;
-        messages.push(format!("Чат: {}, Всего постояли: \n<b>{}</b>", chat_name, time::total_seconds_to_hms(total_seconds)));
+    let names: HashMap<i64, String> = total_manager.get_chats().await?.into_iter()
+        .map(|chat| (chat.chat_id, chat.name()))
+        .collect();
+    bot.send_message(msg.chat.id, hall_of_fame::render(&champions, &names)).await?;
+    Ok(())
+}
 
-        if let Some((_, current_winning_total)) = winning_chat {
-            if total_seconds > current_winning_total {
-                winning_chat = Some((*chat_id, total_seconds));
-            }
-        } else {
-            winning_chat = Some((*chat_id, total_seconds));
+async fn announcements(bot: Bot, msg: Message, value: String, total_manager: Store) -> HandlerResult {
+    let chat_id = msg.chat.id;
+    let enabled = match value.trim() {
+        "" => None,
+        "on" => Some(true),
+        "off" => Some(false),
+        _ => {
+            bot.send_message(chat_id, "Не понял. Пример: /announcements off").await?;
+            return Ok(());
         }
+    };
+    if let Some(enabled) = enabled {
+        if !is_admin(&bot, &msg).await? {
+            bot.send_message(chat_id, "Менять это может только админ чата.").await?;
+            return Ok(());
+        }
+        total_manager.set_announcements(chat_id, enabled).await?;
     }
 
-    if let Some((chat_id, total)) = winning_chat {
-        let chat = bot.get_chat(ChatId(chat_id)).await?;
-        let chat_name = chat.title().unwrap_or_else(|| chat.username().unwrap_or("Нет имени"));
-        messages.push(format!("
-🏆 <b>Победитель:</b> {} с общим стоянием: <b>{}</b> 🏆", chat_name, time::total_seconds_to_hms(total)));
-    }
-
-    bot.send_message(msg.chat.id, messages.join("\n"))
-       .parse_mode(teloxide::types::ParseMode::Html)
-       .await?;
+    let text = if total_manager.get_announcements(chat_id).await? {
+        "Итоги недели, месяца и года среди чатов приходят сюда. Выключить: /announcements off"
+    } else {
+        "Итоги недели, месяца и года среди чатов выключены. Включить: /announcements on"
+    };
+    bot.send_message(chat_id, text).await?;
+    Ok(())
+}
 
+async fn chart(bot: Bot, msg: Message, args: String, total_manager: Store) -> HandlerResult {
+    let chat_id = msg.chat.id;
+    let period = match args.trim() {
+        "" => Period::Days(30),
+        text => match Period::parse(text) {
+            Some(period) => period,
+            None => {
+                bot.send_message(chat_id, "Не понял. Пример: /chart 7d, /chart 30d или /chart year").await?;
+                return Ok(());
+            }
+        },
+    };
+
+    let today = total_manager.get_day_boundary(chat_id).await?.date_of(msg.date.timestamp());
+    let range = period.resolve(today, total_manager.get_week_start(chat_id).await?);
+    let days = total_manager.get_daily_totals(chat_id, range).await?;
+    if days.iter().all(|(_, seconds)| *seconds == 0) {
+        bot.send_message(chat_id, "Нечего показывать.").await?;
+        return Ok(());
+    }
+    let end = range.end.unwrap_or(today).min(today);
+    if chart::is_too_long(&days, range.start, end) {
+        bot.send_message(chat_id, format!("Слишком длинный период, график строится максимум за {} дней.", chart::MAX_DAYS)).await?;
+        return Ok(());
+    }
+    let days = chart::range_days(&days, range.start, end);
+    let caption = chart::caption(&format!("График {}", period.describe()), &days);
+    bot.send_photo(chat_id, InputFile::memory(chart::bar_chart(&days)?).file_name("chart.png"))
+        .caption(caption)
+        .await?;
     Ok(())
 }
 
-async fn total_month(bot: Bot, msg: Message, total_manager: Arc<Total>) -> HandlerResult {
-    let totals = total_manager.get_total_seconds_grouped_by_month().await?;
-    let mut messages = Vec::new();
+const COMPARE_REQUEST: RequestId = RequestId(2);
 
-    let mut winning_chat: Option<(i64, i64)> = None;
+/// The chat standings are recorded in: the chat picked with /start in private chats, else this one
+async fn standing_chat(dialogue: &MyDialogue, msg: &Message) -> StoreResult<ChatId> {
+    if !msg.chat.is_private() {
+        return Ok(msg.chat.id);
+    }
+    Ok(match dialogue.get().await? {
+        Some(State::StandingChoice { chat_id } | State::ReceiveStandingCommand { chat_id, .. }) => chat_id,
+        _ => msg.chat.id,
+    })
+}
 
-    for (chat_id, total) in totals.iter() {
-        let chat = bot.get_chat(ChatId(*chat_id)).await?;
-        let chat_name = chat.title().unwrap_or_else(|| chat.username().unwrap_or("Нет имени"));
-        let total_seconds = total.unwrap_or(0) as i64;
-        messages.push(format!("Чат: {}, Всего постояли в этом месяце: \n<b>{}</b>", chat_name, time::total_seconds_to_hms(total_seconds)));
+/// Both chats over the period in the calendar of the first one
+async fn comparison(bot: &Bot, msg: &Message, total_manager: &Store, chat_id: ChatId, other: ChatId, period: Period) -> StoreResult<String> {
+    let today = total_manager.get_day_boundary(chat_id).await?.date_of(msg.date.timestamp());
+    let range = period.resolve(today, total_manager.get_week_start(chat_id).await?);
+    let end = range.end.map_or(today, |end| end.min(today));
+    let (days, other_days) = (total_manager.get_daily_totals(chat_id, range).await?, total_manager.get_daily_totals(other, range).await?);
+    let Some(start) = range.start.or_else(|| days.iter().chain(&other_days).map(|(date, _)| *date).min()).filter(|start| *start <= end) else {
+        return Ok(format!("Никто не стоял {}.", period.describe()));
+    };
+
+    let names = chats::chat_names(bot, total_manager, vec![chat_id.0, other.0]).await?;
+    let mut sides = Vec::new();
+    for (id, days) in [(chat_id, days), (other, other_days)] {
+        let (history, minimum) = streak::history(total_manager.as_ref(), id, None).await?;
+        let today = total_manager.get_day_boundary(id).await?.date_of(msg.date.timestamp());
+        let name = names.get(&id.0).cloned().unwrap_or_else(|| "Нет имени".to_string());
+        sides.push(compare::Side::new(name, &days, start, end, streak::streaks(&history, minimum, today).current));
+    }
+    Ok(compare::render(&period.describe(), &sides[0], &sides[1]))
+}
 
-        if let Some((_, current_winning_total)) = winning_chat {
-            if total_seconds > current_winning_total {
-                winning_chat = Some((*chat_id, total_seconds));
+async fn compare(bot: Bot,
+                 msg: Message,
+                 args: String,
+                 dialogue: MyDialogue,
+                 total_manager: Store,
+                 pending_compares: PendingCompares) -> HandlerResult {
+    let chat_id = msg.chat.id;
+    let usage = "Не понял. Пример: /compare @chat month";
+    // The chat goes first, a number that isn't a known chat id is a year
+    let (other, period) = match args.split_once(' ').unwrap_or((args.trim(), "")) {
+        (first, rest) if first.starts_with('@') || first.parse::<i64>().is_ok() => match chats::find_chat(&total_manager, first).await? {
+            Some(chat) => (Some(ChatId(chat.chat_id)), rest.trim()),
+            None if first.starts_with('@') => {
+                bot.send_message(chat_id, "Не нашёл такой чат. Пример: /compare @chat month").await?;
+                return Ok(());
+            }
+            None => (None, args.trim()),
+        },
+        _ => (None, args.trim()),
+    };
+    let period = match period {
+        "" => Period::Month,
+        text => match Period::parse(text) {
+            Some(period) => period,
+            None => {
+                bot.send_message(chat_id, usage).await?;
+                return Ok(());
+            }
+        },
+    };
+
+    let Some(other) = other else {
+        // Telegram only shows chat picker buttons in private chats
+        if !msg.chat.is_private() {
+            bot.send_message(chat_id, usage).await?;
+            return Ok(());
+        }
+        pending_compares.lock().unwrap().insert(chat_id, period);
+        bot.send_message(chat_id, "С каким чатом сравнить?")
+           .reply_markup(
+               KeyboardMarkup::new([[
+                   KeyboardButton::new("Группа").request(ButtonRequest::RequestChat(KeyboardButtonRequestChat::new(COMPARE_REQUEST, false))),
+                   KeyboardButton::new("Канал").request(ButtonRequest::RequestChat(KeyboardButtonRequestChat::new(COMPARE_REQUEST, true))),
+               ]])).await?;
+        return Ok(());
+    };
+
+    let standing_chat = standing_chat(&dialogue, &msg).await?;
+    bot.send_message(chat_id, comparison(&bot, &msg, &total_manager, standing_chat, other, period).await?).await?;
+    Ok(())
+}
+
+async fn heatmap(bot: Bot, msg: Message, args: String, total_manager: Store) -> HandlerResult {
+    let chat_id = msg.chat.id;
+    let mut chat_ids = vec![chat_id.0];
+    let other = args.trim();
+    if !other.is_empty() {
+        match chats::find_chat(&total_manager, other).await? {
+            Some(chat) if chat.chat_id != chat_id.0 => chat_ids.push(chat.chat_id),
+            Some(_) => {}
+            None => {
+                bot.send_message(chat_id, "Не нашёл такой чат. Пример: /heatmap @chat").await?;
+                return Ok(());
             }
-        } else {
-            winning_chat = Some((*chat_id, total_seconds));
         }
     }
 
-    if let Some((chat_id, total)) = winning_chat {
-        let chat = bot.get_chat(ChatId(chat_id)).await?;
-        let chat_name = chat.title().unwrap_or_else(|| chat.username().unwrap_or("Нет имени"));
-        messages.push(format!("
-🏆 <b>Победитель месяца:</b> {} с общим стоянием: <b>{}</b> 🏆", chat_name, time::total_seconds_to_hms(total)));
+    let today = total_manager.get_day_boundary(chat_id).await?.date_of(msg.date.timestamp());
+    let range = DateRange { start: Some(chart::year_start(today)), end: Some(today) };
+    let mut calendars = Vec::new();
+    for id in &chat_ids {
+        let days = total_manager.get_daily_totals(ChatId(*id), range).await?;
+        calendars.push(chart::fill_days(&days, chart::year_start(today), today));
+    }
+    if calendars.iter().flatten().all(|(_, seconds)| *seconds == 0) {
+        bot.send_message(chat_id, "Нечего показывать.").await?;
+        return Ok(());
     }
 
-    bot.send_message(msg.chat.id, messages.join("\n"))
-       .parse_mode(teloxide::types::ParseMode::Html)
-       .await?;
+    let names = chats::chat_names(&bot, &total_manager, chat_ids.clone()).await?;
+    let name = |id: &i64| names.get(id).cloned().unwrap_or_else(|| "Нет имени".to_string());
+    let mut lines = vec![format!("Тепловая карта за год ({} — {today})", chart::year_start(today))];
+    if chat_ids.len() > 1 {
+        lines.push(format!("Сверху: {}, снизу: {}", name(&chat_ids[0]), name(&chat_ids[1])));
+    }
+    for (id, days) in chat_ids.iter().zip(&calendars) {
+        let active = days.iter().filter(|(_, seconds)| *seconds > 0).count();
+        lines.push(format!("{}: {active} из {} дн.", name(id), days.len()));
+    }
+    let max = calendars.iter().flatten().map(|(_, seconds)| *seconds).max().unwrap_or_default();
+    lines.push(format!("Самый тёмный цвет — около {}", time::format_short_duration(max)));
 
+    bot.send_photo(chat_id, InputFile::memory(chart::heatmap(&calendars, total_manager.get_week_start(chat_id).await?)?).file_name("heatmap.png"))
+        .caption(lines.join("\n"))
+        .await?;
     Ok(())
 }
 
-async fn total_week(bot: Bot, msg: Message, total_manager: Arc<Total>) -> HandlerResult {
-    let totals = total_manager.get_total_seconds_grouped_by_week().await?;
-    let mut messages = Vec::new();
-
-    let mut winning_chat: Option<(i64, i64)> = None;
-
-    for (chat_id, total) in totals.iter() {
-        let chat = bot.get_chat(ChatId(*chat_id)).await?;
-        let chat_name = chat.title().unwrap_or_else(|| chat.username().unwrap_or("Нет имени"));
-        let total_seconds = total.unwrap_or(0) as i64;
-        messages.push(format!("Чат: {}, Всего постояли на этой неделе: \n<b>{}</b>", chat_name, time::total_seconds_to_hms(total_seconds)));
-
-        if let Some((_, current_winning_total)) = winning_chat {
-            if total_seconds > current_winning_total {
-                winning_chat = Some((*chat_id, total_seconds));
-            }
+async fn export(bot: Bot, msg: Message, args: String, total_manager: Store) -> HandlerResult {
+    let mut format = ExportFormat::Csv;
+    let mut period = Period::All;
+    for arg in args.split_whitespace() {
+        if let Some(parsed) = ExportFormat::parse(arg) {
+            format = parsed;
+        } else if let Some(parsed) = Period::parse(arg) {
+            period = parsed;
         } else {
-            winning_chat = Some((*chat_id, total_seconds));
+            bot.send_message(msg.chat.id, "Не понял. Пример: /export csv month").await?;
+            return Ok(());
         }
     }
 
-    if let Some((chat_id, total)) = winning_chat {
-        let chat = bot.get_chat(ChatId(chat_id)).await?;
-        let chat_name = chat.title().unwrap_or_else(|| chat.username().unwrap_or("Нет имени"));
-        messages.push(format!("
-🏆 <b>Победитель недели:</b> {} с общим стоянием: <b>{}</b> 🏆", chat_name, time::total_seconds_to_hms(total)));
+    let chat_id = msg.chat.id;
+    let boundary = total_manager.get_day_boundary(chat_id).await?;
+    let range = period.resolve(boundary.date_of(msg.date.timestamp()), total_manager.get_week_start(chat_id).await?);
+    let days = total_manager.get_daily_totals(chat_id, range).await?;
+    let sessions = total_manager.get_sessions(chat_id, range).await?;
+    if days.is_empty() && sessions.is_empty() {
+        bot.send_message(chat_id, "Нечего выгружать.").await?;
+        return Ok(());
     }
 
-    bot.send_message(msg.chat.id, messages.join("\n"))
-       .parse_mode(teloxide::types::ParseMode::Html)
-       .await?;
+    match format {
+        ExportFormat::Csv => {
+            bot.send_document(chat_id, InputFile::memory(export::days_to_csv(&days)).file_name("standing_days.csv")).await?;
+            if !sessions.is_empty() {
+                bot.send_document(chat_id, InputFile::memory(export::sessions_to_csv(&sessions, boundary)).file_name("standing_sessions.csv")).await?;
+            }
+        }
+        ExportFormat::Json => {
+            bot.send_document(chat_id, InputFile::memory(export::to_json(chat_id.0, &days, &sessions, boundary)).file_name("standing.json")).await?;
+        }
+    }
 
     Ok(())
 }
 
-async fn total_year(bot: Bot, msg: Message, total_manager: Arc<Total>) -> HandlerResult {
-    let totals = total_manager.get_total_seconds_grouped_by_year().await?;
-    let mut messages = Vec::new();
+/// Channel posts and private chats only come from admins, in groups ask Telegram
+async fn is_admin(bot: &Bot, msg: &Message) -> Result<bool, teloxide::RequestError> {
+    if msg.chat.is_private() || msg.chat.is_channel() || msg.sender_chat.as_ref().is_some_and(|chat| chat.id == msg.chat.id) {
+        return Ok(true);
+    }
+    match &msg.from {
+        Some(user) => Ok(bot.get_chat_member(msg.chat.id, user.id).await?.is_privileged()),
+        None => Ok(false),
+    }
+}
 
-    let mut winning_chat: Option<(i64, i64)> = None;
+const IMPORT_USAGE: &str = "Пришлите CSV или JSON файл с подписью /import add или /import replace, потом /import confirm.";
+const IMPORT_MAX_FILE_SIZE: u32 = 1024 * 1024;
 
-    for (chat_id, total) in totals.iter() {
-        let chat = bot.get_chat(ChatId(*chat_id)).await?;
-        let chat_name = chat.title().unwrap_or_else(|| chat.username().unwrap_or("Нет имени"));
-        let total_seconds = total.unwrap_or(0) as i64;
-        messages.push(format!("Чат: {}, Всего постояли в этом году: \n<b>{}</b>", chat_name, time::total_seconds_to_hms(total_seconds)));
+async fn import_document(bot: Bot, msg: Message, total_manager: Store, pending_imports: PendingImports) -> HandlerResult {
+    let chat_id = msg.chat.id;
+    if !is_admin(&bot, &msg).await? {
+        bot.send_message(chat_id, "Импортировать может только админ чата.").await?;
+        return Ok(());
+    }
 
-        if let Some((_, current_winning_total)) = winning_chat {
-            if total_seconds > current_winning_total {
-                winning_chat = Some((*chat_id, total_seconds));
+    let mut policy = MergePolicy::Add;
+    for arg in msg.caption().unwrap_or_default().split_whitespace().skip(1) {
+        match MergePolicy::parse(arg) {
+            Some(parsed) => policy = parsed,
+            None => {
+                bot.send_message(chat_id, IMPORT_USAGE).await?;
+                return Ok(());
             }
-        } else {
-            winning_chat = Some((*chat_id, total_seconds));
         }
     }
 
-    if let Some((chat_id, total)) = winning_chat {
-        let chat = bot.get_chat(ChatId(chat_id)).await?;
-        let chat_name = chat.title().unwrap_or_else(|| chat.username().unwrap_or("Нет имени"));
-        messages.push(format!("
-🏆 <b>Победитель года:</b> {} с общим стоянием: <b>{}</b> 🏆", chat_name, time::total_seconds_to_hms(total)));
+    let Some(document) = msg.document() else { return Ok(()) };
+    if document.file.size > IMPORT_MAX_FILE_SIZE {
+        bot.send_message(chat_id, "Файл слишком большой.").await?;
+        return Ok(());
+    }
+    let file = bot.get_file(document.file.id.clone()).await?;
+    let mut contents = Vec::new();
+    bot.download_file(&file.path, &mut contents).await?;
+
+    let days = match String::from_utf8(contents).map_err(|e| e.to_string()).and_then(|contents| import::parse_days(&contents)) {
+        Ok(days) => days,
+        Err(e) => {
+            bot.send_message(chat_id, format!("Не смог разобрать файл: {e}")).await?;
+            return Ok(());
+        }
+    };
+    if days.is_empty() {
+        bot.send_message(chat_id, "В файле нет ни одного дня.").await?;
+        return Ok(());
     }
 
-    bot.send_message(msg.chat.id, messages.join("\n"))
-       .parse_mode(teloxide::types::ParseMode::Html)
-       .await?;
+    let pending = PendingImport { days, policy };
+    let summary = import::summarize(total_manager.as_ref(), chat_id, &pending).await?;
+    pending_imports.lock().unwrap().insert(chat_id, pending);
+    bot.send_message(chat_id, format!("{}\n\nОтправьте /import confirm чтобы записать или /import cancel чтобы отменить.", summary.describe(policy))).await?;
+    Ok(())
+}
+
+async fn import(bot: Bot, msg: Message, args: String, total_manager: Store, pending_imports: PendingImports) -> HandlerResult {
+    let chat_id = msg.chat.id;
+    if !is_admin(&bot, &msg).await? {
+        bot.send_message(chat_id, "Импортировать может только админ чата.").await?;
+        return Ok(());
+    }
 
+    match args.trim() {
+        "confirm" => {
+            let pending = pending_imports.lock().unwrap().remove(&chat_id);
+            match pending {
+                Some(pending) => {
+                    total_manager.import_daily_totals(chat_id, &pending.days, pending.policy).await?;
+                    bot.send_message(chat_id, format!("Импорт записан: {} дней.", pending.days.len())).await?;
+                }
+                None => {
+                    bot.send_message(chat_id, format!("Нечего подтверждать. {IMPORT_USAGE}")).await?;
+                }
+            }
+        }
+        "cancel" => {
+            pending_imports.lock().unwrap().remove(&chat_id);
+            bot.send_message(chat_id, "Импорт отменён.").await?;
+        }
+        _ => {
+            bot.send_message(chat_id, IMPORT_USAGE).await?;
+        }
+    }
     Ok(())
 }
 
@@ -460,7 +928,19 @@ async fn help(bot: Bot, msg: Message) -> HandlerResult {
 }
 
 
-async fn chat_shared(bot: Bot, msg: Message, dialogue: MyDialogue, chat: MessageChatShared) -> HandlerResult {
+async fn chat_shared(bot: Bot,
+                     msg: Message,
+                     dialogue: MyDialogue,
+                     chat: MessageChatShared,
+                     total_manager: Store,
+                     pending_compares: PendingCompares) -> HandlerResult {
+    if chat.chat_shared.request_id == COMPARE_REQUEST {
+        let period = pending_compares.lock().unwrap().remove(&msg.chat.id).unwrap_or(Period::Month);
+        let chat_id = standing_chat(&dialogue, &msg).await?;
+        let text = comparison(&bot, &msg, &total_manager, chat_id, chat.chat_shared.chat_id, period).await?;
+        bot.send_message(msg.chat.id, text).reply_markup(KeyboardRemove::new()).await?;
+        return Ok(());
+    }
     bot.send_message(msg.chat.id, format!("Текущий чат: {}",chat.chat_shared.chat_id))
        .reply_markup(
            KeyboardMarkup::new([[
@@ -476,16 +956,24 @@ async fn cancel(
     dialogue: MyDialogue,
     msg: Message,
     cmd: Command,
-    total_manager: Arc<Total>
+    total_manager: Store
 ) -> HandlerResult {
     if let Some(State::ReceiveStandingCommand { chat_id, timestamp }) = dialogue.get().await? {
         if let Command::Cancel(minutes_str) = cmd {
-            let mut total = total_manager.clone().get_total_timestamp_day(timestamp,chat_id).await?.unwrap_or(0);
-
-            total += minutes_str.parse::<i64>().unwrap_or(0) * 60;
+            let seconds = match minutes_str.trim() {
+                "" => Some(0),
+                text => time::parse_duration(text),
+            };
+            let Some(seconds) = seconds else {
+                bot.send_message(msg.chat.id, "Не понял. Пример: /cancel 20 или /cancel 1h20m").await?;
+                return Ok(());
+            };
+            if !(0..=time::SECONDS_PER_DAY).contains(&seconds) {
+                bot.send_message(msg.chat.id, "Засчитать можно от 0 до 24 часов.").await?;
+                return Ok(());
+            }
 
-            dialogue.exit().await?;
-            send_and_update_total(&bot, chat_id, total, total_manager).await?;
+            finish_session_and_send_total(&bot, chat_id, timestamp, msg.date.timestamp(), seconds, StopTrigger::Cancel, total_manager).await?;
             bot.send_message(msg.chat.id, "Отменили стояние.").await?;
 
             // Unpin any standing message
@@ -502,6 +990,203 @@ async fn cancel(
     Ok(())
 }
 
+async fn undo(bot: Bot, dialogue: MyDialogue, msg: Message, args: String, total_manager: Store) -> HandlerResult {
+    let reopen = match args.trim() {
+        "" => false,
+        "reopen" => true,
+        _ => {
+            bot.send_message(msg.chat.id, "Не понял. Пример: /undo или /undo reopen").await?;
+            return Ok(());
+        }
+    };
+
+    // Groups keep a session per member, elsewhere the chat stands as a whole, possibly a chat
+    // picked with the keyboard
+    let member = msg.from.as_ref().filter(|_| msg.chat.is_group() || msg.chat.is_supergroup());
+    let state = dialogue.get().await?;
+    let chat_id = match state {
+        Some(State::StandingChoice { chat_id }) if member.is_none() => chat_id,
+        _ => msg.chat.id,
+    };
+    let standing = match member {
+        Some(user) => total_manager.get_member_session_start(chat_id, user.id).await?.is_some(),
+        None => matches!(state, Some(State::ReceiveStandingCommand { .. })),
+    };
+    if standing {
+        bot.send_message(msg.chat.id, "Сначала закончите текущее стояние.").await?;
+        return Ok(());
+    }
+
+    let Some(session) = total_manager.undo_last_session(chat_id, member.map(|user| user.id), reopen, msg.date.timestamp()).await? else {
+        bot.send_message(msg.chat.id, "Нечего отменять.").await?;
+        return Ok(());
+    };
+    let end_timestamp = session.end_timestamp.unwrap_or_default();
+    let seconds = session.seconds.unwrap_or_default();
+
+    if reopen {
+        if member.is_none() {
+            dialogue.update(State::ReceiveStandingCommand { chat_id, timestamp: session.start_timestamp }).await?;
+        }
+        let reply = bot.send_message(msg.chat.id, format!("Продолжаем стояние, уже {}", time::get_time_difference(session.start_timestamp, msg.date.timestamp())));
+        if chat_id != msg.chat.id {
+            reply.reply_markup(KeyboardMarkup::new([[KeyboardButton::new("СИДИМ")]])).await?;
+        } else {
+            reply.await?;
+        }
+    } else {
+        let boundary = total_manager.get_day_boundary(chat_id).await?;
+        let date = boundary.date_of(end_timestamp);
+        let total = match member {
+            Some(user) => {
+                let range = period::DateRange { start: Some(date), end: Some(date) };
+                total_manager.get_member_daily_totals(chat_id, user.id, range).await?.iter().map(|(_, seconds)| seconds).sum()
+            }
+            None => total_manager.get_total_timestamp_day(end_timestamp, chat_id).await?.unwrap_or(0),
+        };
+        bot.send_message(msg.chat.id, format!(
+            "Отменили стояние на {}\nВсего за {}: {}",
+            time::total_seconds_to_hms(seconds),
+            date,
+            time::total_seconds_to_hms(total),
+        )).await?;
+    }
+    Ok(())
+}
+
+async fn adjust(bot: Bot, msg: Message, args: String, total_manager: Store) -> HandlerResult {
+    let chat_id = msg.chat.id;
+    if !is_admin(&bot, &msg).await? {
+        bot.send_message(chat_id, "Поправлять время может только админ чата.").await?;
+        return Ok(());
+    }
+
+    let mut words = args.split_whitespace().peekable();
+    let Some(seconds) = words.next().and_then(time::parse_duration).filter(|seconds| *seconds != 0) else {
+        bot.send_message(chat_id, "Не понял. Пример: /adjust +15m вчера забыли стикер").await?;
+        return Ok(());
+    };
+    if seconds.abs() > time::SECONDS_PER_DAY {
+        bot.send_message(chat_id, "За раз можно поправить не больше чем на сутки.").await?;
+        return Ok(());
+    }
+    let boundary = total_manager.get_day_boundary(chat_id).await?;
+    let today = boundary.date_of(msg.date.timestamp());
+    let date = match words.peek().and_then(|word| time::parse_day(word, today)) {
+        Some(date) => {
+            words.next();
+            date
+        }
+        None => today,
+    };
+    if date > today {
+        bot.send_message(chat_id, "Этот день ещё не наступил.").await?;
+        return Ok(());
+    }
+    let reason = words.collect::<Vec<_>>().join(" ");
+
+    let range = DateRange { start: Some(date), end: Some(date) };
+    let current = total_manager.get_daily_totals(chat_id, range).await?.first().map_or(0, |(_, seconds)| *seconds);
+    if current.checked_add(seconds).is_none_or(|total| total < 0) {
+        bot.send_message(chat_id, format!("За {date} всего {}, столько не убрать.", time::total_seconds_to_hms(current))).await?;
+        return Ok(());
+    }
+
+    let (user_id, user_name) = match &msg.from {
+        Some(user) => (Some(user.id.0 as i64), user.full_name()),
+        None => (None, msg.author_signature().or(msg.chat.title()).unwrap_or("Канал").to_string()),
+    };
+    let adjustment = Adjustment {
+        user_id,
+        user_name,
+        date,
+        seconds,
+        reason: (!reason.is_empty()).then_some(reason),
+        created_at: msg.date.timestamp(),
+    };
+    let total = total_manager.add_adjustment(chat_id, &adjustment).await?;
+    bot.send_message(chat_id, format!("Поправили {date}: {}\nВсего за день: {}", time::format_signed_duration(seconds), time::total_seconds_to_hms(total))).await?;
+    Ok(())
+}
+
+async fn adjustments(bot: Bot, msg: Message, total_manager: Store) -> HandlerResult {
+    let adjustments = total_manager.get_adjustments(msg.chat.id, 10).await?;
+    if adjustments.is_empty() {
+        bot.send_message(msg.chat.id, "Поправок ещё не было.").await?;
+        return Ok(());
+    }
+
+    let mut lines = vec!["<b>Последние поправки:</b>".to_string()];
+    for adjustment in adjustments {
+        let mut line = format!("{}: {} — {}", adjustment.date, time::format_signed_duration(adjustment.seconds), html::escape(&adjustment.user_name));
+        if let Some(reason) = adjustment.reason {
+            line.push_str(&format!(" ({})", html::escape(&reason)));
+        }
+        lines.push(line);
+    }
+    bot.send_message(msg.chat.id, lines.join("\n"))
+       .parse_mode(teloxide::types::ParseMode::Html)
+       .await?;
+    Ok(())
+}
+
+async fn timezone(bot: Bot, msg: Message, offset: String, total_manager: Store) -> HandlerResult {
+    if !offset.trim().is_empty() {
+        if !is_admin(&bot, &msg).await? {
+            bot.send_message(msg.chat.id, "Менять часовой пояс может только админ чата.").await?;
+            return Ok(());
+        }
+        match time::parse_utc_offset(&offset) {
+            Some(minutes) => total_manager.set_utc_offset(msg.chat.id, minutes).await?,
+            None => {
+                bot.send_message(msg.chat.id, "Не понял часовой пояс. Пример: /timezone +6").await?;
+                return Ok(());
+            }
+        }
+    }
+    let boundary = total_manager.get_day_boundary(msg.chat.id).await?;
+    bot.send_message(msg.chat.id, format!("Часовой пояс: {}", time::format_utc_offset(boundary.utc_offset_minutes))).await?;
+    Ok(())
+}
+
+async fn day_start(bot: Bot, msg: Message, time: String, total_manager: Store) -> HandlerResult {
+    if !time.trim().is_empty() {
+        if !is_admin(&bot, &msg).await? {
+            bot.send_message(msg.chat.id, "Менять начало дня может только админ чата.").await?;
+            return Ok(());
+        }
+        match time::parse_time_of_day(&time) {
+            Some(minutes) => total_manager.set_day_start(msg.chat.id, minutes).await?,
+            None => {
+                bot.send_message(msg.chat.id, "Не понял время. Пример: /daystart 04:00").await?;
+                return Ok(());
+            }
+        }
+    }
+    let boundary = total_manager.get_day_boundary(msg.chat.id).await?;
+    bot.send_message(msg.chat.id, format!("День начинается в {}", time::format_time_of_day(boundary.day_start_minutes))).await?;
+    Ok(())
+}
+
+async fn week_start(bot: Bot, msg: Message, day: String, total_manager: Store) -> HandlerResult {
+    if !day.trim().is_empty() {
+        if !is_admin(&bot, &msg).await? {
+            bot.send_message(msg.chat.id, "Менять начало недели может только админ чата.").await?;
+            return Ok(());
+        }
+        match period::parse_weekday(&day) {
+            Some(weekday) => total_manager.set_week_start(msg.chat.id, weekday).await?,
+            None => {
+                bot.send_message(msg.chat.id, "Не понял день. Пример: /weekstart monday или /weekstart вс").await?;
+                return Ok(());
+            }
+        }
+    }
+    let weekday = total_manager.get_week_start(msg.chat.id).await?;
+    bot.send_message(msg.chat.id, format!("Неделя начинается: {}", period::weekday_name(weekday))).await?;
+    Ok(())
+}
+
 async fn invalid_state(bot: Bot, msg: Message) -> HandlerResult {
     bot.send_message(msg.chat.id, "Unable to handle the message. Type /help to see the usage.")
        .await?;
@@ -513,14 +1198,567 @@ async fn invalid_state(bot: Bot, msg: Message) -> HandlerResult {
 mod tests {
     use super::*;
     use dptree::deps;
-    use teloxide::dispatching::{dialogue::{InMemStorage}};
-    use teloxide_tests::{MockBot, MockMessageText};
+    use memory_store::MemoryStore;
+    use periodic_updates::UpdateData;
+    use std::sync::Arc;
+    use teloxide::dispatching::dialogue::InMemStorage;
+    use teloxide_tests::{MockBot, MockGroupChat, MockMessageDocument, MockMessageSticker, MockMessageText, MockPrivateChat, MockUser};
+    use tokio::sync::watch;
+
+    fn dependencies(store: Store) -> DependencyMap {
+        let storage: MyStorage = InMemStorage::new().erase();
+        let (tx, _) = watch::channel(UpdateData(None, 0));
+        deps![storage, tx, store, PendingImports::default(), PendingCompares::default(), KnownChats::default()]
+    }
+
+    /// A sticker sent by the user to the chat at a unix timestamp
+    fn sticker_at(chat: &teloxide::types::Chat, user: &teloxide::types::User, unique_id: &str, timestamp: i64) -> MockMessageSticker {
+        MockMessageSticker::new()
+            .chat(chat.clone())
+            .from(user.clone())
+            .date(chrono::DateTime::from_timestamp(timestamp, 0).unwrap())
+            .file_unique_id(unique_id)
+    }
+
+    /// Texts of every message the bot sent so far
+    fn sent_texts(bot: &MockBot) -> Vec<String> {
+        bot.get_responses().sent_messages.iter().filter_map(|message| message.text().map(str::to_string)).collect()
+    }
 
     #[tokio::test]
     async fn test_start_tree() {
         let bot = MockBot::new(MockMessageText::new().text("/start"), schema());
-        let storage: MyStorage = InMemStorage::new().erase();
-        bot.dependencies(deps![storage]);
+        bot.dependencies(dependencies(Arc::new(MemoryStore::default())));
         bot.dispatch_and_check_last_text_and_state("Скинь чат бро",State::Start).await;
     }
+
+    #[tokio::test]
+    async fn test_timezone() {
+        let store: Store = Arc::new(MemoryStore::default());
+        let bot = MockBot::new(MockMessageText::new().text("/timezone +6"), schema());
+        bot.dependencies(dependencies(store.clone()));
+        bot.dispatch_and_check_last_text("Часовой пояс: UTC+06:00").await;
+
+        bot.update(MockMessageText::new().text("/timezone"));
+        bot.dispatch_and_check_last_text("Часовой пояс: UTC+06:00").await;
+
+        bot.update(MockMessageText::new().text("/timezone nope"));
+        bot.dispatch_and_check_last_text("Не понял часовой пояс. Пример: /timezone +6").await;
+    }
+
+    #[tokio::test]
+    async fn test_week_start() {
+        let store: Store = Arc::new(MemoryStore::default());
+        let bot = MockBot::new(MockMessageText::new().text("/weekstart"), schema());
+        bot.dependencies(dependencies(store.clone()));
+        bot.dispatch_and_check_last_text("Неделя начинается: понедельник").await;
+
+        bot.update(MockMessageText::new().text("/weekstart sunday"));
+        bot.dispatch_and_check_last_text("Неделя начинается: воскресенье").await;
+
+        bot.update(MockMessageText::new().text("/weekstart пн"));
+        bot.dispatch_and_check_last_text("Неделя начинается: понедельник").await;
+
+        bot.update(MockMessageText::new().text("/weekstart someday"));
+        bot.dispatch_and_check_last_text("Не понял день. Пример: /weekstart monday или /weekstart вс").await;
+    }
+
+    #[tokio::test]
+    async fn test_cancel() {
+        let store: Store = Arc::new(MemoryStore::default());
+        let start = 1714557600; // 2024-05-01 10:00 UTC
+        let cancel = |text: &str| MockMessageText::new().text(text).date(chrono::DateTime::from_timestamp(start + 3600, 0).unwrap());
+        let bot = MockBot::new(cancel("/cancel 999999999999999999"), schema());
+        bot.dependencies(dependencies(store.clone()));
+        let chat_id = MockMessageText::new().build().chat.id;
+        bot.set_state(State::ReceiveStandingCommand { chat_id, timestamp: start }).await;
+        bot.dispatch_and_check_last_text("Не понял. Пример: /cancel 20 или /cancel 1h20m").await;
+
+        bot.update(cancel("/cancel -5"));
+        bot.dispatch_and_check_last_text("Засчитать можно от 0 до 24 часов.").await;
+        bot.update(cancel("/cancel 25h"));
+        bot.dispatch_and_check_last_text("Засчитать можно от 0 до 24 часов.").await;
+
+        bot.update(cancel("/cancel 20"));
+        bot.dispatch_and_check_last_text("Отменили стояние.").await;
+        assert_eq!(store.get_total_timestamp_day(start, chat_id).await.unwrap(), Some(1200));
+    }
+
+    #[tokio::test]
+    async fn test_member_standing() {
+        let store: Store = Arc::new(MemoryStore::default());
+        let chat = MockGroupChat::new().build();
+        let user = MockUser::new().first_name("Alice").build();
+        let start = 1714557600; // 2024-05-01 10:00 UTC
+
+        let bot = MockBot::new(sticker_at(&chat, &user, sticker_handling::STICKER_STAND, start), schema());
+        bot.dependencies(dependencies(store.clone()));
+        bot.dispatch_and_check_last_text("Alice СТОИТ").await;
+
+        bot.update(sticker_at(&chat, &user, sticker_handling::STICKER_SIT, start + 1200));
+        bot.dispatch_and_check_last_text("Всего сегодня у Alice: 0 часов 20 минут 0 секунд").await;
+
+        assert_eq!(store.get_member_totals(chat.id, DateRange::default()).await.unwrap(), vec![(user.id.0 as i64, "Alice".to_string(), 1200)]);
+    }
+
+    #[tokio::test]
+    async fn test_export() {
+        let store: Store = Arc::new(MemoryStore::default());
+        let message = MockMessageText::new().text("/export csv");
+        let chat_id = message.chat.id;
+        store.start_session(chat_id, 1714557600, store::StartTrigger::Sticker).await.unwrap();
+        store.finish_session(chat_id, 1714557600, 1714559400, 1800, StopTrigger::SitSticker).await.unwrap();
+
+        let bot = MockBot::new(message, schema());
+        bot.dependencies(dependencies(store.clone()));
+        bot.dispatch().await;
+
+        let documents = bot.get_responses().sent_messages_document;
+        assert_eq!(documents.len(), 2);
+        assert_eq!(documents[0].bot_request.file_name, "standing_days.csv");
+        assert_eq!(documents[0].bot_request.file_data, "date,seconds\n2024-05-01,1800\n");
+        assert_eq!(documents[1].bot_request.file_name, "standing_sessions.csv");
+
+        bot.update(MockMessageText::new().text("/export json 2020-01-01..2020-12-31"));
+        bot.dispatch_and_check_last_text("Нечего выгружать.").await;
+
+        bot.update(MockMessageText::new().text("/export csv 4000000000d"));
+        bot.dispatch_and_check_last_text("Не понял. Пример: /export csv month").await;
+    }
+
+    #[tokio::test]
+    async fn test_import() {
+        let store: Store = Arc::new(MemoryStore::default());
+        let pending_imports = PendingImports::default();
+        let storage: MyStorage = InMemStorage::new().erase();
+        let (tx, _) = watch::channel(UpdateData(None, 0));
+
+        // The mock server serves "Hello, world!" for every file
+        let bot = MockBot::new(MockMessageDocument::new().caption("/import replace"), schema());
+        bot.dependencies(deps![storage, tx, store.clone(), pending_imports.clone(), PendingCompares::default(), KnownChats::default()]);
+        bot.dispatch_and_check_last_text("В файле нет ни одного дня.").await;
+
+        bot.update(MockMessageText::new().text("/import confirm"));
+        bot.dispatch_and_check_last_text(&format!("Нечего подтверждать. {IMPORT_USAGE}")).await;
+
+        let chat_id = MockMessageText::new().chat.id;
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        pending_imports.lock().unwrap().insert(chat_id, PendingImport { days: vec![(date, 600)], policy: MergePolicy::Add });
+        bot.update(MockMessageText::new().text("/import confirm"));
+        bot.dispatch_and_check_last_text("Импорт записан: 1 дней.").await;
+        assert_eq!(store.get_daily_totals(chat_id, period::DateRange::default()).await.unwrap(), vec![(date, 600)]);
+        assert!(pending_imports.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_adjust() {
+        let store: Store = Arc::new(MemoryStore::default());
+        let bot = MockBot::new(MockMessageText::new().text("/adjustments"), schema());
+        bot.dependencies(dependencies(store.clone()));
+        bot.dispatch_and_check_last_text("Поправок ещё не было.").await;
+
+        bot.update(MockMessageText::new().text("/adjust +15m 2024-05-01 забыли стикер"));
+        bot.dispatch_and_check_last_text("Поправили 2024-05-01: +0 часов 15 минут 0 секунд\nВсего за день: 0 часов 15 минут 0 секунд").await;
+
+        bot.update(MockMessageText::new().text("/adjust -1h 2024-05-01"));
+        bot.dispatch_and_check_last_text("За 2024-05-01 всего 0 часов 15 минут 0 секунд, столько не убрать.").await;
+
+        bot.update(MockMessageText::new().text("/adjust soon"));
+        bot.dispatch_and_check_last_text("Не понял. Пример: /adjust +15m вчера забыли стикер").await;
+
+        bot.update(MockMessageText::new().text("/adjust 999999999999999999"));
+        bot.dispatch_and_check_last_text("Не понял. Пример: /adjust +15m вчера забыли стикер").await;
+
+        bot.update(MockMessageText::new().text("/adjust +25h 2024-05-01"));
+        bot.dispatch_and_check_last_text("За раз можно поправить не больше чем на сутки.").await;
+
+        bot.update(MockMessageText::new().text("/adjust -5 2024-05-01"));
+        bot.dispatch_and_check_last_text("Поправили 2024-05-01: -0 часов 5 минут 0 секунд\nВсего за день: 0 часов 10 минут 0 секунд").await;
+
+        bot.update(MockMessageText::new().text("/adjustments"));
+        bot.dispatch().await;
+        let text = bot.get_responses().sent_messages.last().unwrap().text().unwrap().to_string();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].starts_with("2024-05-01: -0 часов 5 минут 0 секунд — "));
+        assert!(lines[2].ends_with("(забыли стикер)"));
+    }
+
+    #[tokio::test]
+    async fn test_undo() {
+        let store: Store = Arc::new(MemoryStore::default());
+        let message = MockMessageText::new().text("/undo");
+        let chat_id = message.chat.id;
+        let bot = MockBot::new(message, schema());
+        bot.dependencies(dependencies(store.clone()));
+        bot.dispatch_and_check_last_text("Нечего отменять.").await;
+
+        store.start_session(chat_id, 1714557600, store::StartTrigger::Sticker).await.unwrap();
+        store.finish_session(chat_id, 1714557600, 1714559400, 1800, StopTrigger::SitSticker).await.unwrap();
+        store.start_session(chat_id, 1714561200, store::StartTrigger::Sticker).await.unwrap();
+        store.finish_session(chat_id, 1714561200, 1714561800, 600, StopTrigger::LlmText).await.unwrap();
+
+        bot.update(MockMessageText::new().text("/undo"));
+        bot.dispatch_and_check_last_text("Отменили стояние на 0 часов 10 минут 0 секунд\nВсего за 2024-05-01: 0 часов 30 минут 0 секунд").await;
+
+        bot.update(MockMessageText::new().text("/undo reopen").date(chrono::DateTime::from_timestamp(1714560000, 0).unwrap()));
+        bot.dispatch_and_check_last_text_and_state("Продолжаем стояние, уже 40 минут 0 секунд", State::ReceiveStandingCommand { chat_id, timestamp: 1714557600 }).await;
+        assert_eq!(store.get_total_timestamp_day(1714557600, chat_id).await.unwrap(), Some(0));
+
+        bot.update(MockMessageText::new().text("/undo"));
+        bot.dispatch_and_check_last_text("Сначала закончите текущее стояние.").await;
+    }
+
+    #[tokio::test]
+    async fn test_member_undo() {
+        let store: Store = Arc::new(MemoryStore::default());
+        let chat = MockGroupChat::new().build();
+        let alice = MockUser::new().id(1).first_name("Alice").build();
+        let bob = MockUser::new().id(2).first_name("Bob").build();
+        let start = 1714557600; // 2024-05-01 10:00 UTC
+
+        let bot = MockBot::new(sticker_at(&chat, &alice, sticker_handling::STICKER_STAND, start), schema());
+        bot.dependencies(dependencies(store.clone()));
+        bot.dispatch().await;
+        for update in [
+            sticker_at(&chat, &bob, sticker_handling::STICKER_STAND, start),
+            sticker_at(&chat, &alice, sticker_handling::STICKER_SIT, start + 1200),
+            sticker_at(&chat, &bob, sticker_handling::STICKER_SIT, start + 3000),
+            sticker_at(&chat, &alice, sticker_handling::STICKER_STAND, start + 1800),
+            sticker_at(&chat, &alice, sticker_handling::STICKER_SIT, start + 2400),
+        ] {
+            bot.update(update);
+            bot.dispatch().await;
+        }
+
+        bot.update(MockMessageText::new().text("/undo").chat(chat.clone()).from(alice.clone()).date(chrono::DateTime::from_timestamp(start + 3600, 0).unwrap()));
+        bot.dispatch_and_check_last_text("Отменили стояние на 0 часов 10 минут 0 секунд\nВсего за 2024-05-01: 0 часов 20 минут 0 секунд").await;
+    }
+
+    #[tokio::test]
+    async fn test_period_arguments() {
+        let store: Store = Arc::new(MemoryStore::default());
+        let bot = MockBot::new(MockMessageText::new().text("/total someday"), schema());
+        bot.dependencies(dependencies(store.clone()));
+        bot.dispatch_and_check_last_text("Не понял период. Примеры: week, last-week, 2026-09, 2026, 30d, 2026-01-01..2026-03-31").await;
+
+        bot.update(MockMessageText::new().text("/rankings 2020"));
+        bot.dispatch_and_check_last_text("Никто не стоял за 2020 год.").await;
+
+        bot.update(MockMessageText::new().text("/total 4000000000d"));
+        bot.dispatch_and_check_last_text("Не понял период. Примеры: week, last-week, 2026-09, 2026, 30d, 2026-01-01..2026-03-31").await;
+
+        bot.update(MockMessageText::new().text("/rankings 262142-12"));
+        bot.dispatch_and_check_last_text("Не понял период. Примеры: week, last-week, 2026-09, 2026, 30d, 2026-01-01..2026-03-31").await;
+
+        bot.update(MockMessageText::new().text("/avgmonth"));
+        bot.dispatch_and_check_last_text("Никто не стоял за этот месяц.").await;
+    }
+
+    #[tokio::test]
+    async fn test_top() {
+        let store: Store = Arc::new(MemoryStore::default());
+        let message = MockMessageText::new().text("/top");
+        let chat_id = message.chat.id;
+        for (user_id, name, seconds) in [(1, "Alice", 600), (2, "Bob", 1200)] {
+            store.start_member_session(chat_id, UserId(user_id), name, 1714557600).await.unwrap();
+            store.finish_member_session(chat_id, UserId(user_id), 1714557600 + seconds, StopTrigger::SitSticker).await.unwrap();
+        }
+
+        let bot = MockBot::new(message, schema());
+        bot.dependencies(dependencies(store.clone()));
+        bot.dispatch_and_check_last_text("<b>Топ чата</b>\n🥇 1. Bob — <b>0 часов 20 минут 0 секунд</b>\n🥈 2. Alice — <b>0 часов 10 минут 0 секунд</b>\n      −0 часов 10 минут 0 секунд до лидера").await;
+
+        bot.update(MockMessageText::new().text("/top 2024-05-01..2024-05-01"));
+        bot.dispatch_and_check_last_text("<b>Топ чата за 2024-05-01</b>\n🥇 1. Bob — <b>0 часов 20 минут 0 секунд</b>\n🥈 2. Alice — <b>0 часов 10 минут 0 секунд</b>\n      −0 часов 10 минут 0 секунд до лидера").await;
+
+        bot.update(MockMessageText::new().text("/top week"));
+        bot.dispatch_and_check_last_text("Никто не стоял за эту неделю.").await;
+
+        bot.update(MockMessageText::new().text("/top yesterday"));
+        bot.dispatch_and_check_last_text("Не понял период. Примеры: week, month, 7d, 30d, 2026-01-01..2026-03-31").await;
+    }
+
+    #[tokio::test]
+    async fn test_hall_of_fame() {
+        let store: Store = Arc::new(MemoryStore::default());
+        let bot = MockBot::new(MockMessageText::new().text("/halloffame"), schema());
+        bot.dependencies(dependencies(store.clone()));
+        bot.dispatch_and_check_last_text("Чемпионов пока нет: они появятся, когда закончится неделя.").await;
+
+        let date = |d| chrono::NaiveDate::from_ymd_opt(2024, 5, d).unwrap();
+        for (chat_id, title, days) in [(-1, "Standing", vec![(date(7), 7000)]), (-2, "Sitting", vec![(date(1), 700), (date(8), 1400)])] {
+            store.save_chat(&store::ChatInfo { chat_id, title: Some(title.to_string()), username: None, kind: "supergroup".to_string(), active: true }).await.unwrap();
+            store.import_daily_totals(ChatId(chat_id), &days, MergePolicy::Add).await.unwrap();
+        }
+        hall_of_fame::record_closed(store.as_ref(), date(15)).await.unwrap();
+
+        bot.update(MockMessageText::new().text("/halloffame"));
+        bot.dispatch_and_check_last_text("🏆 Зал славы\n\
+                                          Титулы:\n\
+                                          1. Sitting — 1 (недель: 1)\n\
+                                          2. Standing — 1 (недель: 1)\n\
+                                          \n\
+                                          Чемпионы недель:\n\
+                                          неделя с 2024-05-06 — Standing, 16м в день\n\
+                                          неделя с 2024-04-29 — Sitting, 1м в день").await;
+    }
+
+    #[tokio::test]
+    async fn test_announcements() {
+        let store: Store = Arc::new(MemoryStore::default());
+        let message = MockMessageText::new().text("/announcements");
+        let chat_id = message.chat.id;
+        let bot = MockBot::new(message, schema());
+        bot.dependencies(dependencies(store.clone()));
+        bot.dispatch_and_check_last_text("Итоги недели, месяца и года среди чатов приходят сюда. Выключить: /announcements off").await;
+
+        bot.update(MockMessageText::new().text("/announcements off"));
+        bot.dispatch_and_check_last_text("Итоги недели, месяца и года среди чатов выключены. Включить: /announcements on").await;
+        assert!(!store.get_announcements(chat_id).await.unwrap());
+
+        bot.update(MockMessageText::new().text("/announcements maybe"));
+        bot.dispatch_and_check_last_text("Не понял. Пример: /announcements off").await;
+
+        bot.update(MockMessageText::new().text("/announcements on"));
+        bot.dispatch_and_check_last_text("Итоги недели, месяца и года среди чатов приходят сюда. Выключить: /announcements off").await;
+    }
+
+    #[tokio::test]
+    async fn test_rankings_from_chat_cache() {
+        let store: Store = Arc::new(MemoryStore::default());
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        for (chat_id, title, active, seconds) in [(-1, "Standing", true, 600), (-2, "Sitting", true, 1200), (-3, "Left", false, 1800)] {
+            store.save_chat(&store::ChatInfo { chat_id, title: Some(title.to_string()), username: None, kind: "supergroup".to_string(), active }).await.unwrap();
+            store.import_daily_totals(ChatId(chat_id), &[(date, seconds)], MergePolicy::Add).await.unwrap();
+        }
+
+        let message = MockMessageText::new().text("/total 2024-05");
+        let chat_id = message.chat.id;
+        let bot = MockBot::new(message, schema());
+        bot.dependencies(dependencies(store.clone()));
+        bot.dispatch_and_check_last_text("<b>Всего постояли за 2024-05</b>\n🥇 1. Sitting — <b>0 часов 20 минут 0 секунд</b>\n🥈 2. Standing — <b>0 часов 10 минут 0 секунд</b>\n      −0 часов 10 минут 0 секунд до лидера").await;
+
+        // The chat the command came from is cached too
+        assert!(store.get_chats().await.unwrap().iter().any(|chat| chat.chat_id == chat_id.0 && chat.kind == "private" && chat.active));
+
+        // The left chat led in April, the others only swapped places
+        let april = chrono::NaiveDate::from_ymd_opt(2024, 4, 1).unwrap();
+        for (chat_id, seconds) in [(-1, 1200), (-2, 600), (-3, 1800)] {
+            store.import_daily_totals(ChatId(chat_id), &[(april, seconds)], MergePolicy::Add).await.unwrap();
+        }
+        bot.update(MockMessageText::new().text("/total 2024-05"));
+        bot.dispatch().await;
+        let text = sent_texts(&bot).last().unwrap().clone();
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines[1].starts_with("🥇 1. Sitting") && lines[1].ends_with(" ▲1"), "{text}");
+        assert!(lines[2].starts_with("🥈 2. Standing") && lines[2].ends_with(" ▼1"), "{text}");
+    }
+
+    #[tokio::test]
+    async fn test_streak() {
+        let store: Store = Arc::new(MemoryStore::default());
+        let chat = MockGroupChat::new().build();
+        let user = MockUser::new().first_name("Alice").build();
+        let day = 86400;
+        let start = 1714557600; // 2024-05-01 10:00 UTC
+        for offset in [0, day] {
+            store.start_member_session(chat.id, user.id, "Alice", start + offset).await.unwrap();
+            store.finish_member_session(chat.id, user.id, start + offset + 600, StopTrigger::SitSticker).await.unwrap();
+        }
+
+        let bot = MockBot::new(sticker_at(&chat, &user, sticker_handling::STICKER_STAND, start + 2 * day), schema());
+        bot.dependencies(dependencies(store.clone()));
+        bot.dispatch().await;
+        bot.update(sticker_at(&chat, &user, sticker_handling::STICKER_SIT, start + 2 * day + 600));
+        bot.dispatch().await;
+        let texts = sent_texts(&bot);
+        assert!(texts.contains(&"🏆 Серия Alice: 3 дня подряд — новый рекорд!".to_string()));
+        assert!(texts.contains(&"🏆 Серия чата: 3 дня подряд — новый рекорд!".to_string()));
+
+        bot.update(MockMessageText::new().chat(chat.clone()).from(user.clone()).text("/streak").date(chrono::DateTime::from_timestamp(start + 2 * day + 700, 0).unwrap()));
+        bot.dispatch_and_check_last_text("Серия чата: 3 дня подряд\nРекорд: 3 дня\nСерия Alice: 3 дня подряд, рекорд: 3 дня\nДень засчитывается от 0 часов 10 минут 0 секунд").await;
+    }
+
+    #[tokio::test]
+    async fn test_goal() {
+        let store: Store = Arc::new(MemoryStore::default());
+        let bot = MockBot::new(MockMessageText::new().text("/goal"), schema());
+        bot.dependencies(dependencies(store.clone()));
+        bot.dispatch_and_check_last_text("Целей нет. Пример: /goal 2h или /goal week 10h").await;
+
+        bot.update(MockMessageText::new().text("/goal 20m"));
+        bot.dispatch_and_check_last_text("Цель дня: 0м из 20м (0%)").await;
+        bot.update(MockMessageText::new().text("/goal week 1h"));
+        bot.dispatch_and_check_last_text("Цель дня: 0м из 20м (0%)\nЦель недели: 0м из 1ч (0%)").await;
+
+        bot.update(MockMessageText::new().text("/goal off"));
+        bot.dispatch_and_check_last_text("Цель недели: 0м из 1ч (0%)").await;
+
+        // A finished session shows the chat's progress and celebrates the reached goal once
+        let chat = MockGroupChat::new().build();
+        let user = MockUser::new().first_name("Alice").build();
+        store.set_goal(chat.id, GoalKind::Day, Some(1200)).await.unwrap();
+        store.set_goal(chat.id, GoalKind::Week, Some(3600)).await.unwrap();
+        let start = 1714557600; // 2024-05-01 10:00 UTC
+        for (minutes, last) in [(0, "Всего сегодня у Alice: 0 часов 15 минут 0 секунд\nЦель дня: 15м из 20м (75%)\nЦель недели: 15м из 1ч (25%)"),
+                                (30, "🎉 Цель дня выполнена: 20м!"),
+                                (60, "Всего сегодня у Alice: 0 часов 45 минут 0 секунд\nЦель дня: 45м из 20м (225%)\nЦель недели: 45м из 1ч (75%)")] {
+            bot.update(sticker_at(&chat, &user, sticker_handling::STICKER_STAND, start + minutes * 60));
+            bot.dispatch().await;
+            bot.update(sticker_at(&chat, &user, sticker_handling::STICKER_SIT, start + (minutes + 15) * 60));
+            bot.dispatch_and_check_last_text(last).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_digest() {
+        let store: Store = Arc::new(MemoryStore::default());
+        let bot = MockBot::new(MockMessageText::new().text("/digest"), schema());
+        bot.dependencies(dependencies(store.clone()));
+        bot.dispatch_and_check_last_text("Итоги по расписанию выключены. Пример: /digest 21:00 или /digest week 21:00").await;
+
+        bot.update(MockMessageText::new().text("/digest 21:00"));
+        bot.dispatch_and_check_last_text("Итоги дня: каждый день в 21:00").await;
+        bot.update(MockMessageText::new().text("/digest week 20:30"));
+        bot.dispatch_and_check_last_text("Итоги дня: каждый день в 21:00\nИтоги недели: в последний день недели в 20:30").await;
+        bot.update(MockMessageText::new().text("/digest off"));
+        bot.dispatch_and_check_last_text("Итоги недели: в последний день недели в 20:30").await;
+        bot.update(MockMessageText::new().text("/digest 25:00"));
+        bot.dispatch_and_check_last_text("Не понял. Пример: /digest 21:00 или /digest week 21:00").await;
+    }
+
+    #[tokio::test]
+    async fn test_chart() {
+        let store: Store = Arc::new(MemoryStore::default());
+        let bot = MockBot::new(MockMessageText::new().text("/chart"), schema());
+        bot.dependencies(dependencies(store.clone()));
+        bot.dispatch_and_check_last_text("Нечего показывать.").await;
+
+        // The mock server only takes UTF-8 uploads, so the PNG itself is tested in chart.rs
+        bot.update(MockMessageText::new().text("/chart nope"));
+        bot.dispatch_and_check_last_text("Не понял. Пример: /chart 7d, /chart 30d или /chart year").await;
+
+        let chat_id = MockMessageText::new().build().chat.id;
+        store.import_daily_totals(chat_id, &[(chrono::NaiveDate::from_ymd_opt(2020, 1, 1).unwrap(), 600)], MergePolicy::Add).await.unwrap();
+        bot.update(MockMessageText::new().text("/chart all"));
+        bot.dispatch_and_check_last_text("Слишком длинный период, график строится максимум за 366 дней.").await;
+    }
+
+    #[tokio::test]
+    async fn test_heatmap() {
+        let store: Store = Arc::new(MemoryStore::default());
+        let bot = MockBot::new(MockMessageText::new().text("/heatmap"), schema());
+        bot.dependencies(dependencies(store.clone()));
+        bot.dispatch_and_check_last_text("Нечего показывать.").await;
+
+        bot.update(MockMessageText::new().text("/heatmap @nobody"));
+        bot.dispatch_and_check_last_text("Не нашёл такой чат. Пример: /heatmap @chat").await;
+    }
+
+    #[tokio::test]
+    async fn test_stats() {
+        let store: Store = Arc::new(MemoryStore::default());
+        let now = chrono::DateTime::from_timestamp(1714557600, 0).unwrap(); // 2024-05-01 10:00 UTC
+        let stats = |text: &str| MockMessageText::new().text(text).date(now);
+        let bot = MockBot::new(stats("/stats"), schema());
+        bot.dependencies(dependencies(store.clone()));
+        bot.dispatch_and_check_last_text("Никто не стоял за всё время.").await;
+
+        let chat_id = MockMessageText::new().build().chat.id;
+        store.import_daily_totals(chat_id, &[(chrono::NaiveDate::from_ymd_opt(2024, 4, 28).unwrap(), 3 * 3600)], MergePolicy::Add).await.unwrap();
+        bot.update(stats("/stats"));
+        bot.dispatch_and_check_last_text("Статистика за всё время (2024-04-28 — 2024-05-01)\nВсего: 3ч\nВ среднем за день: 45м\nМедиана: 0м\nЛучший день: 2024-04-28 — 3ч\nДней со стоянием: 1 из 4\nСтандартное отклонение: 1ч 17м").await;
+
+        bot.update(stats("/stats 2000d"));
+        bot.dispatch_and_check_last_text("Слишком длинный период, статистика считается максимум за 366 дней.").await;
+
+        bot.update(stats("/stats nope"));
+        bot.dispatch_and_check_last_text("Не понял период. Примеры: week, month, 2026, 30d, 2026-01-01..2026-03-31").await;
+    }
+
+    #[tokio::test]
+    async fn test_records() {
+        let store: Store = Arc::new(MemoryStore::default());
+        let chat = MockGroupChat::new().build();
+        let user = MockUser::new().first_name("Alice").build();
+        let start = 1714557600; // 2024-05-01 10:00 UTC
+        store.start_member_session(chat.id, user.id, "Alice", start).await.unwrap();
+        store.finish_member_session(chat.id, user.id, start + 1200, StopTrigger::SitSticker).await.unwrap();
+
+        let bot = MockBot::new(MockMessageText::new().text("/records"), schema());
+        bot.dependencies(dependencies(store.clone()));
+        bot.dispatch_and_check_last_text("Рекордов пока нет.").await;
+
+        // A longer session the next day beats the session and day records
+        bot.update(sticker_at(&chat, &user, sticker_handling::STICKER_STAND, start + 86400));
+        bot.dispatch().await;
+        bot.update(sticker_at(&chat, &user, sticker_handling::STICKER_SIT, start + 86400 + 1800));
+        bot.dispatch().await;
+        let texts = sent_texts(&bot);
+        assert!(texts.contains(&"🏅 Рекорд Alice — самое долгое стояние: 30м (было 20м)".to_string()), "{texts:?}");
+        assert!(texts.contains(&"🏅 Рекорд чата — лучший день: 30м (было 20м)".to_string()));
+
+        bot.update(MockMessageText::new().chat(chat.clone()).from(user.clone()).text("/records"));
+        bot.dispatch_and_check_last_text("Рекорды чата:\nСамое долгое стояние: 30м (2024-05-02)\nЛучший день: 30м (2024-05-02)\nЛучшая неделя: 50м (неделя с 2024-04-29)\nЛучший месяц: 50м (2024-05)\n\nРекорды Alice:\nСамое долгое стояние: 30м (2024-05-02)\nЛучший день: 30м (2024-05-02)\nЛучшая неделя: 50м (неделя с 2024-04-29)\nЛучший месяц: 50м (2024-05)").await;
+    }
+
+    /// A user picking a chat with a keyboard button, the mocks have no such message
+    struct MockChatShared(teloxide::types::Chat, RequestId, ChatId);
+
+    impl teloxide_tests::IntoUpdate for MockChatShared {
+        fn into_update(self, id: std::sync::atomic::AtomicI32) -> Vec<Update> {
+            let mut message = MockMessageText::new().chat(self.0).build();
+            message.kind = MessageKind::ChatShared(MessageChatShared { chat_shared: teloxide::types::ChatShared { request_id: self.1, chat_id: self.2 } });
+            vec![Update { id: teloxide::types::UpdateId(id.fetch_add(1, std::sync::atomic::Ordering::Relaxed) as u32), kind: teloxide::types::UpdateKind::Message(message) }]
+        }
+    }
+
+    #[tokio::test]
+    async fn test_compare() {
+        let store: Store = Arc::new(MemoryStore::default());
+        let chat = MockPrivateChat::new().first_name("Мы").build();
+        let chat_id = chat.id;
+        let rivals = ChatId(-100);
+        let date = |d| chrono::NaiveDate::from_ymd_opt(2024, 5, d).unwrap();
+        store.save_chat(&store::ChatInfo { chat_id: rivals.0, title: Some("Они".to_string()), username: Some("rivals".to_string()), kind: "channel".to_string(), active: true }).await.unwrap();
+        store.import_daily_totals(chat_id, &[(date(1), 3600), (date(2), 600)], MergePolicy::Add).await.unwrap();
+        store.import_daily_totals(rivals, &[(date(2), 1800)], MergePolicy::Add).await.unwrap();
+        let expected = "Мы vs Они с 2024-05-01 по 2024-05-03\n\
+                        Всего: 1ч 10м — 30м\n\
+                        В среднем за день: 23м — 10м\n\
+                        Серия: 0 дней — 0 дней\n\
+                        Лучший день: 1ч (2024-05-01) — 30м (2024-05-02)\n\
+                        Дней со стоянием: 2 — 1\n\
+                        Победы по дням: 1 — 1, ничьих: 0";
+
+        let bot = MockBot::new(MockMessageText::new().chat(chat.clone()).text("/compare @rivals 2024-05-01..2024-05-03"), schema());
+        bot.dependencies(dependencies(store.clone()));
+        bot.dispatch_and_check_last_text(expected).await;
+
+        bot.update(MockMessageText::new().chat(chat.clone()).text("/compare @nobody"));
+        bot.dispatch_and_check_last_text("Не нашёл такой чат. Пример: /compare @chat month").await;
+
+        // Without a chat the keyboard picks it and the period waits for it
+        bot.update(MockMessageText::new().chat(chat.clone()).text("/compare 2024-05-01..2024-05-03"));
+        bot.dispatch_and_check_last_text("С каким чатом сравнить?").await;
+        bot.update(MockChatShared(chat.clone(), RequestId(2), rivals));
+        bot.dispatch_and_check_last_text(expected).await;
+    }
+
+    #[tokio::test]
+    async fn test_patterns() {
+        let store: Store = Arc::new(MemoryStore::default());
+        let bot = MockBot::new(MockMessageText::new().text("/patterns"), schema());
+        bot.dependencies(dependencies(store.clone()));
+        bot.dispatch_and_check_last_text("Никто не стоял за всё время.").await;
+
+        let chat_id = MockMessageText::new().build().chat.id;
+        let monday = 1714953600; // 2024-05-06 00:00 UTC
+        store.set_utc_offset(chat_id, 180).await.unwrap();
+        store.start_session(chat_id, monday + 6 * 3600, store::StartTrigger::Sticker).await.unwrap();
+        store.finish_session(chat_id, monday + 6 * 3600, monday + 7 * 3600, 3600, StopTrigger::SitSticker).await.unwrap();
+        bot.update(MockMessageText::new().text("/patterns"));
+        bot.dispatch_and_check_last_text("Когда стоим за всё время, время UTC+03:00:\n<pre>По часам:\n09 ████████████ 1ч\n\nПо дням недели:\nПн ████████████ 1ч\nВт\nСр\nЧт\nПт\nСб\nВс</pre>").await;
+    }
 }
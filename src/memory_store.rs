@@ -0,0 +1,549 @@
+use std::{cmp::Reverse, collections::{BTreeMap, HashMap, HashSet}, sync::Mutex};
+
+use async_trait::async_trait;
+use chrono::{NaiveDate, Utc, Weekday};
+use teloxide::types::{ChatId, UserId};
+
+use crate::{digest::{Digest, DigestKind}, goal::{GoalKind, Goals}, hall_of_fame::{Champion, TitleKind}, period::DateRange, streak::DEFAULT_MINIMUM_SECONDS, store::{Adjustment, ChatInfo, ChatTotal, MergePolicy, SessionRecord, StandingStore, StartTrigger, StopTrigger, StoreResult}, time::{credited_seconds, split_by_day, DayBoundary}};
+
+struct Session {
+    chat_id: i64,
+    user_id: Option<u64>,
+    start_timestamp: i64,
+    end_timestamp: Option<i64>,
+    seconds: Option<i64>,
+    start_trigger: Option<StartTrigger>,
+    stop_trigger: Option<StopTrigger>,
+    undone_at: Option<i64>,
+    /// The chat's boundary when the session was credited
+    boundary: Option<DayBoundary>,
+}
+
+#[derive(Default)]
+struct Data {
+    total: BTreeMap<(i64, NaiveDate), i64>,
+    member_total: BTreeMap<(i64, u64, NaiveDate), i64>,
+    members: HashMap<(i64, u64), String>,
+    sessions: Vec<Session>,
+    settings: HashMap<i64, DayBoundary>,
+    adjustments: Vec<(i64, Adjustment)>,
+    chats: BTreeMap<i64, ChatInfo>,
+    streak_minimums: HashMap<i64, i64>,
+    week_starts: HashMap<i64, Weekday>,
+    announcements: HashMap<i64, bool>,
+    goals: HashMap<i64, Goals>,
+    goals_reached: HashSet<(i64, &'static str, NaiveDate)>,
+    digests: BTreeMap<(i64, &'static str), Digest>,
+    /// With whether it was announced
+    champions: BTreeMap<(NaiveDate, &'static str), (Champion, bool)>,
+}
+
+impl Data {
+    fn boundary(&self, chat_id: i64) -> DayBoundary {
+        self.settings.get(&chat_id).copied().unwrap_or_default()
+    }
+}
+
+fn in_range(range: DateRange, date: NaiveDate) -> bool {
+    range.start.is_none_or(|start| date >= start) && range.end.is_none_or(|end| date <= end)
+}
+
+fn record(index: usize, session: &Session) -> SessionRecord {
+    SessionRecord {
+        id: index as i64 + 1,
+        user_id: session.user_id.map(|user_id| user_id as i64),
+        start_timestamp: session.start_timestamp,
+        end_timestamp: session.end_timestamp,
+        seconds: session.seconds,
+        start_trigger: session.start_trigger.map(|trigger| trigger.as_str().to_string()),
+        stop_trigger: session.stop_trigger.map(|trigger| trigger.as_str().to_string()),
+    }
+}
+
+/// Store that keeps everything in memory, for tests that shouldn't touch files
+#[derive(Default)]
+pub struct MemoryStore {
+    data: Mutex<Data>,
+}
+
+#[async_trait]
+impl StandingStore for MemoryStore {
+    async fn get_day_boundary(&self, ChatId(chat_id): ChatId) -> StoreResult<DayBoundary> {
+        Ok(self.data.lock().unwrap().boundary(chat_id))
+    }
+
+    async fn set_utc_offset(&self, ChatId(chat_id): ChatId, utc_offset_minutes: i32) -> StoreResult<()> {
+        self.data.lock().unwrap().settings.entry(chat_id).or_default().utc_offset_minutes = utc_offset_minutes;
+        Ok(())
+    }
+
+    async fn set_day_start(&self, ChatId(chat_id): ChatId, day_start_minutes: i32) -> StoreResult<()> {
+        self.data.lock().unwrap().settings.entry(chat_id).or_default().day_start_minutes = day_start_minutes;
+        Ok(())
+    }
+
+    async fn start_session(&self, ChatId(chat_id): ChatId, timestamp: i64, trigger: StartTrigger) -> StoreResult<()> {
+        self.data.lock().unwrap().sessions.push(Session {
+            chat_id,
+            user_id: None,
+            start_timestamp: timestamp,
+            end_timestamp: None,
+            seconds: None,
+            start_trigger: Some(trigger),
+            stop_trigger: None,
+            undone_at: None,
+            boundary: None,
+        });
+        Ok(())
+    }
+
+    async fn finish_session(&self,
+                            ChatId(chat_id): ChatId,
+                            start_timestamp: i64,
+                            end_timestamp: i64,
+                            seconds: i64,
+                            trigger: StopTrigger) -> StoreResult<i64> {
+        let mut data = self.data.lock().unwrap();
+        let seconds = credited_seconds(start_timestamp, end_timestamp, seconds);
+        let boundary = data.boundary(chat_id);
+        let open = data.sessions.iter_mut().find(|session| {
+            session.chat_id == chat_id
+                && session.user_id.is_none()
+                && session.start_timestamp == start_timestamp
+                && session.end_timestamp.is_none()
+        });
+        match open {
+            Some(session) => {
+                session.end_timestamp = Some(end_timestamp);
+                session.seconds = Some(seconds);
+                session.stop_trigger = Some(trigger);
+                session.boundary = Some(boundary);
+            }
+            None => data.sessions.push(Session {
+                chat_id,
+                user_id: None,
+                start_timestamp,
+                end_timestamp: Some(end_timestamp),
+                seconds: Some(seconds),
+                start_trigger: None,
+                stop_trigger: Some(trigger),
+                undone_at: None,
+                boundary: Some(boundary),
+            }),
+        }
+        for (date, day_seconds) in split_by_day(end_timestamp - seconds, end_timestamp, boundary) {
+            *data.total.entry((chat_id, date)).or_default() += day_seconds;
+        }
+        Ok(data.total.get(&(chat_id, boundary.date_of(end_timestamp))).copied().unwrap_or(0))
+    }
+
+    async fn start_member_session(&self, ChatId(chat_id): ChatId, UserId(user_id): UserId, name: &str, timestamp: i64) -> StoreResult<bool> {
+        let mut data = self.data.lock().unwrap();
+        data.members.insert((chat_id, user_id), name.to_string());
+        if data.sessions.iter().any(|session| session.chat_id == chat_id && session.user_id == Some(user_id) && session.end_timestamp.is_none()) {
+            return Ok(false);
+        }
+        data.sessions.push(Session {
+            chat_id,
+            user_id: Some(user_id),
+            start_timestamp: timestamp,
+            end_timestamp: None,
+            seconds: None,
+            start_trigger: Some(StartTrigger::Sticker),
+            stop_trigger: None,
+            undone_at: None,
+            boundary: None,
+        });
+        Ok(true)
+    }
+
+    async fn get_member_session_start(&self, ChatId(chat_id): ChatId, UserId(user_id): UserId) -> StoreResult<Option<i64>> {
+        Ok(self.data.lock().unwrap().sessions.iter()
+           .find(|session| session.chat_id == chat_id && session.user_id == Some(user_id) && session.end_timestamp.is_none())
+           .map(|session| session.start_timestamp))
+    }
+
+    async fn finish_member_session(&self, ChatId(chat_id): ChatId, UserId(user_id): UserId, end_timestamp: i64, trigger: StopTrigger) -> StoreResult<Option<(i64, i64)>> {
+        let mut data = self.data.lock().unwrap();
+        let boundary = data.boundary(chat_id);
+        let Some(session) = data.sessions.iter_mut()
+            .find(|session| session.chat_id == chat_id && session.user_id == Some(user_id) && session.end_timestamp.is_none()) else {
+            return Ok(None);
+        };
+        let start_timestamp = session.start_timestamp;
+        session.end_timestamp = Some(end_timestamp);
+        session.seconds = Some(end_timestamp - start_timestamp);
+        session.stop_trigger = Some(trigger);
+        session.boundary = Some(boundary);
+
+        for (date, day_seconds) in split_by_day(start_timestamp, end_timestamp, boundary) {
+            *data.total.entry((chat_id, date)).or_default() += day_seconds;
+            *data.member_total.entry((chat_id, user_id, date)).or_default() += day_seconds;
+        }
+        let member_total = data.member_total.get(&(chat_id, user_id, boundary.date_of(end_timestamp))).copied().unwrap_or(0);
+        Ok(Some((start_timestamp, member_total)))
+    }
+
+    async fn get_member_totals(&self, ChatId(chat_id): ChatId, range: DateRange) -> StoreResult<Vec<(i64, String, i64)>> {
+        let data = self.data.lock().unwrap();
+        let mut totals: BTreeMap<u64, i64> = BTreeMap::new();
+        for (&(row_chat_id, user_id, date), &seconds) in &data.member_total {
+            if row_chat_id == chat_id && in_range(range, date) {
+                *totals.entry(user_id).or_default() += seconds;
+            }
+        }
+        let mut result: Vec<(i64, String, i64)> = totals.into_iter()
+            .filter_map(|(user_id, seconds)| {
+                data.members.get(&(chat_id, user_id)).map(|name| (user_id as i64, name.clone(), seconds))
+            })
+            .collect();
+        result.sort_by_key(|row| Reverse(row.2));
+        Ok(result)
+    }
+
+    async fn save_chat(&self, chat: &ChatInfo) -> StoreResult<()> {
+        self.data.lock().unwrap().chats.insert(chat.chat_id, chat.clone());
+        Ok(())
+    }
+
+    async fn get_chats(&self) -> StoreResult<Vec<ChatInfo>> {
+        Ok(self.data.lock().unwrap().chats.values().cloned().collect())
+    }
+
+    async fn get_chat_totals(&self, range: DateRange) -> StoreResult<Vec<ChatTotal>> {
+        let mut totals: BTreeMap<i64, ChatTotal> = BTreeMap::new();
+        for (&(chat_id, date), &seconds) in &self.data.lock().unwrap().total {
+            if in_range(range, date) {
+                let total = totals.entry(chat_id).or_insert(ChatTotal { chat_id, seconds: 0, days: 0, first: date });
+                total.seconds += seconds;
+                total.days += 1;
+            }
+        }
+        Ok(totals.into_values().collect())
+    }
+
+    async fn get_total_timestamp_day(&self, timestamp: i64, ChatId(chat_id): ChatId) -> StoreResult<Option<i64>> {
+        let data = self.data.lock().unwrap();
+        let date = data.boundary(chat_id).date_of(timestamp);
+        Ok(data.total.get(&(chat_id, date)).copied())
+    }
+
+    async fn get_daily_totals(&self, ChatId(chat_id): ChatId, range: DateRange) -> StoreResult<Vec<(NaiveDate, i64)>> {
+        Ok(self.data.lock().unwrap().total.iter()
+           .filter(|((row_chat_id, date), _)| *row_chat_id == chat_id && in_range(range, *date))
+           .map(|((_, date), seconds)| (*date, *seconds))
+           .collect())
+    }
+
+    async fn get_member_daily_totals(&self, ChatId(chat_id): ChatId, UserId(user_id): UserId, range: DateRange) -> StoreResult<Vec<(NaiveDate, i64)>> {
+        Ok(self.data.lock().unwrap().member_total.iter()
+           .filter(|((row_chat_id, row_user_id, date), _)| *row_chat_id == chat_id && *row_user_id == user_id && in_range(range, *date))
+           .map(|((_, _, date), seconds)| (*date, *seconds))
+           .collect())
+    }
+
+    async fn get_streak_minimum(&self, ChatId(chat_id): ChatId) -> StoreResult<i64> {
+        Ok(self.data.lock().unwrap().streak_minimums.get(&chat_id).copied().unwrap_or(DEFAULT_MINIMUM_SECONDS))
+    }
+
+    async fn set_streak_minimum(&self, ChatId(chat_id): ChatId, seconds: i64) -> StoreResult<()> {
+        self.data.lock().unwrap().streak_minimums.insert(chat_id, seconds);
+        Ok(())
+    }
+
+    async fn get_week_start(&self, ChatId(chat_id): ChatId) -> StoreResult<Weekday> {
+        Ok(self.data.lock().unwrap().week_starts.get(&chat_id).copied().unwrap_or(Weekday::Mon))
+    }
+
+    async fn set_week_start(&self, ChatId(chat_id): ChatId, week_start: Weekday) -> StoreResult<()> {
+        self.data.lock().unwrap().week_starts.insert(chat_id, week_start);
+        Ok(())
+    }
+
+    async fn get_announcements(&self, ChatId(chat_id): ChatId) -> StoreResult<bool> {
+        Ok(self.data.lock().unwrap().announcements.get(&chat_id).copied().unwrap_or(true))
+    }
+
+    async fn set_announcements(&self, ChatId(chat_id): ChatId, enabled: bool) -> StoreResult<()> {
+        self.data.lock().unwrap().announcements.insert(chat_id, enabled);
+        Ok(())
+    }
+
+    async fn get_goals(&self, ChatId(chat_id): ChatId) -> StoreResult<Goals> {
+        Ok(self.data.lock().unwrap().goals.get(&chat_id).copied().unwrap_or_default())
+    }
+
+    async fn set_goal(&self, ChatId(chat_id): ChatId, kind: GoalKind, seconds: Option<i64>) -> StoreResult<()> {
+        let mut data = self.data.lock().unwrap();
+        let goals = data.goals.entry(chat_id).or_default();
+        match kind {
+            GoalKind::Day => goals.day = seconds,
+            GoalKind::Week => goals.week = seconds,
+        }
+        Ok(())
+    }
+
+    async fn mark_goal_reached(&self, ChatId(chat_id): ChatId, kind: GoalKind, period_start: NaiveDate) -> StoreResult<bool> {
+        Ok(self.data.lock().unwrap().goals_reached.insert((chat_id, kind.as_str(), period_start)))
+    }
+
+    async fn get_digests(&self) -> StoreResult<Vec<Digest>> {
+        Ok(self.data.lock().unwrap().digests.values().cloned().collect())
+    }
+
+    async fn set_digest(&self, ChatId(chat_id): ChatId, kind: DigestKind, minutes: Option<i32>) -> StoreResult<()> {
+        let mut data = self.data.lock().unwrap();
+        match minutes {
+            Some(minutes) => data.digests.entry((chat_id, kind.as_str()))
+                .or_insert(Digest { chat_id, kind, minutes, last_sent: None })
+                .minutes = minutes,
+            None => {
+                data.digests.remove(&(chat_id, kind.as_str()));
+            }
+        }
+        Ok(())
+    }
+
+    async fn mark_digest_sent(&self, ChatId(chat_id): ChatId, kind: DigestKind, date: NaiveDate) -> StoreResult<()> {
+        if let Some(digest) = self.data.lock().unwrap().digests.get_mut(&(chat_id, kind.as_str())) {
+            digest.last_sent = Some(date);
+        }
+        Ok(())
+    }
+
+    async fn get_champions(&self) -> StoreResult<Vec<Champion>> {
+        Ok(self.data.lock().unwrap().champions.values().map(|(champion, _)| champion.clone()).collect())
+    }
+
+    async fn get_unannounced_champions(&self) -> StoreResult<Vec<Champion>> {
+        Ok(self.data.lock().unwrap().champions.values()
+           .filter(|(_, announced)| !announced)
+           .map(|(champion, _)| champion.clone())
+           .collect())
+    }
+
+    async fn add_champion(&self, champion: &Champion, announced: bool) -> StoreResult<()> {
+        self.data.lock().unwrap().champions.entry((champion.start, champion.kind.as_str())).or_insert_with(|| (champion.clone(), announced));
+        Ok(())
+    }
+
+    async fn mark_champion_announced(&self, kind: TitleKind, start: NaiveDate) -> StoreResult<()> {
+        if let Some((_, announced)) = self.data.lock().unwrap().champions.get_mut(&(start, kind.as_str())) {
+            *announced = true;
+        }
+        Ok(())
+    }
+
+    async fn get_sessions(&self, ChatId(chat_id): ChatId, range: DateRange) -> StoreResult<Vec<SessionRecord>> {
+        let data = self.data.lock().unwrap();
+        let boundary = data.boundary(chat_id);
+        let mut result: Vec<SessionRecord> = data.sessions.iter()
+            .enumerate()
+            .filter(|(_, session)| session.chat_id == chat_id && session.undone_at.is_none() && in_range(range, boundary.date_of(session.start_timestamp)))
+            .map(|(index, session)| record(index, session))
+            .collect();
+        result.sort_by_key(|session| (session.start_timestamp, session.id));
+        Ok(result)
+    }
+
+    async fn import_daily_totals(&self, ChatId(chat_id): ChatId, days: &[(NaiveDate, i64)], policy: MergePolicy) -> StoreResult<()> {
+        let mut data = self.data.lock().unwrap();
+        for (date, seconds) in days {
+            let total = data.total.entry((chat_id, *date)).or_default();
+            match policy {
+                MergePolicy::Replace => *total = *seconds,
+                MergePolicy::Add => *total += seconds,
+            }
+        }
+        Ok(())
+    }
+
+    async fn add_adjustment(&self, ChatId(chat_id): ChatId, adjustment: &Adjustment) -> StoreResult<i64> {
+        let mut data = self.data.lock().unwrap();
+        let total = data.total.entry((chat_id, adjustment.date)).or_default();
+        *total += adjustment.seconds;
+        let total = *total;
+        data.adjustments.push((chat_id, adjustment.clone()));
+        Ok(total)
+    }
+
+    async fn get_adjustments(&self, ChatId(chat_id): ChatId, limit: u32) -> StoreResult<Vec<Adjustment>> {
+        Ok(self.data.lock().unwrap().adjustments.iter()
+           .rev()
+           .filter(|(row_chat_id, _)| *row_chat_id == chat_id)
+           .take(limit as usize)
+           .map(|(_, adjustment)| adjustment.clone())
+           .collect())
+    }
+
+    async fn undo_last_session(&self, ChatId(chat_id): ChatId, user_id: Option<UserId>, reopen: bool, now: i64) -> StoreResult<Option<SessionRecord>> {
+        let mut data = self.data.lock().unwrap();
+        let user_id = user_id.map(|UserId(user_id)| user_id);
+        let Some((index, _)) = data.sessions.iter()
+            .enumerate()
+            .filter(|(_, session)| session.chat_id == chat_id && session.user_id == user_id && session.seconds.is_some() && session.undone_at.is_none())
+            .filter_map(|(index, session)| Some((index, (session.end_timestamp?, index))))
+            .max_by_key(|(_, key)| *key) else {
+            return Ok(None);
+        };
+        let result = record(index, &data.sessions[index]);
+        let boundary = data.sessions[index].boundary.unwrap_or_else(|| data.boundary(chat_id));
+
+        let end_timestamp = result.end_timestamp.unwrap_or_default();
+        for (date, day_seconds) in split_by_day(end_timestamp - result.seconds.unwrap_or_default(), end_timestamp, boundary) {
+            if let Some(total) = data.total.get_mut(&(chat_id, date)) {
+                *total = (*total - day_seconds).max(0);
+            }
+            if let Some(user_id) = user_id {
+                if let Some(total) = data.member_total.get_mut(&(chat_id, user_id, date)) {
+                    *total = (*total - day_seconds).max(0);
+                }
+            }
+        }
+        let session = &mut data.sessions[index];
+        if reopen {
+            session.end_timestamp = None;
+            session.seconds = None;
+            session.stop_trigger = None;
+        } else {
+            session.undone_at = Some(now);
+        }
+        Ok(Some(result))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::total_management::Total;
+
+    /// Runs the same sequence of operations against a store and returns everything it reports
+    async fn scenario(store: &dyn StandingStore) -> Vec<String> {
+        let day = |d: u32, h: u32, m: u32| Utc.with_ymd_and_hms(2024, 5, d, h, m, 0).unwrap().timestamp();
+        let mut results = Vec::new();
+
+        store.set_utc_offset(ChatId(1), 360).await.unwrap();
+        store.start_session(ChatId(1), day(1, 17, 0), StartTrigger::Sticker).await.unwrap();
+        results.push(format!("{:?}", store.finish_session(ChatId(1), day(1, 17, 0), day(1, 19, 0), 7200, StopTrigger::SitSticker).await.unwrap()));
+        results.push(format!("{:?}", store.finish_session(ChatId(2), day(2, 10, 0), day(2, 10, 30), 600, StopTrigger::Cancel).await.unwrap()));
+        results.push(format!("{:?}", store.finish_session(ChatId(3), day(2, 10, 0), day(2, 10, 30), i64::MAX, StopTrigger::Cancel).await.unwrap()));
+
+        results.push(format!("{:?}", store.start_member_session(ChatId(2), UserId(10), "Alice", day(2, 11, 0)).await.unwrap()));
+        results.push(format!("{:?}", store.start_member_session(ChatId(2), UserId(10), "Alice", day(2, 11, 5)).await.unwrap()));
+        results.push(format!("{:?}", store.start_member_session(ChatId(2), UserId(20), "Bob", day(2, 11, 10)).await.unwrap()));
+        results.push(format!("{:?}", store.get_member_session_start(ChatId(2), UserId(20)).await.unwrap()));
+        results.push(format!("{:?}", store.finish_member_session(ChatId(2), UserId(10), day(2, 12, 0), StopTrigger::SitSticker).await.unwrap()));
+        results.push(format!("{:?}", store.finish_member_session(ChatId(2), UserId(20), day(2, 11, 20), StopTrigger::SitSticker).await.unwrap()));
+        results.push(format!("{:?}", store.finish_member_session(ChatId(2), UserId(20), day(2, 11, 30), StopTrigger::SitSticker).await.unwrap()));
+        results.push(format!("{:?}", store.get_member_totals(ChatId(2), DateRange::default()).await.unwrap()));
+        results.push(format!("{:?}", store.get_member_totals(ChatId(2), DateRange { start: None, end: NaiveDate::from_ymd_opt(2024, 5, 1) }).await.unwrap()));
+        results.push(format!("{:?}", store.get_member_daily_totals(ChatId(2), UserId(10), DateRange::default()).await.unwrap()));
+        results.push(format!("{:?}", store.get_member_daily_totals(ChatId(2), UserId(30), DateRange::default()).await.unwrap()));
+        results.push(format!("{:?}", store.get_streak_minimum(ChatId(2)).await.unwrap()));
+        store.set_streak_minimum(ChatId(2), 60).await.unwrap();
+        store.set_utc_offset(ChatId(2), 0).await.unwrap();
+        results.push(format!("{:?}", store.get_streak_minimum(ChatId(2)).await.unwrap()));
+
+        store.set_week_start(ChatId(2), Weekday::Sun).await.unwrap();
+        store.set_week_start(ChatId(3), Weekday::Sat).await.unwrap();
+        store.set_week_start(ChatId(3), Weekday::Mon).await.unwrap();
+        for chat_id in [1, 2, 3] {
+            results.push(format!("{:?}", store.get_week_start(ChatId(chat_id)).await.unwrap()));
+        }
+
+        store.set_announcements(ChatId(2), false).await.unwrap();
+        store.set_announcements(ChatId(3), false).await.unwrap();
+        store.set_announcements(ChatId(3), true).await.unwrap();
+        for chat_id in [1, 2, 3] {
+            results.push(format!("{:?}", store.get_announcements(ChatId(chat_id)).await.unwrap()));
+        }
+
+        store.set_goal(ChatId(2), GoalKind::Day, Some(3600)).await.unwrap();
+        store.set_goal(ChatId(2), GoalKind::Week, Some(7200)).await.unwrap();
+        store.set_goal(ChatId(2), GoalKind::Day, None).await.unwrap();
+        results.push(format!("{:?}", store.get_goals(ChatId(2)).await.unwrap()));
+        results.push(format!("{:?}", store.get_goals(ChatId(1)).await.unwrap()));
+        let may_5 = NaiveDate::from_ymd_opt(2024, 5, 5).unwrap();
+        results.push(format!("{:?}", store.mark_goal_reached(ChatId(2), GoalKind::Week, may_5).await.unwrap()));
+        results.push(format!("{:?}", store.mark_goal_reached(ChatId(2), GoalKind::Week, may_5).await.unwrap()));
+        results.push(format!("{:?}", store.mark_goal_reached(ChatId(2), GoalKind::Day, may_5).await.unwrap()));
+
+        store.set_digest(ChatId(2), DigestKind::Week, Some(1200)).await.unwrap();
+        store.set_digest(ChatId(2), DigestKind::Day, Some(1260)).await.unwrap();
+        store.set_digest(ChatId(1), DigestKind::Day, Some(600)).await.unwrap();
+        store.mark_digest_sent(ChatId(2), DigestKind::Day, may_5).await.unwrap();
+        store.set_digest(ChatId(2), DigestKind::Day, Some(1320)).await.unwrap();
+        store.set_digest(ChatId(1), DigestKind::Day, None).await.unwrap();
+        results.push(format!("{:?}", store.get_digests().await.unwrap()));
+
+        let may_6 = NaiveDate::from_ymd_opt(2024, 5, 6).unwrap();
+        store.add_champion(&Champion { kind: TitleKind::Week, start: may_6, chat_id: 2, seconds: 600 }, false).await.unwrap();
+        store.add_champion(&Champion { kind: TitleKind::Week, start: may_6, chat_id: 1, seconds: 900 }, true).await.unwrap();
+        store.add_champion(&Champion { kind: TitleKind::Month, start: NaiveDate::from_ymd_opt(2024, 4, 1).unwrap(), chat_id: 1, seconds: 300 }, false).await.unwrap();
+        store.add_champion(&Champion { kind: TitleKind::Week, start: may_5, chat_id: 1, seconds: 300 }, true).await.unwrap();
+        results.push(format!("{:?}", store.get_champions().await.unwrap()));
+        results.push(format!("{:?}", store.get_unannounced_champions().await.unwrap()));
+        store.mark_champion_announced(TitleKind::Week, may_6).await.unwrap();
+        store.mark_champion_announced(TitleKind::Year, may_6).await.unwrap();
+        results.push(format!("{:?}", store.get_unannounced_champions().await.unwrap()));
+
+        results.push(format!("{:?}", store.get_total_timestamp_day(day(1, 17, 0), ChatId(1)).await.unwrap()));
+        results.push(format!("{:?}", store.get_total_timestamp_day(day(1, 19, 0), ChatId(1)).await.unwrap()));
+        results.push(format!("{:?}", store.get_total_timestamp_day(day(2, 10, 0), ChatId(2)).await.unwrap()));
+        results.push(format!("{:?}", store.get_chat_totals(DateRange::default()).await.unwrap()));
+        let mut chat = ChatInfo { chat_id: 2, title: Some("Standing".to_string()), username: None, kind: "supergroup".to_string(), active: true };
+        store.save_chat(&chat).await.unwrap();
+        chat.active = false;
+        store.save_chat(&chat).await.unwrap();
+        store.save_chat(&ChatInfo { chat_id: 1, title: None, username: Some("stand".to_string()), kind: "channel".to_string(), active: true }).await.unwrap();
+        results.push(format!("{:?}", store.get_chats().await.unwrap()));
+        let may_2 = DateRange { start: NaiveDate::from_ymd_opt(2024, 5, 2), end: None };
+        results.push(format!("{:?}", store.get_chat_totals(may_2).await.unwrap()));
+        results.push(format!("{:?}", store.get_day_boundary(ChatId(1)).await.unwrap()));
+        let range = DateRange { start: NaiveDate::from_ymd_opt(2024, 5, 2), end: NaiveDate::from_ymd_opt(2024, 5, 2) };
+        results.push(format!("{:?}", store.get_daily_totals(ChatId(1), range).await.unwrap()));
+        results.push(format!("{:?}", store.get_daily_totals(ChatId(2), DateRange::default()).await.unwrap()));
+        results.push(format!("{:?}", store.get_sessions(ChatId(2), DateRange::default()).await.unwrap()));
+        results.push(format!("{:?}", store.get_sessions(ChatId(1), range).await.unwrap()));
+
+        let imported = [(NaiveDate::from_ymd_opt(2024, 4, 30).unwrap(), 100), (NaiveDate::from_ymd_opt(2024, 5, 2).unwrap(), 50)];
+        store.import_daily_totals(ChatId(2), &imported, MergePolicy::Add).await.unwrap();
+        results.push(format!("{:?}", store.get_daily_totals(ChatId(2), DateRange::default()).await.unwrap()));
+        store.import_daily_totals(ChatId(2), &imported, MergePolicy::Replace).await.unwrap();
+        results.push(format!("{:?}", store.get_daily_totals(ChatId(2), DateRange::default()).await.unwrap()));
+
+        for (seconds, reason) in [(900, Some("forgot the sticker".to_string())), (-300, None), (60, None)] {
+            let adjustment = Adjustment { user_id: Some(10), user_name: "Alice".to_string(), date: imported[1].0, seconds, reason, created_at: day(3, 9, 0) };
+            results.push(format!("{:?}", store.add_adjustment(ChatId(2), &adjustment).await.unwrap()));
+        }
+        results.push(format!("{:?}", store.get_adjustments(ChatId(2), 2).await.unwrap()));
+        results.push(format!("{:?}", store.get_adjustments(ChatId(1), 10).await.unwrap()));
+
+        store.start_member_session(ChatId(2), UserId(10), "Alice", day(2, 20, 0)).await.unwrap();
+        store.finish_member_session(ChatId(2), UserId(10), day(2, 20, 10), StopTrigger::SitSticker).await.unwrap();
+        results.push(format!("{:?}", store.undo_last_session(ChatId(2), Some(UserId(10)), true, day(3, 0, 0)).await.unwrap()));
+        results.push(format!("{:?}", store.get_member_session_start(ChatId(2), UserId(10)).await.unwrap()));
+        results.push(format!("{:?}", store.finish_member_session(ChatId(2), UserId(10), day(2, 20, 30), StopTrigger::SitSticker).await.unwrap()));
+        results.push(format!("{:?}", store.undo_last_session(ChatId(2), None, false, day(3, 0, 0)).await.unwrap()));
+        results.push(format!("{:?}", store.undo_last_session(ChatId(2), None, false, day(3, 0, 0)).await.unwrap()));
+        // Chat 1's session was split at its local midnight, undoing it after the offset changed
+        // must take the seconds off the same two days
+        store.set_utc_offset(ChatId(1), 0).await.unwrap();
+        store.import_daily_totals(ChatId(1), &[(NaiveDate::from_ymd_opt(2024, 5, 1).unwrap(), 1000)], MergePolicy::Replace).await.unwrap();
+        results.push(format!("{:?}", store.undo_last_session(ChatId(1), None, false, day(3, 0, 0)).await.unwrap()));
+        results.push(format!("{:?}", store.get_daily_totals(ChatId(1), DateRange::default()).await.unwrap()));
+        results.push(format!("{:?}", store.get_daily_totals(ChatId(2), DateRange::default()).await.unwrap()));
+        results.push(format!("{:?}", store.get_member_totals(ChatId(2), DateRange::default()).await.unwrap()));
+        results.push(format!("{:?}", store.get_sessions(ChatId(2), DateRange::default()).await.unwrap()));
+        results
+    }
+
+    #[tokio::test]
+    async fn test_same_as_sqlite() {
+        let memory = MemoryStore::default();
+        let sqlite = Total::connect(":memory:").await.unwrap();
+        assert_eq!(scenario(&memory).await, scenario(sqlite.as_ref()).await);
+    }
+}
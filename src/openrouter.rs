@@ -1,8 +1,6 @@
-use reqwest;
 use serde_json::{json, Value};
 use std::error::Error;
 use std::env;
-use dotenv::dotenv;
 
 pub async fn is_intent_to_sit(message: &str) -> Result<bool, Box<dyn Error>> {
     let api_key = env::var("OPENROUTER_API_KEY")
@@ -47,7 +45,7 @@ Message:
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tokio;
+    use dotenv::dotenv;
 
     // Load .env file before each test
     fn setup() {
@@ -63,13 +61,13 @@ mod tests {
             .await
             .expect("Function should not error");
 
-        assert_eq!(result, true, "Should return true for Чил");
+        assert!(result, "Should return true for Чил");
 
         let result = is_intent_to_sit("ну ща не надолго, лежать пойду уже")
             .await
             .expect("Function should not error");
 
-        assert_eq!(result, false, "Should return true for sit intent");
+        assert!(!result, "Should return true for sit intent");
 
     }
 
@@ -81,6 +79,6 @@ mod tests {
             .await
             .expect("Function should not error");
 
-        assert_eq!(result, false, "Should return false for stand intent");
+        assert!(!result, "Should return false for stand intent");
     }
 }
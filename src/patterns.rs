@@ -0,0 +1,120 @@
+use chrono::{DateTime, Datelike};
+
+use crate::{store::SessionRecord, time::format_short_duration};
+
+const WEEKDAYS: [&str; 7] = ["Пн", "Вт", "Ср", "Чт", "Пт", "Сб", "Вс"];
+const BAR_WIDTH: i64 = 12;
+
+/// Seconds stood in each hour of the day and each weekday, by the chat's clock
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Patterns {
+    pub hours: [i64; 24],
+    /// Monday first
+    pub weekdays: [i64; 7],
+}
+
+impl Patterns {
+    /// Splits the finished sessions at the hours they span. `utc_offset_minutes` is the chat's
+    /// clock, the day start setting doesn't move hours or weekdays.
+    pub fn new(sessions: &[SessionRecord], utc_offset_minutes: i32) -> Self {
+        let mut patterns = Patterns::default();
+        let offset = i64::from(utc_offset_minutes) * 60;
+        for session in sessions {
+            let Some(end) = session.end_timestamp else {
+                continue;
+            };
+            let (mut current, end) = (session.start_timestamp + offset, end + offset);
+            while current < end {
+                let next_hour = (current.div_euclid(3600) + 1) * 3600;
+                let seconds = next_hour.min(end) - current;
+                patterns.hours[current.div_euclid(3600).rem_euclid(24) as usize] += seconds;
+                if let Some(time) = DateTime::from_timestamp(current, 0) {
+                    patterns.weekdays[time.weekday().num_days_from_monday() as usize] += seconds;
+                }
+                current += seconds;
+            }
+        }
+        patterns
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hours.iter().all(|seconds| *seconds == 0)
+    }
+
+    /// Hours from the first to the last one with standing and every weekday, as text bars
+    pub fn render(&self) -> String {
+        let first = self.hours.iter().position(|seconds| *seconds > 0).unwrap_or(0);
+        let last = self.hours.iter().rposition(|seconds| *seconds > 0).unwrap_or(23);
+        let max_hour = self.hours.iter().max().copied().unwrap_or_default();
+        let max_weekday = self.weekdays.iter().max().copied().unwrap_or_default();
+
+        let mut lines = vec!["По часам:".to_string()];
+        lines.extend((first..=last).map(|hour| line(&format!("{hour:02}"), self.hours[hour], max_hour)));
+        lines.push("\nПо дням недели:".to_string());
+        lines.extend(WEEKDAYS.iter().zip(self.weekdays).map(|(day, seconds)| line(day, seconds, max_weekday)));
+        lines.join("\n")
+    }
+}
+
+fn line(label: &str, seconds: i64, max: i64) -> String {
+    let width = if max == 0 { 0 } else { (seconds * BAR_WIDTH + max / 2) / max };
+    let bar = match width {
+        0 if seconds > 0 => "▏".to_string(),
+        width => "█".repeat(width as usize),
+    };
+    match seconds {
+        0 => label.to_string(),
+        seconds => format!("{label} {bar:<12} {}", format_short_duration(seconds)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(start_timestamp: i64, seconds: i64) -> SessionRecord {
+        SessionRecord {
+            id: 1,
+            user_id: None,
+            start_timestamp,
+            end_timestamp: Some(start_timestamp + seconds),
+            seconds: Some(seconds),
+            start_trigger: None,
+            stop_trigger: None,
+        }
+    }
+
+    #[test]
+    fn test_patterns() {
+        let monday = 1714953600; // 2024-05-06 00:00 UTC
+        let sessions = [
+            // 09:30 to 10:30 local at UTC+6
+            session(monday + 3 * 3600 + 1800, 3600),
+            // Sunday 23:30 to Monday 00:30
+            session(monday - 6 * 3600 - 1800, 3600),
+            SessionRecord { end_timestamp: None, ..session(monday, 3600) },
+        ];
+        let patterns = Patterns::new(&sessions, 360);
+        assert_eq!(patterns.hours[9], 1800);
+        assert_eq!(patterns.hours[10], 1800);
+        assert_eq!(patterns.hours[23], 1800);
+        assert_eq!(patterns.hours[0], 1800);
+        assert_eq!(patterns.hours.iter().sum::<i64>(), 7200);
+        assert_eq!(patterns.weekdays, [5400, 0, 0, 0, 0, 0, 1800]);
+    }
+
+    #[test]
+    fn test_render() {
+        let monday = 1714953600;
+        let patterns = Patterns::new(&[session(monday + 9 * 3600, 3600), session(monday + 11 * 3600, 600)], 0);
+        assert_eq!(patterns.render(), "По часам:\n\
+                                       09 ████████████ 1ч\n\
+                                       10\n\
+                                       11 ██           10м\n\
+                                       \n\
+                                       По дням недели:\n\
+                                       Пн ████████████ 1ч 10м\n\
+                                       Вт\nСр\nЧт\nПт\nСб\nВс");
+        assert!(Patterns::default().is_empty());
+    }
+}
@@ -0,0 +1,269 @@
+use chrono::{Datelike, Days, Months, NaiveDate, Weekday};
+
+/// Inclusive range of local dates, unbounded on a side that is None.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DateRange {
+    pub start: Option<NaiveDate>,
+    pub end: Option<NaiveDate>,
+}
+
+/// Longest `Nd` period or date range, about a hundred years
+const MAX_DAYS: u32 = 36500;
+
+/// Period given as a command argument
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Period {
+    All,
+    Week,
+    Month,
+    Year,
+    LastWeek,
+    LastMonth,
+    LastYear,
+    /// A whole calendar month, `2026-09`
+    CalendarMonth(i32, u32),
+    /// A whole calendar year, `2026`
+    CalendarYear(i32),
+    /// The last N days including today
+    Days(u32),
+    Range(NaiveDate, NaiveDate),
+}
+
+impl Period {
+    /// Parses `all`, `week`, `month`, `year`, `last-week`, `last-month`, `last-year`, `2026-09`,
+    /// `2026`, `30d`, `2026-10-01` or `2024-01-01..2024-03-31`.
+    pub fn parse(text: &str) -> Option<Self> {
+        let date = |text: &str| NaiveDate::parse_from_str(text, "%Y-%m-%d").ok();
+        match text.trim() {
+            "" | "all" => Some(Period::All),
+            "week" => Some(Period::Week),
+            "month" => Some(Period::Month),
+            "year" => Some(Period::Year),
+            "last-week" => Some(Period::LastWeek),
+            "last-month" => Some(Period::LastMonth),
+            "last-year" => Some(Period::LastYear),
+            text => {
+                if let Some(days) = text.strip_suffix('d') {
+                    return days.parse().ok().filter(|days| (1..=MAX_DAYS).contains(days)).map(Period::Days);
+                }
+                if let Some((start, end)) = text.split_once("..") {
+                    let (start, end) = (date(start)?, date(end)?);
+                    return (start <= end && (end - start).num_days() < i64::from(MAX_DAYS)).then_some(Period::Range(start, end));
+                }
+                if let Some(day) = date(text) {
+                    return Some(Period::Range(day, day));
+                }
+                match text.split_once('-') {
+                    Some((year, month)) => {
+                        let (year, month) = (year.parse().ok()?, month.parse().ok()?);
+                        NaiveDate::from_ymd_opt(year, month, 1)
+                            .and_then(|start| start.checked_add_months(Months::new(1)))
+                            .map(|_| Period::CalendarMonth(year, month))
+                    }
+                    None => {
+                        let year = text.parse().ok()?;
+                        NaiveDate::from_ymd_opt(year, 1, 1).map(|_| Period::CalendarYear(year))
+                    }
+                }
+            }
+        }
+    }
+
+    /// The dates the period covers when `today` is the current local date and weeks begin on
+    /// `week_start`.
+    pub fn resolve(&self, today: NaiveDate, week_start: Weekday) -> DateRange {
+        let since = |start: NaiveDate| DateRange { start: Some(start), end: Some(today) };
+        match *self {
+            Period::All => DateRange::default(),
+            Period::Week => since(start_of_week(today, week_start)),
+            Period::Month => since(today.with_day(1).unwrap()),
+            Period::Year => since(today.with_ordinal(1).unwrap()),
+            Period::LastWeek => {
+                let end = start_of_week(today, week_start) - Days::new(1);
+                DateRange { start: Some(start_of_week(end, week_start)), end: Some(end) }
+            }
+            Period::LastMonth => {
+                let end = today.with_day(1).unwrap() - Days::new(1);
+                DateRange { start: end.with_day(1), end: Some(end) }
+            }
+            Period::LastYear => {
+                let end = today.with_ordinal(1).unwrap() - Days::new(1);
+                DateRange { start: end.with_ordinal(1), end: Some(end) }
+            }
+            Period::CalendarMonth(year, month) => {
+                let start = NaiveDate::from_ymd_opt(year, month, 1);
+                let end = start.and_then(|start| start.checked_add_months(Months::new(1))).map(|next| next - Days::new(1));
+                DateRange { start, end }
+            }
+            Period::CalendarYear(year) => DateRange { start: NaiveDate::from_ymd_opt(year, 1, 1), end: NaiveDate::from_ymd_opt(year, 12, 31) },
+            Period::Days(days) => since(today.checked_sub_days(Days::new(u64::from(days) - 1)).unwrap_or(NaiveDate::MIN)),
+            Period::Range(start, end) => DateRange { start: Some(start), end: Some(end) },
+        }
+    }
+
+    /// The period just before this one, to compare with: the whole previous week for this week,
+    /// the same number of days before a range. None for all time.
+    pub fn previous(&self, today: NaiveDate, week_start: Weekday) -> Option<DateRange> {
+        let before = |range: DateRange| {
+            let (start, end) = (range.start?, range.end?);
+            let days = Days::new((end - start).num_days() as u64 + 1);
+            Some(DateRange { start: Some(start.checked_sub_days(days)?), end: Some(start.checked_sub_days(Days::new(1))?) })
+        };
+        match *self {
+            Period::All => None,
+            Period::Week => Some(Period::LastWeek.resolve(today, week_start)),
+            Period::Month => Some(Period::LastMonth.resolve(today, week_start)),
+            Period::Year => Some(Period::LastYear.resolve(today, week_start)),
+            Period::LastWeek => Some(Period::LastWeek.resolve(start_of_week(today, week_start) - Days::new(1), week_start)),
+            Period::LastMonth => Some(Period::LastMonth.resolve(today.with_day(1)? - Days::new(1), week_start)),
+            Period::LastYear => Some(Period::CalendarYear(today.year() - 2).resolve(today, week_start)),
+            Period::CalendarMonth(year, month) => {
+                let previous = NaiveDate::from_ymd_opt(year, month, 1)?.checked_sub_months(Months::new(1))?;
+                Some(Period::CalendarMonth(previous.year(), previous.month()).resolve(today, week_start))
+            }
+            Period::CalendarYear(year) => Some(Period::CalendarYear(year - 1).resolve(today, week_start)),
+            Period::Days(_) | Period::Range(..) => before(self.resolve(today, week_start)),
+        }
+    }
+
+    /// Heading for messages about the period: "за прошлую неделю".
+    pub fn describe(&self) -> String {
+        match *self {
+            Period::All => "за всё время".to_string(),
+            Period::Week => "за эту неделю".to_string(),
+            Period::Month => "за этот месяц".to_string(),
+            Period::Year => "за этот год".to_string(),
+            Period::LastWeek => "за прошлую неделю".to_string(),
+            Period::LastMonth => "за прошлый месяц".to_string(),
+            Period::LastYear => "за прошлый год".to_string(),
+            Period::CalendarMonth(year, month) => format!("за {year}-{month:02}"),
+            Period::CalendarYear(year) => format!("за {year} год"),
+            Period::Days(days) => format!("за последние {days} дн."),
+            Period::Range(start, end) if start == end => format!("за {start}"),
+            Period::Range(start, end) => format!("с {start} по {end}"),
+        }
+    }
+}
+
+/// The last `week_start` on or before the date
+pub fn start_of_week(date: NaiveDate, week_start: Weekday) -> NaiveDate {
+    date.week(week_start).first_day()
+}
+
+/// Parses `monday`, `mon`, `понедельник` or `пн` and the other days.
+pub fn parse_weekday(text: &str) -> Option<Weekday> {
+    const RUSSIAN: [(&str, &str); 7] = [
+        ("пн", "понедельник"),
+        ("вт", "вторник"),
+        ("ср", "среда"),
+        ("чт", "четверг"),
+        ("пт", "пятница"),
+        ("сб", "суббота"),
+        ("вс", "воскресенье"),
+    ];
+    let text = text.trim().to_lowercase();
+    text.parse().ok().or_else(|| {
+        RUSSIAN.iter()
+            .position(|(short, long)| text == *short || text == *long)
+            .and_then(|index| Weekday::try_from(index as u8).ok())
+    })
+}
+
+/// "понедельник"
+pub fn weekday_name(weekday: Weekday) -> &'static str {
+    ["понедельник", "вторник", "среда", "четверг", "пятница", "суббота", "воскресенье"][weekday.num_days_from_monday() as usize]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Period::parse(""), Some(Period::All));
+        assert_eq!(Period::parse("month"), Some(Period::Month));
+        assert_eq!(Period::parse("30d"), Some(Period::Days(30)));
+        assert_eq!(Period::parse("0d"), None);
+        assert_eq!(Period::parse("36500d"), Some(Period::Days(36500)));
+        assert_eq!(Period::parse("36501d"), None);
+        assert_eq!(Period::parse("4000000000d"), None);
+        assert_eq!(Period::parse("2024-01-01..2024-03-31"), Some(Period::Range(date(2024, 1, 1), date(2024, 3, 31))));
+        assert_eq!(Period::parse("2024-03-31..2024-01-01"), None);
+        assert_eq!(Period::parse("0001-01-01..9999-12-31"), None);
+        assert_eq!(Period::parse("yesterday"), None);
+        assert_eq!(Period::parse("last-week"), Some(Period::LastWeek));
+        assert_eq!(Period::parse("2026-09"), Some(Period::CalendarMonth(2026, 9)));
+        assert_eq!(Period::parse("2026-13"), None);
+        // The last month chrono can represent has no end
+        assert_eq!(Period::parse("262142-12"), None);
+        assert_eq!(Period::parse("262143"), None);
+        assert_eq!(Period::parse("2026"), Some(Period::CalendarYear(2026)));
+        assert_eq!(Period::parse("2026-10-01"), Some(Period::Range(date(2026, 10, 1), date(2026, 10, 1))));
+    }
+
+    #[test]
+    fn test_resolve() {
+        let today = date(2024, 5, 15);
+        assert_eq!(Period::All.resolve(today, Weekday::Sun), DateRange::default());
+        assert_eq!(Period::Month.resolve(today, Weekday::Sun), DateRange { start: Some(date(2024, 5, 1)), end: Some(today) });
+        assert_eq!(Period::Year.resolve(today, Weekday::Sun), DateRange { start: Some(date(2024, 1, 1)), end: Some(today) });
+        assert_eq!(Period::Days(7).resolve(today, Weekday::Sun), DateRange { start: Some(date(2024, 5, 9)), end: Some(today) });
+        assert_eq!(Period::LastWeek.resolve(today, Weekday::Sun), DateRange { start: Some(date(2024, 5, 5)), end: Some(date(2024, 5, 11)) });
+        assert_eq!(Period::LastMonth.resolve(today, Weekday::Sun), DateRange { start: Some(date(2024, 4, 1)), end: Some(date(2024, 4, 30)) });
+        assert_eq!(Period::LastYear.resolve(today, Weekday::Sun), DateRange { start: Some(date(2023, 1, 1)), end: Some(date(2023, 12, 31)) });
+        assert_eq!(Period::CalendarMonth(2024, 2).resolve(today, Weekday::Sun), DateRange { start: Some(date(2024, 2, 1)), end: Some(date(2024, 2, 29)) });
+        assert_eq!(Period::CalendarYear(2020).resolve(today, Weekday::Sun), DateRange { start: Some(date(2020, 1, 1)), end: Some(date(2020, 12, 31)) });
+    }
+
+    #[test]
+    fn test_previous() {
+        let today = date(2024, 5, 15);
+        let range = |start, end| Some(DateRange { start: Some(start), end: Some(end) });
+        assert_eq!(Period::All.previous(today, Weekday::Sun), None);
+        assert_eq!(Period::Week.previous(today, Weekday::Sun), range(date(2024, 5, 5), date(2024, 5, 11)));
+        assert_eq!(Period::LastWeek.previous(today, Weekday::Sun), range(date(2024, 4, 28), date(2024, 5, 4)));
+        assert_eq!(Period::Month.previous(today, Weekday::Sun), range(date(2024, 4, 1), date(2024, 4, 30)));
+        assert_eq!(Period::LastMonth.previous(today, Weekday::Sun), range(date(2024, 3, 1), date(2024, 3, 31)));
+        assert_eq!(Period::LastYear.previous(today, Weekday::Sun), range(date(2022, 1, 1), date(2022, 12, 31)));
+        assert_eq!(Period::CalendarMonth(2024, 1).previous(today, Weekday::Sun), range(date(2023, 12, 1), date(2023, 12, 31)));
+        assert_eq!(Period::Days(7).previous(today, Weekday::Sun), range(date(2024, 5, 2), date(2024, 5, 8)));
+        assert_eq!(Period::Range(date(2024, 3, 1), date(2024, 3, 10)).previous(today, Weekday::Sun), range(date(2024, 2, 20), date(2024, 2, 29)));
+        // Nothing before the first representable date
+        assert_eq!(Period::Range(NaiveDate::MIN, date(2024, 3, 10)).previous(today, Weekday::Sun), None);
+        assert_eq!(Period::CalendarMonth(NaiveDate::MIN.year(), 1).previous(today, Weekday::Sun), None);
+    }
+
+    #[test]
+    fn test_monday_weeks() {
+        // 2024-05-15 is a Wednesday
+        let today = date(2024, 5, 15);
+        assert_eq!(Period::Week.resolve(today, Weekday::Mon), DateRange { start: Some(date(2024, 5, 13)), end: Some(today) });
+        assert_eq!(Period::LastWeek.resolve(today, Weekday::Mon), DateRange { start: Some(date(2024, 5, 6)), end: Some(date(2024, 5, 12)) });
+        assert_eq!(Period::Week.previous(today, Weekday::Mon), Some(DateRange { start: Some(date(2024, 5, 6)), end: Some(date(2024, 5, 12)) }));
+    }
+
+    #[test]
+    fn test_start_of_week() {
+        // 2024-05-05 is a Sunday, a week starting on Sunday is just that day so far
+        assert_eq!(start_of_week(date(2024, 5, 5), Weekday::Sun), date(2024, 5, 5));
+        assert_eq!(start_of_week(date(2024, 5, 6), Weekday::Sun), date(2024, 5, 5));
+        assert_eq!(start_of_week(date(2024, 5, 11), Weekday::Sun), date(2024, 5, 5));
+        assert_eq!(start_of_week(date(2024, 5, 5), Weekday::Mon), date(2024, 4, 29));
+        assert_eq!(start_of_week(date(2024, 5, 6), Weekday::Mon), date(2024, 5, 6));
+        assert_eq!(start_of_week(date(2024, 5, 6), Weekday::Sat), date(2024, 5, 4));
+    }
+
+    #[test]
+    fn test_parse_weekday() {
+        assert_eq!(parse_weekday("monday"), Some(Weekday::Mon));
+        assert_eq!(parse_weekday("Sun"), Some(Weekday::Sun));
+        assert_eq!(parse_weekday("вс"), Some(Weekday::Sun));
+        assert_eq!(parse_weekday("Суббота"), Some(Weekday::Sat));
+        assert_eq!(parse_weekday("someday"), None);
+        assert_eq!(weekday_name(Weekday::Wed), "среда");
+    }
+}
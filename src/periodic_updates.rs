@@ -1,16 +1,16 @@
-use std::{any::Any, error::Error, ops::Deref, sync::Arc};
+use std::sync::Arc;
 
-use chrono::{DateTime, Utc};
 use teloxide::{
     dispatching::dialogue::GetChatId, prelude::*
 };
-use tokio::{sync::{watch, Mutex}, time::{sleep,Duration}};
+use tokio::{sync::watch, time::{sleep,Duration}};
 
-use crate::time::{get_time_difference, get_time_difference_from_now};
+use crate::time::get_time_difference_from_now;
 
 #[derive(Clone, Default, serde::Serialize, serde::Deserialize)]
 pub struct UpdateData(pub Option<Message>, pub i64);
 
+#[allow(dead_code)]
 pub async fn periodic_update_msg(bot: Bot, rx: Arc<watch::Receiver<UpdateData>>) {
     loop {
         let UpdateData(message, timestamp) = rx.borrow().clone();
@@ -26,11 +26,11 @@ pub async fn periodic_update_msg(bot: Bot, rx: Arc<watch::Receiver<UpdateData>>)
     }
 }
 
-pub async fn update_periodically(bot: Bot) -> watch::Sender<UpdateData> {
+pub async fn update_periodically(_bot: Bot) -> watch::Sender<UpdateData> {
     let (tx, rx) = watch::channel(UpdateData(None, 0));
     let rx = Arc::new(rx); // Shared state
-    let rx_clone = Arc::clone(&rx);
+    let _rx_clone = Arc::clone(&rx);
 
     // tokio::spawn(async move {periodic_update_msg(bot, rx_clone)}.await);
-    return tx;
+    tx
 }
@@ -0,0 +1,228 @@
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, NaiveDate, Weekday};
+use teloxide::{prelude::*, types::UserId};
+
+use crate::{period::{start_of_week, DateRange}, store::{StandingStore, StoreResult}, time::{format_short_duration, split_by_day}};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RecordKind {
+    Session,
+    Day,
+    Week,
+    Month,
+}
+
+impl RecordKind {
+    const ALL: [RecordKind; 4] = [RecordKind::Session, RecordKind::Day, RecordKind::Week, RecordKind::Month];
+
+    fn label(&self) -> &'static str {
+        match self {
+            RecordKind::Session => "самое долгое стояние",
+            RecordKind::Day => "лучший день",
+            RecordKind::Week => "лучшая неделя",
+            RecordKind::Month => "лучший месяц",
+        }
+    }
+
+    /// First day of the week or month the day is in
+    fn period_of(&self, date: NaiveDate, week_start: Weekday) -> NaiveDate {
+        match self {
+            RecordKind::Session | RecordKind::Day => date,
+            RecordKind::Week => start_of_week(date, week_start),
+            RecordKind::Month => date.with_day(1).unwrap(),
+        }
+    }
+
+    fn describe_date(&self, date: NaiveDate) -> String {
+        match self {
+            RecordKind::Session | RecordKind::Day => date.to_string(),
+            RecordKind::Week => format!("неделя с {date}"),
+            RecordKind::Month => date.format("%Y-%m").to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Record {
+    pub kind: RecordKind,
+    pub seconds: i64,
+    /// Day of the session, first day of the week or month
+    pub date: NaiveDate,
+}
+
+impl Record {
+    pub fn describe(&self) -> String {
+        let label = self.kind.label();
+        let mut chars = label.chars();
+        let label: String = chars.next().into_iter().flat_map(char::to_uppercase).chain(chars).collect();
+        format!("{label}: {} ({})", format_short_duration(self.seconds), self.kind.describe_date(self.date))
+    }
+}
+
+/// A record that a session just beat
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BrokenRecord {
+    pub kind: RecordKind,
+    pub seconds: i64,
+    pub previous: i64,
+}
+
+impl BrokenRecord {
+    /// `subject` is "Рекорд чата" or "Рекорд Alice"
+    pub fn message(&self, subject: &str) -> String {
+        format!("🏅 {subject} — {}: {} (было {})", self.kind.label(), format_short_duration(self.seconds), format_short_duration(self.previous))
+    }
+}
+
+/// Totals of each day, week or month, keyed by their first day. Sessions are kept one by one.
+fn totals(kind: RecordKind, days: &[(NaiveDate, i64)], sessions: &[(NaiveDate, i64)], week_start: Weekday) -> Vec<(NaiveDate, i64)> {
+    if kind == RecordKind::Session {
+        return sessions.to_vec();
+    }
+    let mut totals: BTreeMap<NaiveDate, i64> = BTreeMap::new();
+    for (date, seconds) in days {
+        *totals.entry(kind.period_of(*date, week_start)).or_default() += seconds;
+    }
+    totals.into_iter().collect()
+}
+
+/// The best session, day, week and month. Ties go to whoever got there first.
+pub fn records(days: &[(NaiveDate, i64)], sessions: &[(NaiveDate, i64)], week_start: Weekday) -> Vec<Record> {
+    RecordKind::ALL.iter()
+        .filter_map(|kind| {
+            totals(*kind, days, sessions, week_start).into_iter()
+                .filter(|(_, seconds)| *seconds > 0)
+                .fold(None, |best: Option<(NaiveDate, i64)>, (date, seconds)| match best {
+                    Some((_, best_seconds)) if best_seconds >= seconds => best,
+                    _ => Some((date, seconds)),
+                })
+                .map(|(date, seconds)| Record { kind: *kind, seconds, date })
+        })
+        .collect()
+}
+
+/// Records beaten by the session of `session_seconds` that credited `added` seconds to `today`.
+/// `days` and `sessions` already include it. A first ever day or session has nothing to beat.
+pub fn broken(days: &[(NaiveDate, i64)],
+              sessions: &[(NaiveDate, i64)],
+              week_start: Weekday,
+              today: NaiveDate,
+              added: i64,
+              session_seconds: i64) -> Vec<BrokenRecord> {
+    RecordKind::ALL.iter()
+        .filter_map(|kind| {
+            let (current, added) = match kind {
+                RecordKind::Session => (session_seconds, session_seconds),
+                _ => (totals(*kind, days, sessions, week_start).into_iter()
+                          .find(|(date, _)| *date == kind.period_of(today, week_start))
+                          .map_or(0, |(_, seconds)| seconds),
+                      added),
+            };
+            let mut skipped = false;
+            let previous = totals(*kind, days, sessions, week_start).into_iter()
+                .filter(|(date, seconds)| match kind {
+                    // Leave out the session itself, once
+                    RecordKind::Session if !skipped && *seconds == current => {
+                        skipped = true;
+                        false
+                    }
+                    RecordKind::Session => true,
+                    _ => *date != kind.period_of(today, week_start),
+                })
+                .map(|(_, seconds)| seconds)
+                .max()
+                .filter(|previous| *previous > 0)?;
+            (current > previous && current - added <= previous).then_some(BrokenRecord { kind: *kind, seconds: current, previous })
+        })
+        .collect()
+}
+
+/// Day totals and finished sessions, dated by the chat's local day, of the chat or of a member of it
+pub async fn history(store: &dyn StandingStore, chat_id: ChatId, user_id: Option<UserId>) -> StoreResult<(Vec<(NaiveDate, i64)>, Vec<(NaiveDate, i64)>)> {
+    let boundary = store.get_day_boundary(chat_id).await?;
+    let days = match user_id {
+        Some(user_id) => store.get_member_daily_totals(chat_id, user_id, DateRange::default()).await?,
+        None => store.get_daily_totals(chat_id, DateRange::default()).await?,
+    };
+    let sessions = store.get_sessions(chat_id, DateRange::default()).await?.into_iter()
+        .filter(|session| user_id.is_none_or(|user_id| session.user_id == Some(user_id.0 as i64)))
+        .filter_map(|session| Some((boundary.date_of(session.start_timestamp), session.seconds?)))
+        .collect();
+    Ok((days, sessions))
+}
+
+/// Posts the records the session from `start_timestamp` to `end_timestamp` beat for the chat or a
+/// member of it
+pub async fn announce(bot: &Bot,
+                      store: &dyn StandingStore,
+                      chat_id: ChatId,
+                      user_id: Option<UserId>,
+                      subject: &str,
+                      start_timestamp: i64,
+                      end_timestamp: i64) -> StoreResult<()> {
+    let boundary = store.get_day_boundary(chat_id).await?;
+    let today = boundary.date_of(end_timestamp);
+    let added = split_by_day(start_timestamp, end_timestamp, boundary).into_iter()
+        .filter(|(date, _)| *date == today)
+        .map(|(_, seconds)| seconds)
+        .sum();
+    let (days, sessions) = history(store, chat_id, user_id).await?;
+    let week_start = store.get_week_start(chat_id).await?;
+    for record in broken(&days, &sessions, week_start, today, added, end_timestamp - start_timestamp) {
+        bot.send_message(chat_id, record.message(subject)).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_records() {
+        let days = [(date(4, 30), 3600), (date(5, 1), 1800), (date(5, 2), 3600), (date(5, 10), 600)];
+        let sessions = [(date(4, 30), 3600), (date(5, 1), 1200), (date(5, 1), 600)];
+        assert_eq!(records(&days, &sessions, Weekday::Sun), vec![
+            Record { kind: RecordKind::Session, seconds: 3600, date: date(4, 30) },
+            // The earlier of two equal days
+            Record { kind: RecordKind::Day, seconds: 3600, date: date(4, 30) },
+            Record { kind: RecordKind::Week, seconds: 9000, date: date(4, 28) },
+            Record { kind: RecordKind::Month, seconds: 6000, date: date(5, 1) },
+        ]);
+        assert_eq!(records(&days, &sessions, Weekday::Sun)[2].describe(), "Лучшая неделя: 2ч 30м (неделя с 2024-04-28)");
+        assert!(records(&[], &[], Weekday::Sun).is_empty());
+    }
+
+    #[test]
+    fn test_broken() {
+        let days = [(date(5, 1), 3600), (date(5, 9), 3000)];
+        let sessions = [(date(5, 1), 3600), (date(5, 9), 1800), (date(5, 9), 1200)];
+        // Still short of the best day
+        assert!(broken(&days, &sessions, Weekday::Sun, date(5, 9), 1200, 1200).is_empty());
+
+        let days = [(date(5, 1), 3600), (date(5, 9), 4200)];
+        let sessions = [(date(5, 1), 3600), (date(5, 9), 1800), (date(5, 9), 1200), (date(5, 9), 1200)];
+        assert_eq!(broken(&days, &sessions, Weekday::Sun, date(5, 9), 1200, 1200), vec![
+            BrokenRecord { kind: RecordKind::Day, seconds: 4200, previous: 3600 },
+            BrokenRecord { kind: RecordKind::Week, seconds: 4200, previous: 3600 },
+        ]);
+        // Already beaten earlier that day
+        let days = [(date(5, 1), 3600), (date(5, 9), 5400)];
+        assert!(broken(&days, &sessions, Weekday::Sun, date(5, 9), 1200, 1200).is_empty());
+
+        let sessions = [(date(5, 1), 3600), (date(5, 9), 4000)];
+        let days = [(date(5, 1), 3600), (date(5, 9), 4000)];
+        assert_eq!(broken(&days, &sessions, Weekday::Sun, date(5, 9), 4000, 4000)[0],
+                   BrokenRecord { kind: RecordKind::Session, seconds: 4000, previous: 3600 });
+        assert_eq!(broken(&days, &sessions, Weekday::Sun, date(5, 9), 4000, 4000)[0].message("Рекорд чата"),
+                   "🏅 Рекорд чата — самое долгое стояние: 1ч 6м (было 1ч)");
+
+        // The first day ever isn't a record
+        assert!(broken(&[(date(5, 1), 600)], &[(date(5, 1), 600)], Weekday::Sun, date(5, 1), 600, 600).is_empty());
+    }
+}
@@ -0,0 +1,95 @@
+use chrono::NaiveDate;
+
+use crate::time::format_short_duration;
+
+/// Statistics of a run of calendar days, days nobody stood on count as zero
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Stats {
+    pub days: usize,
+    pub active_days: usize,
+    pub total: i64,
+    pub average: i64,
+    pub median: i64,
+    /// The latest of the best days
+    pub best: Option<(NaiveDate, i64)>,
+    pub std_dev: i64,
+}
+
+impl Stats {
+    /// Expects every day of the period, zero-filled like [`crate::chart::fill_days`] does.
+    pub fn new(days: &[(NaiveDate, i64)]) -> Self {
+        if days.is_empty() {
+            return Stats::default();
+        }
+        let count = days.len() as i64;
+        let total: i64 = days.iter().map(|(_, seconds)| seconds).sum();
+        let mean = total as f64 / count as f64;
+        let variance = days.iter().map(|(_, seconds)| (*seconds as f64 - mean).powi(2)).sum::<f64>() / count as f64;
+
+        let mut sorted: Vec<i64> = days.iter().map(|(_, seconds)| *seconds).collect();
+        sorted.sort_unstable();
+        let middle = sorted.len() / 2;
+        let median = if sorted.len().is_multiple_of(2) { (sorted[middle - 1] + sorted[middle]) / 2 } else { sorted[middle] };
+
+        Stats {
+            days: days.len(),
+            active_days: days.iter().filter(|(_, seconds)| *seconds > 0).count(),
+            total,
+            average: total / count,
+            median,
+            best: days.iter().max_by_key(|(_, seconds)| *seconds).copied().filter(|(_, seconds)| *seconds > 0),
+            std_dev: variance.sqrt().round() as i64,
+        }
+    }
+
+    pub fn describe(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("Всего: {}", format_short_duration(self.total)),
+            format!("В среднем за день: {}", format_short_duration(self.average)),
+            format!("Медиана: {}", format_short_duration(self.median)),
+        ];
+        if let Some((date, seconds)) = self.best {
+            lines.push(format!("Лучший день: {date} — {}", format_short_duration(seconds)));
+        }
+        lines.push(format!("Дней со стоянием: {} из {}", self.active_days, self.days));
+        lines.push(format!("Стандартное отклонение: {}", format_short_duration(self.std_dev)));
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chart::fill_days;
+
+    fn date(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 5, d).unwrap()
+    }
+
+    #[test]
+    fn test_stats() {
+        // One long day in four: the average counts the empty ones
+        let stats = Stats::new(&fill_days(&[(date(2), 3 * 3600)], date(1), date(4)));
+        assert_eq!(stats, Stats {
+            days: 4,
+            active_days: 1,
+            total: 10800,
+            average: 2700,
+            median: 0,
+            best: Some((date(2), 10800)),
+            std_dev: 4677,
+        });
+
+        let stats = Stats::new(&[(date(1), 600), (date(2), 1200), (date(3), 1200)]);
+        assert_eq!((stats.median, stats.best, stats.std_dev), (1200, Some((date(3), 1200)), 283));
+        assert_eq!(Stats::new(&fill_days(&[], date(1), date(2))).best, None);
+        assert_eq!(Stats::new(&[]), Stats::default());
+    }
+
+    #[test]
+    fn test_describe() {
+        let stats = Stats::new(&fill_days(&[(date(2), 3 * 3600), (date(3), 1800)], date(1), date(4)));
+        assert_eq!(stats.describe().join("\n"),
+                   "Всего: 3ч 30м\nВ среднем за день: 52м\nМедиана: 15м\nЛучший день: 2024-05-02 — 3ч\nДней со стоянием: 2 из 4\nСтандартное отклонение: 1ч 14м");
+    }
+}
@@ -1,19 +1,19 @@
-use std::{error::Error, sync::Arc};
+use std::error::Error;
 
-use chrono::{DateTime, Utc};
 use teloxide::{
     dispatching::dialogue::GetChatId, prelude::*
 };
 use tokio::sync::watch;
 
-use crate::{periodic_updates::UpdateData, time::{get_seconds_difference, get_seconds_difference_from_now, get_time_difference, get_time_difference_from_now, total_seconds_to_hms}, total_management::Total, HandlerResult, MyDialogue, State};
+use crate::{goal, periodic_updates::UpdateData, records, streak, time::{get_time_difference, get_time_difference_from_now, total_seconds_to_hms}, store::{StartTrigger, StopTrigger, Store}, HandlerResult, MyDialogue, State};
 
 pub const STICKER_STAND: &str = "AgADUW0AAk1IgUo";
+pub const STICKER_SIT: &str = "AgADP24AAn23-Eo";
 const SIT_STICKERS_SET: [&str; 5] =
     ["AgADlmEAAlOI8Eo", // Sit wrong pack
      "AgADF3IAAgh4iEo", // Sit punisher
      "AgAD5WYAAtxw-Uo", // chill
-     "AgADP24AAn23-Eo", // sit
+     STICKER_SIT, // sit
      "AgADYmMAAhK2qUo" // Laying down
     ];
 
@@ -22,7 +22,7 @@ pub async fn standing_status_handler(bot: Bot,
                                      msg: Message,
                                      (chat_id, timestamp): (ChatId,i64),
                                      tx: watch::Sender<UpdateData>,
-                                     total_manager: Arc<Total>
+                                     total_manager: Store
 ) -> HandlerResult {
     if let Some(sticker) = msg.sticker() {
         if SIT_STICKERS_SET.contains(&sticker.file.unique_id.as_str()) {
@@ -32,8 +32,7 @@ pub async fn standing_status_handler(bot: Bot,
             let end_timestamp = msg.date.timestamp();
             bot.send_message(chat_id, format!("ПОСТОЯЛИ {}",get_time_difference(timestamp, end_timestamp))).await?;
 
-            let total = get_total(total_manager.clone(), chat_id, timestamp, end_timestamp).await;
-            send_and_update_total(&bot, chat_id, total, total_manager).await?;
+            finish_session_and_send_total(&bot, chat_id, timestamp, end_timestamp, end_timestamp - timestamp, StopTrigger::SitSticker, total_manager).await?;
 
         } else {
             bot.send_message(chat_id, format!("СТОИМ {}",get_time_difference_from_now(timestamp))).await?;
@@ -42,37 +41,76 @@ pub async fn standing_status_handler(bot: Bot,
     Ok(())
 }
 
-pub async fn get_total(total_manager: Arc<Total>, chat_id: ChatId, start_timestamp: i64, end_timestamp: i64) -> i64 {
-    let end_datetime = DateTime::from_timestamp(end_timestamp, 0).unwrap_or(Utc::now());
-    let seconds_diff = get_seconds_difference(start_timestamp, end_datetime);
+pub async fn finish_session_and_send_total(bot: &Bot,
+                                           chat_id: ChatId,
+                                           start_timestamp: i64,
+                                           end_timestamp: i64,
+                                           seconds: i64,
+                                           trigger: StopTrigger,
+                                           total_manager: Store
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let total = total_manager.finish_session(chat_id, start_timestamp, end_timestamp, seconds, trigger).await?;
+    send_with_goal_progress(bot, &total_manager, chat_id, end_timestamp, format!("Всего постояли сегодня: {}", total_seconds_to_hms(total))).await?;
+    streak::announce(bot, total_manager.as_ref(), chat_id, None, "Серия чата", end_timestamp - seconds, end_timestamp).await?;
+    records::announce(bot, total_manager.as_ref(), chat_id, None, "Рекорд чата", end_timestamp - seconds, end_timestamp).await?;
+    goal::announce(bot, total_manager.as_ref(), chat_id, end_timestamp).await?;
+    Ok(())
+}
 
-    let total = if let Ok(Some(existing_total)) = total_manager.clone().get_total_timestamp_day(start_timestamp, chat_id).await {
-        existing_total + seconds_diff
-    } else {
-        seconds_diff
-    };
-    return total;
+/// Sends the day's total followed by the chat's progress towards its goals
+async fn send_with_goal_progress(bot: &Bot, total_manager: &Store, chat_id: ChatId, timestamp: i64, mut summary: String) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let today = total_manager.get_day_boundary(chat_id).await?.date_of(timestamp);
+    for line in goal::progress_lines(total_manager.as_ref(), chat_id, today).await? {
+        summary.push('\n');
+        summary.push_str(&line);
+    }
+    bot.send_message(chat_id, summary).await?;
+    Ok(())
 }
 
-pub async fn send_and_update_total(bot: &Bot, chat_id: ChatId, total: i64, total_manager: Arc<Total>) -> Result<(), Box<dyn Error + Send + Sync>> {
-    total_manager.set_total_today(chat_id, total).await?;
-    bot.send_message(chat_id, format!("Всего постояли сегодня: {}", total_seconds_to_hms(total))).await?;
+pub async fn start_standing_handler(bot: Bot, dialogue: MyDialogue, msg: Message, tx: watch::Sender<UpdateData>, total_manager: Store) -> HandlerResult {
+    if let Some(sticker) = msg.sticker() {
+        if sticker.file.unique_id == STICKER_STAND {
+            let chat_id = msg.chat_id().unwrap();
+            let timestamp = msg.date.timestamp();
+            dialogue.update(State::ReceiveStandingCommand { chat_id, timestamp }).await?;
+            total_manager.start_session(chat_id, timestamp, StartTrigger::Sticker).await?;
+            let standing_msg = bot.send_message(chat_id, "СТОИМ БРАТЬЯ").await?;
+            bot.pin_chat_message(standing_msg.chat.id, standing_msg.id).await?;
+            let _ = tx.send(UpdateData(Some(standing_msg), timestamp));
+        }
+    }
     Ok(())
 }
 
-pub async fn start_standing_handler(bot: Bot, dialogue: MyDialogue, msg: Message, tx: watch::Sender<UpdateData>) -> HandlerResult {
-    match msg.sticker().map(ToOwned::to_owned) {
-        Some(sticker) => {
-            if sticker.file.unique_id == STICKER_STAND {
-                let chat_id = msg.chat_id().unwrap();
-                let timestamp = msg.date.timestamp();
-                dialogue.update(State::ReceiveStandingCommand { chat_id, timestamp: timestamp }).await?;
-                let standing_msg = bot.send_message(chat_id, "СТОИМ БРАТЬЯ").await?;
-                bot.pin_chat_message(standing_msg.chat.id, standing_msg.id).await?;
-                let _ = tx.send(UpdateData(Some(standing_msg), timestamp));
-            }
+/// Stickers in group chats: each member stands on their own
+pub async fn member_sticker_handler(bot: Bot, msg: Message, total_manager: Store) -> HandlerResult {
+    let (Some(sticker), Some(user)) = (msg.sticker(), msg.from.as_ref()) else {
+        return Ok(());
+    };
+    let chat_id = msg.chat.id;
+    let name = user.full_name();
+    let timestamp = msg.date.timestamp();
+    let unique_id = sticker.file.unique_id.as_str();
+
+    if unique_id == STICKER_STAND {
+        if total_manager.start_member_session(chat_id, user.id, &name, timestamp).await? {
+            bot.send_message(chat_id, format!("{name} СТОИТ")).await?;
+        } else if let Some(start_timestamp) = total_manager.get_member_session_start(chat_id, user.id).await? {
+            bot.send_message(chat_id, format!("{name} УЖЕ СТОИТ {}", get_time_difference_from_now(start_timestamp))).await?;
+        }
+    } else if SIT_STICKERS_SET.contains(&unique_id) {
+        if let Some((start_timestamp, member_total)) = total_manager.finish_member_session(chat_id, user.id, timestamp, StopTrigger::SitSticker).await? {
+            bot.send_message(chat_id, format!("{name} ПОСТОЯЛ(А) {}", get_time_difference(start_timestamp, timestamp))).await?;
+            send_with_goal_progress(&bot, &total_manager, chat_id, timestamp, format!("Всего сегодня у {name}: {}", total_seconds_to_hms(member_total))).await?;
+            streak::announce(&bot, total_manager.as_ref(), chat_id, Some(user.id), &format!("Серия {name}"), start_timestamp, timestamp).await?;
+            streak::announce(&bot, total_manager.as_ref(), chat_id, None, "Серия чата", start_timestamp, timestamp).await?;
+            records::announce(&bot, total_manager.as_ref(), chat_id, Some(user.id), &format!("Рекорд {name}"), start_timestamp, timestamp).await?;
+            records::announce(&bot, total_manager.as_ref(), chat_id, None, "Рекорд чата", start_timestamp, timestamp).await?;
+            goal::announce(&bot, total_manager.as_ref(), chat_id, timestamp).await?;
         }
-        None => {}
+    } else if let Some(start_timestamp) = total_manager.get_member_session_start(chat_id, user.id).await? {
+        bot.send_message(chat_id, format!("{name} СТОИТ {}", get_time_difference_from_now(start_timestamp))).await?;
     }
     Ok(())
 }
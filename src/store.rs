@@ -0,0 +1,265 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{NaiveDate, Weekday};
+use serde::Serialize;
+use teloxide::types::{ChatId, UserId};
+
+use crate::{digest::{Digest, DigestKind}, goal::{GoalKind, Goals}, hall_of_fame::{Champion, TitleKind}, period::DateRange, time::DayBoundary};
+
+pub type StoreError = Box<dyn std::error::Error + Send + Sync>;
+pub type StoreResult<T> = Result<T, StoreError>;
+
+/// Storage shared by the handlers
+pub type Store = Arc<dyn StandingStore>;
+
+/// What started a standing session.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StartTrigger {
+    /// Stand sticker posted in the chat
+    Sticker,
+    /// "СТОИМ БРАТЬЯ" button in the private keyboard
+    Keyboard,
+}
+
+impl StartTrigger {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StartTrigger::Sticker => "sticker",
+            StartTrigger::Keyboard => "keyboard",
+        }
+    }
+}
+
+/// What stopped a standing session.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StopTrigger {
+    /// One of the sit stickers
+    SitSticker,
+    /// "СИДИМ" button in the private keyboard
+    Keyboard,
+    /// Text message the LLM recognized as an intent to sit
+    LlmText,
+    /// /cancel command
+    Cancel,
+}
+
+impl StopTrigger {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StopTrigger::SitSticker => "sit_sticker",
+            StopTrigger::Keyboard => "keyboard",
+            StopTrigger::LlmText => "llm_text",
+            StopTrigger::Cancel => "cancel",
+        }
+    }
+}
+
+/// A recorded session as stored, triggers are the `as_str` names.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SessionRecord {
+    pub id: i64,
+    pub user_id: Option<i64>,
+    pub start_timestamp: i64,
+    pub end_timestamp: Option<i64>,
+    pub seconds: Option<i64>,
+    pub start_trigger: Option<String>,
+    pub stop_trigger: Option<String>,
+}
+
+/// Cached metadata of a chat the bot has seen
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChatInfo {
+    pub chat_id: i64,
+    /// Title of groups and channels, full name of private chats
+    pub title: Option<String>,
+    pub username: Option<String>,
+    /// `private`, `group`, `supergroup` or `channel`
+    pub kind: String,
+    /// False once the bot has left or was removed
+    pub active: bool,
+}
+
+impl ChatInfo {
+    pub fn name(&self) -> String {
+        match (&self.title, &self.username) {
+            (Some(title), _) => title.clone(),
+            (None, Some(username)) => format!("@{username}"),
+            (None, None) => "Нет имени".to_string(),
+        }
+    }
+}
+
+/// A chat's standing over a range of days
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChatTotal {
+    pub chat_id: i64,
+    pub seconds: i64,
+    /// Days that have a total
+    pub days: i64,
+    /// The first of them
+    pub first: NaiveDate,
+}
+
+impl ChatTotal {
+    /// Average over every calendar day of the range up to `today`, days nobody stood on count as
+    /// zero. A range without a start begins on the chat's first day.
+    pub fn average(&self, range: DateRange, today: NaiveDate) -> i64 {
+        let start = range.start.unwrap_or(self.first);
+        let end = range.end.map_or(today, |end| end.min(today));
+        let days = (end - start).num_days() + 1;
+        if days <= 0 { 0 } else { self.seconds / days }
+    }
+}
+
+/// A manual correction of a day's total made with /adjust.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Adjustment {
+    /// None when made on behalf of a channel
+    pub user_id: Option<i64>,
+    pub user_name: String,
+    pub date: NaiveDate,
+    pub seconds: i64,
+    pub reason: Option<String>,
+    pub created_at: i64,
+}
+
+/// What to do with days that already have a total
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MergePolicy {
+    /// The imported value becomes the day's total
+    Replace,
+    /// The imported value is added to the day's total
+    Add,
+}
+
+impl MergePolicy {
+    pub fn parse(text: &str) -> Option<Self> {
+        match text {
+            "replace" => Some(MergePolicy::Replace),
+            "add" => Some(MergePolicy::Add),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MergePolicy::Replace => "заменить",
+            MergePolicy::Add => "добавить",
+        }
+    }
+}
+
+/// Everything the handlers read and write. Implemented by the SQLite [`crate::total_management::Total`]
+/// and by [`crate::memory_store::MemoryStore`] for tests.
+#[async_trait]
+pub trait StandingStore: Send + Sync {
+    async fn get_day_boundary(&self, chat_id: ChatId) -> StoreResult<DayBoundary>;
+
+    async fn set_utc_offset(&self, chat_id: ChatId, utc_offset_minutes: i32) -> StoreResult<()>;
+
+    async fn set_day_start(&self, chat_id: ChatId, day_start_minutes: i32) -> StoreResult<()>;
+
+    async fn start_session(&self, chat_id: ChatId, timestamp: i64, trigger: StartTrigger) -> StoreResult<()>;
+
+    /// Closes the open session started at `start_timestamp`, credits `seconds` to the chat's local
+    /// days they fall on and returns the total of the day of `end_timestamp`. The credit is capped
+    /// by [`crate::time::credited_seconds`].
+    async fn finish_session(&self,
+                            chat_id: ChatId,
+                            start_timestamp: i64,
+                            end_timestamp: i64,
+                            seconds: i64,
+                            trigger: StopTrigger) -> StoreResult<i64>;
+
+    /// Opens a session for a member of a group chat. Returns false if the member is already standing.
+    async fn start_member_session(&self, chat_id: ChatId, user_id: UserId, name: &str, timestamp: i64) -> StoreResult<bool>;
+
+    async fn get_member_session_start(&self, chat_id: ChatId, user_id: UserId) -> StoreResult<Option<i64>>;
+
+    /// Closes the member's open session and credits it to both the member and the chat.
+    /// Returns the start of the session and the member's total for the day of `end_timestamp`.
+    async fn finish_member_session(&self, chat_id: ChatId, user_id: UserId, end_timestamp: i64, trigger: StopTrigger) -> StoreResult<Option<(i64, i64)>>;
+
+    /// Total standing time of every member of the chat over the range, best first.
+    async fn get_member_totals(&self, chat_id: ChatId, range: DateRange) -> StoreResult<Vec<(i64, String, i64)>>;
+
+    /// Inserts or refreshes the cached metadata of a chat.
+    async fn save_chat(&self, chat: &ChatInfo) -> StoreResult<()>;
+
+    /// Every cached chat.
+    async fn get_chats(&self) -> StoreResult<Vec<ChatInfo>>;
+
+    /// Every chat's day totals summed over the range. Dates are local to each chat, so the range is
+    /// compared to each chat's own calendar dates.
+    async fn get_chat_totals(&self, range: DateRange) -> StoreResult<Vec<ChatTotal>>;
+
+    async fn get_total_timestamp_day(&self, timestamp: i64, chat_id: ChatId) -> StoreResult<Option<i64>>;
+
+    /// The chat's day rows within the range, oldest first.
+    async fn get_daily_totals(&self, chat_id: ChatId, range: DateRange) -> StoreResult<Vec<(NaiveDate, i64)>>;
+
+    /// A member's day rows within the range, oldest first.
+    async fn get_member_daily_totals(&self, chat_id: ChatId, user_id: UserId, range: DateRange) -> StoreResult<Vec<(NaiveDate, i64)>>;
+
+    /// Least standing a day needs to count towards a streak.
+    async fn get_streak_minimum(&self, chat_id: ChatId) -> StoreResult<i64>;
+
+    async fn set_streak_minimum(&self, chat_id: ChatId, seconds: i64) -> StoreResult<()>;
+
+    /// First day of the chat's weeks, Monday unless set.
+    async fn get_week_start(&self, chat_id: ChatId) -> StoreResult<Weekday>;
+
+    async fn set_week_start(&self, chat_id: ChatId, week_start: Weekday) -> StoreResult<()>;
+
+    /// Whether the chat gets the final leaderboards of closed periods, on unless turned off.
+    async fn get_announcements(&self, chat_id: ChatId) -> StoreResult<bool>;
+
+    async fn set_announcements(&self, chat_id: ChatId, enabled: bool) -> StoreResult<()>;
+
+    async fn get_goals(&self, chat_id: ChatId) -> StoreResult<Goals>;
+
+    /// Sets the goal, None removes it.
+    async fn set_goal(&self, chat_id: ChatId, kind: GoalKind, seconds: Option<i64>) -> StoreResult<()>;
+
+    /// Records that the goal was reached in the day or week starting at `period_start`. Returns
+    /// false if it was already recorded.
+    async fn mark_goal_reached(&self, chat_id: ChatId, kind: GoalKind, period_start: NaiveDate) -> StoreResult<bool>;
+
+    /// Every chat's scheduled digests.
+    async fn get_digests(&self) -> StoreResult<Vec<Digest>>;
+
+    /// Schedules the digest at the local time of day, None turns it off.
+    async fn set_digest(&self, chat_id: ChatId, kind: DigestKind, minutes: Option<i32>) -> StoreResult<()>;
+
+    async fn mark_digest_sent(&self, chat_id: ChatId, kind: DigestKind, date: NaiveDate) -> StoreResult<()>;
+
+    /// Champions of every closed period recorded so far, oldest first.
+    async fn get_champions(&self) -> StoreResult<Vec<Champion>>;
+
+    /// Champions whose final leaderboard hasn't been posted yet, oldest first.
+    async fn get_unannounced_champions(&self) -> StoreResult<Vec<Champion>>;
+
+    /// Records the champion of a period, a period that already has one keeps it.
+    async fn add_champion(&self, champion: &Champion, announced: bool) -> StoreResult<()>;
+
+    async fn mark_champion_announced(&self, kind: TitleKind, start: NaiveDate) -> StoreResult<()>;
+
+    /// The chat's sessions that started on a local day within the range, oldest first. Undone
+    /// sessions are left out.
+    async fn get_sessions(&self, chat_id: ChatId, range: DateRange) -> StoreResult<Vec<SessionRecord>>;
+
+    /// Writes imported day totals in one transaction, replacing or adding to existing rows.
+    async fn import_daily_totals(&self, chat_id: ChatId, days: &[(NaiveDate, i64)], policy: MergePolicy) -> StoreResult<()>;
+
+    /// Adds the adjustment to its day's total and records it. Returns the new day total.
+    async fn add_adjustment(&self, chat_id: ChatId, adjustment: &Adjustment) -> StoreResult<i64>;
+
+    /// The chat's latest adjustments, newest first.
+    async fn get_adjustments(&self, chat_id: ChatId, limit: u32) -> StoreResult<Vec<Adjustment>>;
+
+    /// Takes the most recent finished session of the chat (`user_id` None) or of a member back out
+    /// of the totals. With `reopen` the session is open again from its original start, otherwise it
+    /// is marked undone at `now`. Returns the session as it was before the undo.
+    async fn undo_last_session(&self, chat_id: ChatId, user_id: Option<UserId>, reopen: bool, now: i64) -> StoreResult<Option<SessionRecord>>;
+}
@@ -0,0 +1,167 @@
+use chrono::{Days, NaiveDate};
+use teloxide::{prelude::*, types::UserId};
+
+use crate::{period::DateRange, store::{StandingStore, StoreResult}, time::split_by_day};
+
+/// Streak minimum for chats that haven't set one
+pub const DEFAULT_MINIMUM_SECONDS: i64 = 600;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Streaks {
+    /// Days in a row up to today, or up to yesterday while today can still count
+    pub current: u32,
+    pub longest: u32,
+}
+
+/// What finishing a session did to a streak
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StreakEvent {
+    Extended(u32),
+    /// Extended past the longest streak so far
+    Record(u32),
+    /// Today starts a new streak, the previous one of that many days ended
+    Broken(u32),
+}
+
+impl StreakEvent {
+    /// `subject` is "Серия чата" or "Серия Alice"
+    pub fn message(&self, subject: &str) -> String {
+        match *self {
+            StreakEvent::Extended(days) => format!("🔥 {subject}: {days} {} подряд", days_word(days)),
+            StreakEvent::Record(days) => format!("🏆 {subject}: {days} {} подряд — новый рекорд!", days_word(days)),
+            StreakEvent::Broken(days) => format!("💔 {subject} ({days} {} подряд) прервалась. Начинаем новую!", days_word(days)),
+        }
+    }
+}
+
+/// "день", "дня" or "дней" to go after the number
+pub fn days_word(days: u32) -> &'static str {
+    match (days % 10, days % 100) {
+        (_, 11..=14) => "дней",
+        (1, _) => "день",
+        (2..=4, _) => "дня",
+        _ => "дней",
+    }
+}
+
+/// Runs of consecutive days with at least `minimum` seconds, as (first day, length), oldest first.
+/// `days` must be sorted by date.
+fn runs(days: &[(NaiveDate, i64)], minimum: i64) -> Vec<(NaiveDate, u32)> {
+    let mut runs: Vec<(NaiveDate, u32)> = Vec::new();
+    let mut last: Option<NaiveDate> = None;
+    for &(date, _) in days.iter().filter(|(_, seconds)| *seconds >= minimum) {
+        match runs.last_mut() {
+            Some((_, length)) if last.and_then(|last| last.succ_opt()) == Some(date) => *length += 1,
+            _ => runs.push((date, 1)),
+        }
+        last = Some(date);
+    }
+    runs
+}
+
+fn run_end((start, length): (NaiveDate, u32)) -> NaiveDate {
+    start + Days::new(u64::from(length) - 1)
+}
+
+pub fn streaks(days: &[(NaiveDate, i64)], minimum: i64, today: NaiveDate) -> Streaks {
+    let runs = runs(days, minimum);
+    let yesterday = today.pred_opt().unwrap_or(today);
+    Streaks {
+        current: runs.iter().rev()
+            .find(|run| run_end(**run) <= today)
+            .filter(|run| run_end(**run) == today || run_end(**run) == yesterday)
+            .map_or(0, |(_, length)| *length),
+        longest: runs.iter().map(|(_, length)| *length).max().unwrap_or(0),
+    }
+}
+
+/// The streak news after `added` seconds were credited to today, if today just reached the minimum.
+pub fn event(days: &[(NaiveDate, i64)], minimum: i64, today: NaiveDate, added: i64) -> Option<StreakEvent> {
+    let total = days.iter().find(|(date, _)| *date == today).map_or(0, |(_, seconds)| *seconds);
+    if total < minimum || total - added >= minimum {
+        return None;
+    }
+
+    let before: Vec<(NaiveDate, i64)> = days.iter().copied().filter(|(date, _)| *date < today).collect();
+    let Streaks { current, longest } = streaks(&before, minimum, today);
+    let current = current + 1;
+    if current == 1 {
+        return runs(&before, minimum).last()
+            .map(|(_, length)| *length)
+            .filter(|length| *length >= 2)
+            .map(StreakEvent::Broken);
+    }
+    Some(if current > longest { StreakEvent::Record(current) } else { StreakEvent::Extended(current) })
+}
+
+/// Daily rows of the chat, or of a member of it, with the chat's streak minimum
+pub async fn history(store: &dyn StandingStore, chat_id: ChatId, user_id: Option<UserId>) -> StoreResult<(Vec<(NaiveDate, i64)>, i64)> {
+    let days = match user_id {
+        Some(user_id) => store.get_member_daily_totals(chat_id, user_id, DateRange::default()).await?,
+        None => store.get_daily_totals(chat_id, DateRange::default()).await?,
+    };
+    Ok((days, store.get_streak_minimum(chat_id).await?))
+}
+
+/// Posts the streak news, if any, after the time from `start_timestamp` to `end_timestamp` was
+/// credited to the chat or to a member
+pub async fn announce(bot: &Bot,
+                      store: &dyn StandingStore,
+                      chat_id: ChatId,
+                      user_id: Option<UserId>,
+                      subject: &str,
+                      start_timestamp: i64,
+                      end_timestamp: i64) -> StoreResult<()> {
+    let boundary = store.get_day_boundary(chat_id).await?;
+    let today = boundary.date_of(end_timestamp);
+    let added = split_by_day(start_timestamp, end_timestamp, boundary).into_iter()
+        .filter(|(date, _)| *date == today)
+        .map(|(_, seconds)| seconds)
+        .sum();
+    let (days, minimum) = history(store, chat_id, user_id).await?;
+    if let Some(event) = event(&days, minimum, today, added) {
+        bot.send_message(chat_id, event.message(subject)).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 5, d).unwrap()
+    }
+
+    #[test]
+    fn test_streaks() {
+        let days = [(date(1), 600), (date(2), 700), (date(3), 100), (date(4), 600), (date(5), 600), (date(6), 600), (date(8), 900)];
+        assert_eq!(streaks(&days, 600, date(8)), Streaks { current: 1, longest: 3 });
+        assert_eq!(streaks(&days, 600, date(9)), Streaks { current: 1, longest: 3 });
+        assert_eq!(streaks(&days, 600, date(10)), Streaks { current: 0, longest: 3 });
+        assert_eq!(streaks(&days, 50, date(6)), Streaks { current: 6, longest: 6 });
+        assert_eq!(streaks(&[], 600, date(6)), Streaks::default());
+    }
+
+    #[test]
+    fn test_event() {
+        let days = [(date(1), 600), (date(2), 600), (date(3), 300)];
+        // Today only reaches the minimum once
+        assert_eq!(event(&days, 600, date(3), 300), None);
+        assert_eq!(event(&[(date(1), 600), (date(2), 600), (date(3), 700)], 600, date(3), 400), Some(StreakEvent::Record(3)));
+        assert_eq!(event(&[(date(1), 600), (date(2), 600), (date(3), 700)], 600, date(3), 50), None);
+
+        let days = [(date(1), 600), (date(2), 600), (date(3), 600), (date(5), 600), (date(6), 600)];
+        assert_eq!(event(&days, 600, date(6), 600), Some(StreakEvent::Extended(2)));
+        assert_eq!(event(&days[..4], 600, date(5), 600), Some(StreakEvent::Broken(3)));
+        assert_eq!(event(&[(date(5), 600)], 600, date(5), 600), None);
+    }
+
+    #[test]
+    fn test_messages() {
+        assert_eq!(StreakEvent::Extended(2).message("Серия чата"), "🔥 Серия чата: 2 дня подряд");
+        assert_eq!(StreakEvent::Broken(21).message("Серия Alice"), "💔 Серия Alice (21 день подряд) прервалась. Начинаем новую!");
+        assert_eq!(days_word(11), "дней");
+        assert_eq!(days_word(5), "дней");
+    }
+}
@@ -1,7 +1,9 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Days, NaiveDate, Utc};
+
+pub const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
 
 pub fn get_time_difference_from_now(timestamp: i64) -> String {
-    return get_time_difference(timestamp, Utc::now().timestamp());
+    get_time_difference(timestamp, Utc::now().timestamp())
 }
 
 pub fn get_time_difference(timestamp: i64, end_timestamp: i64) -> String {
@@ -10,9 +12,9 @@ pub fn get_time_difference(timestamp: i64, end_timestamp: i64) -> String {
         let difference = end.signed_duration_since(first);
         let minutes = difference.num_minutes();
         let seconds = difference.num_seconds() % 60;
-        return format!("{minutes} минут {seconds} секунд");
+        format!("{minutes} минут {seconds} секунд")
     } else {
-        return "".to_string();
+        "".to_string()
     }
 }
 
@@ -20,19 +22,301 @@ pub fn total_seconds_to_hms(total: i64) -> String {
     let hours = total / 3600;
     let minutes = (total % 3600) / 60;
     let seconds = total % 60;
-    return format!("{hours} часов {minutes} минут {seconds} секунд");
+    format!("{hours} часов {minutes} минут {seconds} секунд")
 }
 
+/// Where a chat's calendar day begins: its UTC offset and an optional shift of the day start
+/// (e.g. 04:00 for people who stay up late). The default is a UTC day starting at midnight.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DayBoundary {
+    pub utc_offset_minutes: i32,
+    pub day_start_minutes: i32,
+}
 
-pub fn get_seconds_difference_from_now(timestamp: i64) -> i64 {
-    get_seconds_difference(timestamp,Utc::now())
+impl DayBoundary {
+    /// Seconds to add to a UTC timestamp so that the chat's day starts at UTC midnight.
+    pub fn shift_seconds(&self) -> i64 {
+        i64::from(self.utc_offset_minutes - self.day_start_minutes) * 60
+    }
+
+    /// Timestamp at which the given local day begins.
+    pub fn start_of(&self, date: NaiveDate) -> i64 {
+        date.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp() - self.shift_seconds()
+    }
+
+    pub fn date_of(&self, timestamp: i64) -> NaiveDate {
+        DateTime::from_timestamp(timestamp + self.shift_seconds(), 0)
+            .unwrap_or_default()
+            .date_naive()
+    }
 }
 
-pub fn get_seconds_difference(timestamp: i64,now:DateTime<Utc>) -> i64 {
-    if let Some(past) = DateTime::from_timestamp(timestamp,0) {
-        let difference = now.signed_duration_since(past);
-        return difference.num_seconds();
-    } else {
-        return 0;
+/// What a session is credited with when `seconds` are asked for: never negative, and at most the
+/// session's length or a day, whichever is longer.
+pub fn credited_seconds(start_timestamp: i64, end_timestamp: i64, seconds: i64) -> i64 {
+    seconds.clamp(0, end_timestamp.saturating_sub(start_timestamp).max(SECONDS_PER_DAY))
+}
+
+/// Splits the interval between two timestamps at the chat's day boundaries into the seconds spent on each day.
+pub fn split_by_day(start_timestamp: i64, end_timestamp: i64, boundary: DayBoundary) -> Vec<(NaiveDate, i64)> {
+    let shift = boundary.shift_seconds();
+    let mut result = Vec::new();
+    let (Some(mut current), Some(end)) = (DateTime::from_timestamp(start_timestamp + shift, 0), DateTime::from_timestamp(end_timestamp + shift, 0)) else {
+        return result;
+    };
+    while current < end {
+        let day = current.date_naive();
+        let next_midnight = day
+            .checked_add_days(Days::new(1))
+            .map(|d| d.and_hms_opt(0, 0, 0).unwrap().and_utc())
+            .unwrap_or(end);
+        let segment_end = next_midnight.min(end);
+        result.push((day, segment_end.signed_duration_since(current).num_seconds()));
+        current = segment_end;
+    }
+    result
+}
+
+/// Parses a UTC offset such as `+6`, `UTC+6`, `-3` or `+05:30` into minutes.
+pub fn parse_utc_offset(text: &str) -> Option<i32> {
+    let text = text.trim();
+    let text = text.strip_prefix("UTC").or_else(|| text.strip_prefix("utc")).unwrap_or(text);
+    let (sign, rest) = match text.chars().next()? {
+        '+' => (1, &text[1..]),
+        '-' => (-1, &text[1..]),
+        _ => (1, text),
+    };
+    let (hours, minutes) = match rest.split_once(':') {
+        Some((hours, minutes)) => (hours.parse::<i32>().ok()?, minutes.parse::<i32>().ok()?),
+        None => (rest.parse::<i32>().ok()?, 0),
+    };
+    if !(0..=14).contains(&hours) || !(0..60).contains(&minutes) {
+        return None;
+    }
+    Some(sign * (hours * 60 + minutes))
+}
+
+pub fn format_utc_offset(minutes: i32) -> String {
+    let sign = if minutes < 0 { '-' } else { '+' };
+    let minutes = minutes.abs();
+    format!("UTC{sign}{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+/// Parses a time of day such as `04:00` or `4` into minutes after midnight.
+pub fn parse_time_of_day(text: &str) -> Option<i32> {
+    let text = text.trim();
+    let (hours, minutes) = match text.split_once(':') {
+        Some((hours, minutes)) => (hours.parse::<i32>().ok()?, minutes.parse::<i32>().ok()?),
+        None => (text.parse::<i32>().ok()?, 0),
+    };
+    if !(0..24).contains(&hours) || !(0..60).contains(&minutes) {
+        return None;
+    }
+    Some(hours * 60 + minutes)
+}
+
+pub fn format_time_of_day(minutes: i32) -> String {
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+/// Signed duration like `+15m`, `-1h`, `1h30m`, `90s` or `-20` (minutes), in seconds
+pub fn parse_duration(text: &str) -> Option<i64> {
+    let text = text.trim();
+    let (sign, text) = match text.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, text.strip_prefix('+').unwrap_or(text)),
+    };
+    if let Ok(minutes) = text.parse::<i64>() {
+        return minutes.checked_mul(60)?.checked_mul(sign);
+    }
+
+    let mut seconds: i64 = 0;
+    let mut number = String::new();
+    for c in text.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c {
+            'h' | 'ч' => 3600,
+            'm' | 'м' => 60,
+            's' | 'с' => 1,
+            _ => return None,
+        };
+        seconds = seconds.checked_add(number.parse::<i64>().ok()?.checked_mul(unit)?)?;
+        number.clear();
+    }
+    if !number.is_empty() || text.is_empty() {
+        return None;
+    }
+    seconds.checked_mul(sign)
+}
+
+/// `today`, `yesterday` (or сегодня/вчера) or `YYYY-MM-DD`
+pub fn parse_day(text: &str, today: NaiveDate) -> Option<NaiveDate> {
+    match text {
+        "today" | "сегодня" => Some(today),
+        "yesterday" | "вчера" => today.checked_sub_days(Days::new(1)),
+        _ => NaiveDate::parse_from_str(text, "%Y-%m-%d").ok(),
+    }
+}
+
+pub fn format_signed_duration(seconds: i64) -> String {
+    let sign = if seconds < 0 { '-' } else { '+' };
+    format!("{sign}{}", total_seconds_to_hms(seconds.abs()))
+}
+
+/// "1ч 20м", "2ч", "45м", or "30с" below a minute
+pub fn format_short_duration(seconds: i64) -> String {
+    let (hours, minutes) = (seconds / 3600, seconds % 3600 / 60);
+    match (hours, minutes) {
+        (0, 0) if seconds > 0 => format!("{seconds}с"),
+        (0, minutes) => format!("{minutes}м"),
+        (hours, 0) => format!("{hours}ч"),
+        (hours, minutes) => format!("{hours}ч {minutes}м"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn timestamp(y: i32, m: u32, d: u32, h: u32, min: u32) -> i64 {
+        Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap().timestamp()
+    }
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn split_by_day_utc(start_timestamp: i64, end_timestamp: i64) -> Vec<(NaiveDate, i64)> {
+        split_by_day(start_timestamp, end_timestamp, DayBoundary::default())
+    }
+
+    #[test]
+    fn test_split_within_day() {
+        let split = split_by_day_utc(timestamp(2024, 5, 1, 10, 0), timestamp(2024, 5, 1, 10, 30));
+        assert_eq!(split, vec![(date(2024, 5, 1), 1800)]);
+    }
+
+    #[test]
+    fn test_split_across_midnight() {
+        let split = split_by_day_utc(timestamp(2024, 5, 1, 23, 30), timestamp(2024, 5, 2, 0, 30));
+        assert_eq!(split, vec![(date(2024, 5, 1), 1800), (date(2024, 5, 2), 1800)]);
+    }
+
+    #[test]
+    fn test_split_across_several_days() {
+        let split = split_by_day_utc(timestamp(2024, 12, 31, 22, 0), timestamp(2025, 1, 2, 1, 0));
+        assert_eq!(split, vec![(date(2024, 12, 31), 7200), (date(2025, 1, 1), 86400), (date(2025, 1, 2), 3600)]);
+    }
+
+    #[test]
+    fn test_split_ending_at_midnight() {
+        let split = split_by_day_utc(timestamp(2024, 5, 1, 23, 0), timestamp(2024, 5, 2, 0, 0));
+        assert_eq!(split, vec![(date(2024, 5, 1), 3600)]);
+    }
+
+    #[test]
+    fn test_split_empty_interval() {
+        assert!(split_by_day_utc(timestamp(2024, 5, 1, 10, 0), timestamp(2024, 5, 1, 10, 0)).is_empty());
+        assert!(split_by_day_utc(timestamp(2024, 5, 1, 10, 0), timestamp(2024, 5, 1, 9, 0)).is_empty());
+    }
+
+    #[test]
+    fn test_split_with_utc_offset() {
+        let boundary = DayBoundary { utc_offset_minutes: 6 * 60, day_start_minutes: 0 };
+        // 17:30..18:30 UTC is 23:30..00:30 in UTC+6
+        let split = split_by_day(timestamp(2024, 5, 1, 17, 30), timestamp(2024, 5, 1, 18, 30), boundary);
+        assert_eq!(split, vec![(date(2024, 5, 1), 1800), (date(2024, 5, 2), 1800)]);
+    }
+
+    #[test]
+    fn test_split_with_day_start() {
+        let boundary = DayBoundary { utc_offset_minutes: 6 * 60, day_start_minutes: 4 * 60 };
+        // 23:30..00:30 local time stays on the same day when the day starts at 04:00
+        let split = split_by_day(timestamp(2024, 5, 1, 17, 30), timestamp(2024, 5, 1, 18, 30), boundary);
+        assert_eq!(split, vec![(date(2024, 5, 1), 3600)]);
+        // 03:30..04:30 local time is split at 04:00
+        let split = split_by_day(timestamp(2024, 5, 1, 21, 30), timestamp(2024, 5, 1, 22, 30), boundary);
+        assert_eq!(split, vec![(date(2024, 5, 1), 1800), (date(2024, 5, 2), 1800)]);
+    }
+
+    #[test]
+    fn test_date_of() {
+        let boundary = DayBoundary { utc_offset_minutes: 6 * 60, day_start_minutes: 4 * 60 };
+        assert_eq!(boundary.date_of(timestamp(2024, 5, 1, 21, 59)), date(2024, 5, 1));
+        assert_eq!(boundary.date_of(timestamp(2024, 5, 1, 22, 0)), date(2024, 5, 2));
+        assert_eq!(DayBoundary::default().date_of(timestamp(2024, 5, 1, 23, 59)), date(2024, 5, 1));
+        assert_eq!(boundary.start_of(date(2024, 5, 2)), timestamp(2024, 5, 1, 22, 0));
+    }
+
+    #[test]
+    fn test_parse_utc_offset() {
+        assert_eq!(parse_utc_offset("+6"), Some(360));
+        assert_eq!(parse_utc_offset("UTC+6"), Some(360));
+        assert_eq!(parse_utc_offset("6"), Some(360));
+        assert_eq!(parse_utc_offset("-3"), Some(-180));
+        assert_eq!(parse_utc_offset("+05:30"), Some(330));
+        assert_eq!(parse_utc_offset("+15"), None);
+        assert_eq!(parse_utc_offset("abc"), None);
+        assert_eq!(parse_utc_offset(""), None);
+        assert_eq!(format_utc_offset(330), "UTC+05:30");
+        assert_eq!(format_utc_offset(-180), "UTC-03:00");
+    }
+
+    #[test]
+    fn test_parse_time_of_day() {
+        assert_eq!(parse_time_of_day("04:00"), Some(240));
+        assert_eq!(parse_time_of_day("4"), Some(240));
+        assert_eq!(parse_time_of_day("23:59"), Some(1439));
+        assert_eq!(parse_time_of_day("24:00"), None);
+        assert_eq!(format_time_of_day(240), "04:00");
+    }
+
+    #[test]
+    fn test_credited_seconds() {
+        assert_eq!(credited_seconds(0, 600, 1200), 1200);
+        assert_eq!(credited_seconds(0, 600, -60), 0);
+        assert_eq!(credited_seconds(0, 600, 50_000_000_000), SECONDS_PER_DAY);
+        assert_eq!(credited_seconds(0, 3 * SECONDS_PER_DAY, 3 * SECONDS_PER_DAY), 3 * SECONDS_PER_DAY);
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("+15m"), Some(900));
+        assert_eq!(parse_duration("-1h"), Some(-3600));
+        assert_eq!(parse_duration("1h30m"), Some(5400));
+        assert_eq!(parse_duration("90s"), Some(90));
+        assert_eq!(parse_duration("-20"), Some(-1200));
+        assert_eq!(parse_duration("1ч5м"), Some(3900));
+        assert_eq!(parse_duration("15"), Some(900));
+        assert_eq!(parse_duration("15x"), None);
+        assert_eq!(parse_duration("1h30"), None);
+        assert_eq!(parse_duration("+"), None);
+        assert_eq!(parse_duration("999999999999999999"), None);
+        assert_eq!(parse_duration("9999999999999999h"), None);
+        assert_eq!(parse_duration("9223372036854775h9223372036854775h"), None);
+    }
+
+    #[test]
+    fn test_parse_day() {
+        let today = NaiveDate::from_ymd_opt(2026, 10, 2).unwrap();
+        assert_eq!(parse_day("today", today), Some(today));
+        assert_eq!(parse_day("вчера", today), NaiveDate::from_ymd_opt(2026, 10, 1));
+        assert_eq!(parse_day("2026-09-30", today), NaiveDate::from_ymd_opt(2026, 9, 30));
+        assert_eq!(parse_day("someday", today), None);
+        assert_eq!(format_signed_duration(-900), "-0 часов 15 минут 0 секунд");
+    }
+
+    #[test]
+    fn test_format_short_duration() {
+        assert_eq!(format_short_duration(4800), "1ч 20м");
+        assert_eq!(format_short_duration(7200), "2ч");
+        assert_eq!(format_short_duration(2700), "45м");
+        assert_eq!(format_short_duration(30), "30с");
+        assert_eq!(format_short_duration(0), "0м");
     }
 }
@@ -1,7 +1,13 @@
-use std::{ops::Deref, sync::Arc};
+use std::sync::Arc;
 
-use sqlx::{Error, Pool, SqlitePool, Executor, Row};
-use teloxide::types::ChatId;
+use async_trait::async_trait;
+use chrono::{NaiveDate, Weekday};
+use sqlx::{migrate::Migrator, sqlite::SqliteRow, Error, Pool, Row, Sqlite, SqlitePool, Transaction};
+use teloxide::types::{ChatId, UserId};
+
+use crate::{digest::{Digest, DigestKind}, goal::{GoalKind, Goals}, hall_of_fame::{Champion, TitleKind}, period::DateRange, streak::DEFAULT_MINIMUM_SECONDS, store::{Adjustment, ChatInfo, ChatTotal, MergePolicy, SessionRecord, StandingStore, StartTrigger, StopTrigger, StoreResult}, time::{credited_seconds, split_by_day, DayBoundary}};
+
+static MIGRATOR: Migrator = sqlx::migrate!();
 
 #[derive(Clone)]
 pub struct Total {
@@ -9,208 +15,1021 @@ pub struct Total {
 }
 
 impl Total {
-    pub async fn create_table(path: &str) -> Result<Arc<Self>, Error> {
+    /// Opens the database and brings its schema up to date. Fails if the database was migrated
+    /// by a newer build that has migrations this one doesn't know about.
+    pub async fn connect(path: &str) -> Result<Arc<Self>, Error> {
         let pool = SqlitePool::connect(format!("sqlite:{path}?mode=rwc").as_str()).await?;
+        MIGRATOR.run(&pool).await?;
+        Ok(Arc::new(Self {pool}))
+    }
+
+    /// Adds seconds to the chat's day row, or to the member's one when `user_id` is given.
+    async fn add_to_total(tx: &mut Transaction<'_, Sqlite>, ChatId(chat_id): ChatId, user_id: Option<UserId>, date: &str, seconds: i64) -> Result<(), Error> {
+        match user_id {
+            None => sqlx::query(
+                "
+            INSERT INTO total VALUES (?, ?, ?)
+            ON CONFLICT(chat_id,date) DO UPDATE SET total_seconds=total_seconds + excluded.total_seconds
+                ")
+                .bind(chat_id)
+                .bind(date)
+                .bind(seconds)
+                .execute(&mut **tx)
+                .await?,
+            Some(UserId(user_id)) => sqlx::query(
+                "
+            INSERT INTO member_total VALUES (?, ?, ?, ?)
+            ON CONFLICT(chat_id,user_id,date) DO UPDATE SET total_seconds=total_seconds + excluded.total_seconds
+                ")
+                .bind(chat_id)
+                .bind(user_id as i64)
+                .bind(date)
+                .bind(seconds)
+                .execute(&mut **tx)
+                .await?,
+        };
+        Ok(())
+    }
+
+    /// Champions from rows of kind, period_start, chat_id and seconds, unknown kinds are skipped
+    fn champions(rows: Vec<SqliteRow>) -> StoreResult<Vec<Champion>> {
+        let mut champions = Vec::new();
+        for row in rows {
+            let kind: String = row.try_get(0)?;
+            let Some(kind) = TitleKind::parse(&kind) else {
+                continue;
+            };
+            let start: String = row.try_get(1)?;
+            champions.push(Champion {
+                kind,
+                start: NaiveDate::parse_from_str(&start, "%Y-%m-%d")?,
+                chat_id: row.try_get(2)?,
+                seconds: row.try_get(3)?,
+            });
+        }
+        Ok(champions)
+    }
+
+    /// Takes seconds off a day's total without letting it go below zero
+    async fn remove_from_total(tx: &mut Transaction<'_, Sqlite>, ChatId(chat_id): ChatId, user_id: Option<UserId>, date: &str, seconds: i64) -> Result<(), Error> {
+        match user_id {
+            None => sqlx::query("UPDATE total SET total_seconds = MAX(0, total_seconds - ?) WHERE chat_id = ? AND date = ?")
+                .bind(seconds)
+                .bind(chat_id)
+                .bind(date)
+                .execute(&mut **tx)
+                .await?,
+            Some(UserId(user_id)) => sqlx::query("UPDATE member_total SET total_seconds = MAX(0, total_seconds - ?) WHERE chat_id = ? AND user_id = ? AND date = ?")
+                .bind(seconds)
+                .bind(chat_id)
+                .bind(user_id as i64)
+                .bind(date)
+                .execute(&mut **tx)
+                .await?,
+        };
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl StandingStore for Total {
+    async fn get_day_boundary(&self, ChatId(chat_id): ChatId) -> StoreResult<DayBoundary> {
+        let row = sqlx::query("SELECT utc_offset_minutes, day_start_minutes FROM chat_settings WHERE chat_id = ?")
+            .bind(chat_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        match row {
+            Some(row) => Ok(DayBoundary {
+                utc_offset_minutes: row.try_get(0)?,
+                day_start_minutes: row.try_get(1)?,
+            }),
+            None => Ok(DayBoundary::default()),
+        }
+    }
+
+    async fn set_utc_offset(&self, ChatId(chat_id): ChatId, utc_offset_minutes: i32) -> StoreResult<()> {
         sqlx::query(
-            "
-CREATE TABLE IF NOT EXISTS total (
-    chat_id BIGINT,
-    date TEXT,
-    total_seconds INT,
-    CONSTRAINT id_date UNIQUE(chat_id, date)
-);
-        ").execute(&pool)
-            .await?;
-        return Ok(Arc::new(Self {pool}));
-    }
-
-    pub async fn set_total_today(self: &Self,ChatId(chat_id):ChatId,total_seconds: i64) -> Result<(),Error>
-    {
-            self.pool
-                .acquire()
-                .await?
-                .execute(
-                    sqlx::query(
-                        "
-            INSERT INTO total VALUES (?, date('now'), ?)
-            ON CONFLICT(chat_id,date) DO UPDATE SET total_seconds=excluded.total_seconds
-                                ",
-                    )
-                        .bind(chat_id)
-                        .bind(total_seconds),
-                )
+            "INSERT INTO chat_settings (chat_id, utc_offset_minutes) VALUES (?, ?)
+             ON CONFLICT(chat_id) DO UPDATE SET utc_offset_minutes=excluded.utc_offset_minutes")
+            .bind(chat_id)
+            .bind(utc_offset_minutes)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn set_day_start(&self, ChatId(chat_id): ChatId, day_start_minutes: i32) -> StoreResult<()> {
+        sqlx::query(
+            "INSERT INTO chat_settings (chat_id, day_start_minutes) VALUES (?, ?)
+             ON CONFLICT(chat_id) DO UPDATE SET day_start_minutes=excluded.day_start_minutes")
+            .bind(chat_id)
+            .bind(day_start_minutes)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn start_session(&self, ChatId(chat_id): ChatId, timestamp: i64, trigger: StartTrigger) -> StoreResult<()> {
+        sqlx::query("INSERT INTO sessions (chat_id, start_timestamp, start_trigger) VALUES (?, ?, ?)")
+            .bind(chat_id)
+            .bind(timestamp)
+            .bind(trigger.as_str())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn finish_session(&self,
+                            chat_id: ChatId,
+                            start_timestamp: i64,
+                            end_timestamp: i64,
+                            seconds: i64,
+                            trigger: StopTrigger) -> StoreResult<i64> {
+        let boundary = self.get_day_boundary(chat_id).await?;
+        let seconds = credited_seconds(start_timestamp, end_timestamp, seconds);
+        let mut tx = self.pool.begin().await?;
+        let updated = sqlx::query(
+            "UPDATE sessions SET end_timestamp = ?, seconds = ?, stop_trigger = ?, utc_offset_minutes = ?, day_start_minutes = ?
+             WHERE chat_id = ? AND user_id IS NULL AND start_timestamp = ? AND end_timestamp IS NULL")
+            .bind(end_timestamp)
+            .bind(seconds)
+            .bind(trigger.as_str())
+            .bind(boundary.utc_offset_minutes)
+            .bind(boundary.day_start_minutes)
+            .bind(chat_id.0)
+            .bind(start_timestamp)
+            .execute(&mut *tx)
+            .await?;
+        if updated.rows_affected() == 0 {
+            sqlx::query(
+                "INSERT INTO sessions (chat_id, start_timestamp, end_timestamp, seconds, stop_trigger, utc_offset_minutes, day_start_minutes)
+                 VALUES (?, ?, ?, ?, ?, ?, ?)")
+                .bind(chat_id.0)
+                .bind(start_timestamp)
+                .bind(end_timestamp)
+                .bind(seconds)
+                .bind(trigger.as_str())
+                .bind(boundary.utc_offset_minutes)
+                .bind(boundary.day_start_minutes)
+                .execute(&mut *tx)
                 .await?;
-            Ok(())
+        }
+        // The credited seconds are the last `seconds` before the end, so /cancel with minutes
+        // is split the same way as a regular session
+        for (date, day_seconds) in split_by_day(end_timestamp - seconds, end_timestamp, boundary) {
+            Self::add_to_total(&mut tx, chat_id, None, &date.to_string(), day_seconds).await?;
+        }
+        // Read back in the same transaction so the total always includes this session
+        let total: Option<i64> = sqlx::query_scalar("SELECT total_seconds FROM total WHERE chat_id = ? AND date = ?")
+            .bind(chat_id.0)
+            .bind(boundary.date_of(end_timestamp).to_string())
+            .fetch_optional(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok(total.unwrap_or(0))
+    }
+
+    async fn start_member_session(&self, ChatId(chat_id): ChatId, user_id: UserId, name: &str, timestamp: i64) -> StoreResult<bool> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            "INSERT INTO members VALUES (?, ?, ?)
+             ON CONFLICT(chat_id, user_id) DO UPDATE SET name=excluded.name")
+            .bind(chat_id)
+            .bind(user_id.0 as i64)
+            .bind(name)
+            .execute(&mut *tx)
+            .await?;
+        let open = sqlx::query("SELECT 1 FROM sessions WHERE chat_id = ? AND user_id = ? AND end_timestamp IS NULL")
+            .bind(chat_id)
+            .bind(user_id.0 as i64)
+            .fetch_optional(&mut *tx)
+            .await?;
+        if open.is_some() {
+            return Ok(false);
+        }
+        sqlx::query("INSERT INTO sessions (chat_id, user_id, start_timestamp, start_trigger) VALUES (?, ?, ?, ?)")
+            .bind(chat_id)
+            .bind(user_id.0 as i64)
+            .bind(timestamp)
+            .bind(StartTrigger::Sticker.as_str())
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn get_member_session_start(&self, ChatId(chat_id): ChatId, user_id: UserId) -> StoreResult<Option<i64>> {
+        let row = sqlx::query("SELECT start_timestamp FROM sessions WHERE chat_id = ? AND user_id = ? AND end_timestamp IS NULL")
+            .bind(chat_id)
+            .bind(user_id.0 as i64)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.map(|row| row.try_get(0)).transpose()?)
     }
 
-    pub async fn get_average_total_per_day_by_chat(self: &Self) -> Result<Vec<(i64, Option<i64>)>, Error> {
-        let rows = sqlx::query("SELECT chat_id, AVG(total_seconds) FROM total GROUP BY chat_id")
+    async fn finish_member_session(&self, chat_id: ChatId, user_id: UserId, end_timestamp: i64, trigger: StopTrigger) -> StoreResult<Option<(i64, i64)>> {
+        let Some(start_timestamp) = self.get_member_session_start(chat_id, user_id).await? else {
+            return Ok(None);
+        };
+        let boundary = self.get_day_boundary(chat_id).await?;
+        let seconds = end_timestamp - start_timestamp;
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            "UPDATE sessions SET end_timestamp = ?, seconds = ?, stop_trigger = ?, utc_offset_minutes = ?, day_start_minutes = ?
+             WHERE chat_id = ? AND user_id = ? AND end_timestamp IS NULL")
+            .bind(end_timestamp)
+            .bind(seconds)
+            .bind(trigger.as_str())
+            .bind(boundary.utc_offset_minutes)
+            .bind(boundary.day_start_minutes)
+            .bind(chat_id.0)
+            .bind(user_id.0 as i64)
+            .execute(&mut *tx)
+            .await?;
+        for (date, day_seconds) in split_by_day(start_timestamp, end_timestamp, boundary) {
+            let date = date.to_string();
+            Self::add_to_total(&mut tx, chat_id, None, &date, day_seconds).await?;
+            Self::add_to_total(&mut tx, chat_id, Some(user_id), &date, day_seconds).await?;
+        }
+        tx.commit().await?;
+
+        let member_total: Option<i64> = sqlx::query("SELECT total_seconds FROM member_total WHERE chat_id = ? AND user_id = ? AND date = ?")
+            .bind(chat_id.0)
+            .bind(user_id.0 as i64)
+            .bind(boundary.date_of(end_timestamp).to_string())
+            .fetch_optional(&self.pool)
+            .await?
+            .map(|row| row.try_get(0))
+            .transpose()?;
+        Ok(Some((start_timestamp, member_total.unwrap_or(0))))
+    }
+
+    async fn get_member_totals(&self, ChatId(chat_id): ChatId, range: DateRange) -> StoreResult<Vec<(i64, String, i64)>> {
+        let rows = sqlx::query(
+            "SELECT m.user_id, m.name, SUM(t.total_seconds) AS seconds FROM member_total t
+             JOIN members m ON m.chat_id = t.chat_id AND m.user_id = t.user_id
+             WHERE t.chat_id = ?1 AND (?2 IS NULL OR t.date >= ?2) AND (?3 IS NULL OR t.date <= ?3)
+             GROUP BY m.user_id ORDER BY seconds DESC")
+            .bind(chat_id)
+            .bind(range.start.map(|date| date.to_string()))
+            .bind(range.end.map(|date| date.to_string()))
             .fetch_all(&self.pool)
             .await?;
 
         let mut result = Vec::new();
         for row in rows {
-            let chat_id: i64 = row.try_get(0)?;
-            let avg_total_seconds: Option<f64> = row.try_get(1)?;
-            result.push((chat_id, avg_total_seconds.map(|x| x as i64)));
+            result.push((row.try_get(0)?, row.try_get(1)?, row.try_get(2)?));
         }
 
         Ok(result)
     }
 
-    pub async fn get_average_total_per_month_by_chat(self: &Self) -> Result<Vec<(i64, Option<i64>)>, Error> {
-        let rows = sqlx::query("SELECT chat_id, AVG(total_seconds) FROM total WHERE date >= date('now', 'start of month') GROUP BY chat_id")
+    async fn save_chat(&self, chat: &ChatInfo) -> StoreResult<()> {
+        sqlx::query(
+            "INSERT OR REPLACE INTO chats (chat_id, title, username, kind, active) VALUES (?, ?, ?, ?, ?)")
+            .bind(chat.chat_id)
+            .bind(&chat.title)
+            .bind(&chat.username)
+            .bind(&chat.kind)
+            .bind(chat.active)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn get_chats(&self) -> StoreResult<Vec<ChatInfo>> {
+        let rows = sqlx::query("SELECT chat_id, title, username, kind, active FROM chats ORDER BY chat_id")
             .fetch_all(&self.pool)
             .await?;
 
         let mut result = Vec::new();
         for row in rows {
-            let chat_id: i64 = row.try_get(0)?;
-            let avg_total_seconds: Option<f64> = row.try_get(1)?;
-            result.push((chat_id, avg_total_seconds.map(|x| x as i64)));
+            result.push(ChatInfo {
+                chat_id: row.try_get(0)?,
+                title: row.try_get(1)?,
+                username: row.try_get(2)?,
+                kind: row.try_get(3)?,
+                active: row.try_get(4)?,
+            });
         }
 
         Ok(result)
     }
 
-    pub async fn get_average_total_per_week_by_chat(self: &Self) -> Result<Vec<(i64, Option<i64>)>, Error> {
-        let rows = sqlx::query("SELECT chat_id, AVG(total_seconds) FROM total WHERE date >= date('now', 'weekday 0', '-7 days') GROUP BY chat_id")
+    async fn get_chat_totals(&self, range: DateRange) -> StoreResult<Vec<ChatTotal>> {
+        let rows = sqlx::query(
+            "SELECT chat_id, SUM(total_seconds), COUNT(*), MIN(date) FROM total
+             WHERE (?1 IS NULL OR date >= ?1) AND (?2 IS NULL OR date <= ?2)
+             GROUP BY chat_id ORDER BY chat_id")
+            .bind(range.start.map(|date| date.to_string()))
+            .bind(range.end.map(|date| date.to_string()))
             .fetch_all(&self.pool)
             .await?;
 
         let mut result = Vec::new();
         for row in rows {
-            let chat_id: i64 = row.try_get(0)?;
-            let avg_total_seconds: Option<f64> = row.try_get(1)?;
-            result.push((chat_id, avg_total_seconds.map(|x| x as i64)));
+            let first: String = row.try_get(3)?;
+            result.push(ChatTotal {
+                chat_id: row.try_get(0)?,
+                seconds: row.try_get(1)?,
+                days: row.try_get(2)?,
+                first: NaiveDate::parse_from_str(&first, "%Y-%m-%d")?,
+            });
         }
 
         Ok(result)
     }
 
-    pub async fn get_average_total_per_year_by_chat(self: &Self) -> Result<Vec<(i64, Option<i64>)>, Error> {
-        let rows = sqlx::query("SELECT chat_id, AVG(total_seconds) FROM total WHERE date >= date('now', 'start of year') GROUP BY chat_id")
+    async fn get_total_timestamp_day(&self, timestamp: i64, ChatId(chat_id): ChatId) -> StoreResult<Option<i64>> {
+        #[derive(sqlx::FromRow)]
+        struct TotalSecondsDbRow {
+            total_seconds: i64,
+        }
+        let date = self.get_day_boundary(ChatId(chat_id)).await?.date_of(timestamp);
+        let bytes = sqlx::query_as::<_, TotalSecondsDbRow>(
+            "SELECT total_seconds FROM total WHERE chat_id = ? and date = ?"
+        )
+            .bind(chat_id)
+            .bind(date.to_string())
+            .fetch_optional(&self.pool)
+            .await?
+            .map(|r| r.total_seconds);
+
+        Ok(bytes)
+    }
+
+
+    async fn get_daily_totals(&self, ChatId(chat_id): ChatId, range: DateRange) -> StoreResult<Vec<(NaiveDate, i64)>> {
+        let rows = sqlx::query(
+            "SELECT date, total_seconds FROM total
+             WHERE chat_id = ?1 AND (?2 IS NULL OR date >= ?2) AND (?3 IS NULL OR date <= ?3)
+             ORDER BY date")
+            .bind(chat_id)
+            .bind(range.start.map(|date| date.to_string()))
+            .bind(range.end.map(|date| date.to_string()))
             .fetch_all(&self.pool)
             .await?;
 
         let mut result = Vec::new();
         for row in rows {
-            let chat_id: i64 = row.try_get(0)?;
-            let avg_total_seconds: Option<f64> = row.try_get(1)?;
-            result.push((chat_id, avg_total_seconds.map(|x| x as i64)));
+            let date: String = row.try_get(0)?;
+            result.push((date.parse::<NaiveDate>()?, row.try_get(1)?));
         }
 
         Ok(result)
     }
 
-    pub async fn get_total_seconds_grouped_by_chat(self: &Self) -> Result<Vec<(i64, Option<i64>)>, Error> {
-        let rows = sqlx::query("SELECT chat_id, SUM(total_seconds) FROM total GROUP BY chat_id")
+    async fn get_member_daily_totals(&self, ChatId(chat_id): ChatId, UserId(user_id): UserId, range: DateRange) -> StoreResult<Vec<(NaiveDate, i64)>> {
+        let rows = sqlx::query(
+            "SELECT date, total_seconds FROM member_total
+             WHERE chat_id = ?1 AND user_id = ?2 AND (?3 IS NULL OR date >= ?3) AND (?4 IS NULL OR date <= ?4)
+             ORDER BY date")
+            .bind(chat_id)
+            .bind(user_id as i64)
+            .bind(range.start.map(|date| date.to_string()))
+            .bind(range.end.map(|date| date.to_string()))
             .fetch_all(&self.pool)
             .await?;
 
         let mut result = Vec::new();
         for row in rows {
-            let chat_id: i64 = row.try_get(0)?;
-            let avg_total_seconds: Option<i64> = row.try_get(1)?;
-            result.push((chat_id, avg_total_seconds));
+            let date: String = row.try_get(0)?;
+            result.push((date.parse::<NaiveDate>()?, row.try_get(1)?));
         }
 
         Ok(result)
     }
 
-    pub async fn get_total_seconds_grouped_by_month(self: &Self) -> Result<Vec<(i64, Option<i64>)>, Error> {
-        let rows = sqlx::query("SELECT chat_id, SUM(total_seconds) FROM total WHERE date >= date('now', 'start of month') GROUP BY chat_id")
+    async fn get_streak_minimum(&self, ChatId(chat_id): ChatId) -> StoreResult<i64> {
+        let minimum: Option<i64> = sqlx::query_scalar("SELECT streak_minimum_seconds FROM chat_settings WHERE chat_id = ?")
+            .bind(chat_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(minimum.unwrap_or(DEFAULT_MINIMUM_SECONDS))
+    }
+
+    async fn set_streak_minimum(&self, ChatId(chat_id): ChatId, seconds: i64) -> StoreResult<()> {
+        sqlx::query(
+            "INSERT INTO chat_settings (chat_id, streak_minimum_seconds) VALUES (?, ?)
+             ON CONFLICT(chat_id) DO UPDATE SET streak_minimum_seconds=excluded.streak_minimum_seconds")
+            .bind(chat_id)
+            .bind(seconds)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn get_week_start(&self, ChatId(chat_id): ChatId) -> StoreResult<Weekday> {
+        let week_start: Option<u8> = sqlx::query_scalar("SELECT week_start FROM chat_settings WHERE chat_id = ?")
+            .bind(chat_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(week_start.and_then(|day| Weekday::try_from(day.wrapping_sub(1)).ok()).unwrap_or(Weekday::Mon))
+    }
+
+    async fn set_week_start(&self, ChatId(chat_id): ChatId, week_start: Weekday) -> StoreResult<()> {
+        sqlx::query(
+            "INSERT INTO chat_settings (chat_id, week_start) VALUES (?, ?)
+             ON CONFLICT(chat_id) DO UPDATE SET week_start=excluded.week_start")
+            .bind(chat_id)
+            .bind(week_start.number_from_monday())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn get_announcements(&self, ChatId(chat_id): ChatId) -> StoreResult<bool> {
+        let enabled: Option<bool> = sqlx::query_scalar("SELECT announcements FROM chat_settings WHERE chat_id = ?")
+            .bind(chat_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(enabled.unwrap_or(true))
+    }
+
+    async fn set_announcements(&self, ChatId(chat_id): ChatId, enabled: bool) -> StoreResult<()> {
+        sqlx::query(
+            "INSERT INTO chat_settings (chat_id, announcements) VALUES (?, ?)
+             ON CONFLICT(chat_id) DO UPDATE SET announcements=excluded.announcements")
+            .bind(chat_id)
+            .bind(enabled)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn get_goals(&self, ChatId(chat_id): ChatId) -> StoreResult<Goals> {
+        let rows = sqlx::query("SELECT kind, seconds FROM goals WHERE chat_id = ?")
+            .bind(chat_id)
             .fetch_all(&self.pool)
             .await?;
 
-        let mut result = Vec::new();
+        let mut goals = Goals::default();
         for row in rows {
-            let chat_id: i64 = row.try_get(0)?;
-            let sum_total_seconds: Option<i64> = row.try_get(1)?;
-            result.push((chat_id, sum_total_seconds));
+            let kind: String = row.try_get(0)?;
+            let seconds: i64 = row.try_get(1)?;
+            match kind.as_str() {
+                "day" => goals.day = Some(seconds),
+                "week" => goals.week = Some(seconds),
+                _ => {}
+            }
         }
+        Ok(goals)
+    }
 
-        Ok(result)
+    async fn set_goal(&self, ChatId(chat_id): ChatId, kind: GoalKind, seconds: Option<i64>) -> StoreResult<()> {
+        match seconds {
+            Some(seconds) => sqlx::query("INSERT OR REPLACE INTO goals (chat_id, kind, seconds) VALUES (?, ?, ?)")
+                .bind(chat_id)
+                .bind(kind.as_str())
+                .bind(seconds)
+                .execute(&self.pool)
+                .await?,
+            None => sqlx::query("DELETE FROM goals WHERE chat_id = ? AND kind = ?")
+                .bind(chat_id)
+                .bind(kind.as_str())
+                .execute(&self.pool)
+                .await?,
+        };
+        Ok(())
+    }
+
+    async fn mark_goal_reached(&self, ChatId(chat_id): ChatId, kind: GoalKind, period_start: NaiveDate) -> StoreResult<bool> {
+        let inserted = sqlx::query("INSERT OR IGNORE INTO goals_reached (chat_id, kind, period_start) VALUES (?, ?, ?)")
+            .bind(chat_id)
+            .bind(kind.as_str())
+            .bind(period_start.to_string())
+            .execute(&self.pool)
+            .await?;
+        Ok(inserted.rows_affected() > 0)
     }
 
-    pub async fn get_total_seconds_grouped_by_week(self: &Self) -> Result<Vec<(i64, Option<i64>)>, Error> {
-        let rows = sqlx::query("SELECT chat_id, SUM(total_seconds) FROM total WHERE date >= date('now', 'weekday 0', '-7 days') GROUP BY chat_id;")
+    async fn get_digests(&self) -> StoreResult<Vec<Digest>> {
+        let rows = sqlx::query("SELECT chat_id, kind, minutes, last_sent FROM digests ORDER BY chat_id, kind")
             .fetch_all(&self.pool)
             .await?;
 
-        let mut result = Vec::new();
+        let mut digests = Vec::new();
         for row in rows {
-            let chat_id: i64 = row.try_get(0)?;
-            let sum_total_seconds: Option<i64> = row.try_get(1)?;
-            result.push((chat_id, sum_total_seconds));
+            let kind: String = row.try_get(1)?;
+            let Some(kind) = DigestKind::parse(&kind) else {
+                continue;
+            };
+            let last_sent: Option<String> = row.try_get(3)?;
+            digests.push(Digest {
+                chat_id: row.try_get(0)?,
+                kind,
+                minutes: row.try_get(2)?,
+                last_sent: last_sent.and_then(|date| NaiveDate::parse_from_str(&date, "%Y-%m-%d").ok()),
+            });
         }
+        Ok(digests)
+    }
 
-        Ok(result)
+    async fn set_digest(&self, ChatId(chat_id): ChatId, kind: DigestKind, minutes: Option<i32>) -> StoreResult<()> {
+        match minutes {
+            Some(minutes) => sqlx::query(
+                "INSERT INTO digests (chat_id, kind, minutes) VALUES (?, ?, ?)
+                 ON CONFLICT(chat_id, kind) DO UPDATE SET minutes=excluded.minutes")
+                .bind(chat_id)
+                .bind(kind.as_str())
+                .bind(minutes)
+                .execute(&self.pool)
+                .await?,
+            None => sqlx::query("DELETE FROM digests WHERE chat_id = ? AND kind = ?")
+                .bind(chat_id)
+                .bind(kind.as_str())
+                .execute(&self.pool)
+                .await?,
+        };
+        Ok(())
+    }
+
+    async fn mark_digest_sent(&self, ChatId(chat_id): ChatId, kind: DigestKind, date: NaiveDate) -> StoreResult<()> {
+        sqlx::query("UPDATE digests SET last_sent = ? WHERE chat_id = ? AND kind = ?")
+            .bind(date.to_string())
+            .bind(chat_id)
+            .bind(kind.as_str())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn get_champions(&self) -> StoreResult<Vec<Champion>> {
+        let rows = sqlx::query("SELECT kind, period_start, chat_id, seconds FROM champions ORDER BY period_start, kind")
+            .fetch_all(&self.pool)
+            .await?;
+        Self::champions(rows)
+    }
+
+    async fn get_unannounced_champions(&self) -> StoreResult<Vec<Champion>> {
+        let rows = sqlx::query("SELECT kind, period_start, chat_id, seconds FROM champions WHERE NOT announced ORDER BY period_start, kind")
+            .fetch_all(&self.pool)
+            .await?;
+        Self::champions(rows)
     }
 
-    pub async fn get_total_seconds_grouped_by_year(self: &Self) -> Result<Vec<(i64, Option<i64>)>, Error> {
-        let rows = sqlx::query("SELECT chat_id, SUM(total_seconds) FROM total WHERE date >= date('now', 'start of year') GROUP BY chat_id")
+    async fn add_champion(&self, champion: &Champion, announced: bool) -> StoreResult<()> {
+        sqlx::query("INSERT OR IGNORE INTO champions (kind, period_start, chat_id, seconds, announced) VALUES (?, ?, ?, ?, ?)")
+            .bind(champion.kind.as_str())
+            .bind(champion.start.to_string())
+            .bind(champion.chat_id)
+            .bind(champion.seconds)
+            .bind(announced)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn mark_champion_announced(&self, kind: TitleKind, start: NaiveDate) -> StoreResult<()> {
+        sqlx::query("UPDATE champions SET announced = TRUE WHERE kind = ? AND period_start = ?")
+            .bind(kind.as_str())
+            .bind(start.to_string())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn get_sessions(&self, chat_id: ChatId, range: DateRange) -> StoreResult<Vec<SessionRecord>> {
+        let boundary = self.get_day_boundary(chat_id).await?;
+        let rows = sqlx::query(
+            "SELECT id, user_id, start_timestamp, end_timestamp, seconds, start_trigger, stop_trigger FROM sessions
+             WHERE chat_id = ?1 AND undone_at IS NULL
+               AND (?2 IS NULL OR start_timestamp >= ?2) AND (?3 IS NULL OR start_timestamp < ?3)
+             ORDER BY start_timestamp, id")
+            .bind(chat_id.0)
+            .bind(range.start.map(|date| boundary.start_of(date)))
+            .bind(range.end.and_then(|date| date.succ_opt()).map(|date| boundary.start_of(date)))
             .fetch_all(&self.pool)
             .await?;
 
         let mut result = Vec::new();
         for row in rows {
-            let chat_id: i64 = row.try_get(0)?;
-            let sum_total_seconds: Option<i64> = row.try_get(1)?;
-            result.push((chat_id, sum_total_seconds));
+            result.push(SessionRecord {
+                id: row.try_get(0)?,
+                user_id: row.try_get(1)?,
+                start_timestamp: row.try_get(2)?,
+                end_timestamp: row.try_get(3)?,
+                seconds: row.try_get(4)?,
+                start_trigger: row.try_get(5)?,
+                stop_trigger: row.try_get(6)?,
+            });
         }
 
         Ok(result)
     }
 
-    pub async fn get_total_timestamp_day(&self, timestamp: i64, ChatId(chat_id): ChatId) -> Result<Option<i64>, Error> {
-        #[derive(sqlx::FromRow)]
-        struct TotalSecondsDbRow {
-            total_seconds: i64,
+    async fn import_daily_totals(&self, chat_id: ChatId, days: &[(NaiveDate, i64)], policy: MergePolicy) -> StoreResult<()> {
+        let mut tx = self.pool.begin().await?;
+        for (date, seconds) in days {
+            let date = date.format("%Y-%m-%d").to_string();
+            match policy {
+                MergePolicy::Replace => {
+                    sqlx::query("INSERT OR REPLACE INTO total VALUES (?, ?, ?)")
+                        .bind(chat_id.0)
+                        .bind(&date)
+                        .bind(seconds)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+                MergePolicy::Add => Self::add_to_total(&mut tx, chat_id, None, &date, *seconds).await?,
+            }
         }
-        let bytes = sqlx::query_as::<_, TotalSecondsDbRow>(
-            "SELECT total_seconds FROM total WHERE chat_id = ? and date = date(?, 'unixepoch')"
-        )
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn add_adjustment(&self, chat_id: ChatId, adjustment: &Adjustment) -> StoreResult<i64> {
+        let date = adjustment.date.to_string();
+        let mut tx = self.pool.begin().await?;
+        Self::add_to_total(&mut tx, chat_id, None, &date, adjustment.seconds).await?;
+        sqlx::query(
+            "INSERT INTO adjustments (chat_id, user_id, user_name, date, seconds, reason, created_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?)")
+            .bind(chat_id.0)
+            .bind(adjustment.user_id)
+            .bind(&adjustment.user_name)
+            .bind(&date)
+            .bind(adjustment.seconds)
+            .bind(&adjustment.reason)
+            .bind(adjustment.created_at)
+            .execute(&mut *tx)
+            .await?;
+        let total: i64 = sqlx::query_scalar("SELECT total_seconds FROM total WHERE chat_id = ? AND date = ?")
+            .bind(chat_id.0)
+            .bind(&date)
+            .fetch_one(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(total)
+    }
+
+    async fn get_adjustments(&self, ChatId(chat_id): ChatId, limit: u32) -> StoreResult<Vec<Adjustment>> {
+        let rows = sqlx::query(
+            "SELECT user_id, user_name, date, seconds, reason, created_at FROM adjustments
+             WHERE chat_id = ? ORDER BY id DESC LIMIT ?")
             .bind(chat_id)
-            .bind(timestamp)
-            .fetch_optional(&self.pool)
-            .await?
-            .map(|r| r.total_seconds);
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
 
-        Ok(bytes)
+        let mut result = Vec::new();
+        for row in rows {
+            let date: String = row.try_get(2)?;
+            result.push(Adjustment {
+                user_id: row.try_get(0)?,
+                user_name: row.try_get(1)?,
+                date: date.parse::<NaiveDate>()?,
+                seconds: row.try_get(3)?,
+                reason: row.try_get(4)?,
+                created_at: row.try_get(5)?,
+            });
+        }
+
+        Ok(result)
     }
 
+    async fn undo_last_session(&self, chat_id: ChatId, user_id: Option<UserId>, reopen: bool, now: i64) -> StoreResult<Option<SessionRecord>> {
+        let current_boundary = self.get_day_boundary(chat_id).await?;
+        let mut tx = self.pool.begin().await?;
+        let row = sqlx::query(
+            "SELECT id, user_id, start_timestamp, end_timestamp, seconds, start_trigger, stop_trigger, utc_offset_minutes, day_start_minutes FROM sessions
+             WHERE chat_id = ?1 AND user_id IS ?2 AND end_timestamp IS NOT NULL AND seconds IS NOT NULL AND undone_at IS NULL
+             ORDER BY end_timestamp DESC, id DESC LIMIT 1")
+            .bind(chat_id.0)
+            .bind(user_id.map(|UserId(user_id)| user_id as i64))
+            .fetch_optional(&mut *tx)
+            .await?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let session = SessionRecord {
+            id: row.try_get(0)?,
+            user_id: row.try_get(1)?,
+            start_timestamp: row.try_get(2)?,
+            end_timestamp: row.try_get(3)?,
+            seconds: row.try_get(4)?,
+            start_trigger: row.try_get(5)?,
+            stop_trigger: row.try_get(6)?,
+        };
+        // Take the seconds off the days they were credited to, even if the boundary changed since
+        let boundary = match (row.try_get(7)?, row.try_get(8)?) {
+            (Some(utc_offset_minutes), Some(day_start_minutes)) => DayBoundary { utc_offset_minutes, day_start_minutes },
+            _ => current_boundary,
+        };
+
+        let end_timestamp = session.end_timestamp.unwrap_or_default();
+        for (date, day_seconds) in split_by_day(end_timestamp - session.seconds.unwrap_or_default(), end_timestamp, boundary) {
+            let date = date.to_string();
+            Self::remove_from_total(&mut tx, chat_id, None, &date, day_seconds).await?;
+            if user_id.is_some() {
+                Self::remove_from_total(&mut tx, chat_id, user_id, &date, day_seconds).await?;
+            }
+        }
+        if reopen {
+            sqlx::query("UPDATE sessions SET end_timestamp = NULL, seconds = NULL, stop_trigger = NULL WHERE id = ?")
+                .bind(session.id)
+                .execute(&mut *tx)
+                .await?;
+        } else {
+            sqlx::query("UPDATE sessions SET undone_at = ? WHERE id = ?")
+                .bind(now)
+                .bind(session.id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+
+        Ok(Some(session))
+    }
 }
 
 #[cfg(test)]
 mod test_management {
-    use chrono::Utc;
+    use chrono::{Datelike, TimeZone, Utc};
 
     use super::*;
+    use crate::period::{start_of_week, Period};
+
+    /// The current period in UTC with Monday weeks, the calendar of chats without settings
+    fn this(period: Period) -> DateRange {
+        period.resolve(DayBoundary::default().date_of(Utc::now().timestamp()), Weekday::Mon)
+    }
+
+    async fn stand_today(total: &Total, chat_id: ChatId, seconds: i64) -> i64 {
+        let now = Utc::now().timestamp();
+        total.start_session(chat_id, now - seconds, StartTrigger::Sticker).await.unwrap();
+        total.finish_session(chat_id, now - seconds, now, seconds, StopTrigger::SitSticker).await.unwrap()
+    }
+
+
+    /// Path of a fresh database file, removed when dropped
+    struct TempDatabase(String);
+
+    impl TempDatabase {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("standing_bot_{name}_{}.sqlite", std::process::id()));
+            let _ = std::fs::remove_file(&path);
+            Self(path.to_string_lossy().into_owned())
+        }
+    }
+
+    impl Drop for TempDatabase {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_migrating_old_database() {
+        let database = TempDatabase::new("old");
+        let pool = SqlitePool::connect(&format!("sqlite:{}?mode=rwc", database.0)).await.unwrap();
+        sqlx::query("CREATE TABLE total (chat_id BIGINT, date TEXT, total_seconds INT, CONSTRAINT id_date UNIQUE(chat_id, date))")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO total VALUES (1, '2024-05-01', 100)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        pool.close().await;
+
+        let total = Total::connect(&database.0).await.unwrap();
+        let start = Utc.with_ymd_and_hms(2024, 5, 1, 10, 0, 0).unwrap().timestamp();
+        total.start_session(ChatId(1), start, StartTrigger::Sticker).await.unwrap();
+        assert_eq!(total.finish_session(ChatId(1), start, start + 50, 50, StopTrigger::SitSticker).await.unwrap(), 150);
+        total.pool.close().await;
+
+        // Running the migrations again is a no-op
+        let total = Total::connect(&database.0).await.unwrap();
+        assert_eq!(total.get_total_timestamp_day(start, ChatId(1)).await.unwrap(), Some(150));
+        total.pool.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_refusing_newer_database() {
+        let database = TempDatabase::new("newer");
+        let total = Total::connect(&database.0).await.unwrap();
+        sqlx::query("INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time) VALUES (9999, 'from the future', TRUE, x'00', 0)")
+            .execute(&total.pool)
+            .await
+            .unwrap();
+        total.pool.close().await;
+
+        assert!(matches!(
+            Total::connect(&database.0).await.err(),
+            Some(Error::Migrate(error)) if matches!(*error, sqlx::migrate::MigrateError::VersionMissing(9999))
+        ));
+    }
 
     #[tokio::test]
     async fn test_creating() {
-        let total = Total::create_table(":memory:").await.unwrap();
-        total.clone().set_total_today(ChatId(1), 100).await.unwrap();
+        let total = Total::connect(":memory:").await.unwrap();
+        stand_today(&total, ChatId(1), 100).await;
 
         assert_eq!(total.clone().get_total_timestamp_day(Utc::now().timestamp(),ChatId(1)).await.unwrap().unwrap(), 100);
     }
 
+    #[tokio::test]
+    async fn test_session_is_recorded() {
+        let total = Total::connect(":memory:").await.unwrap();
+        total.start_session(ChatId(1), 1000, StartTrigger::Keyboard).await.unwrap();
+        let day_total = total.finish_session(ChatId(1), 1000, 1600, 600, StopTrigger::LlmText).await.unwrap();
+        assert_eq!(day_total, 600);
+
+        let row = sqlx::query("SELECT chat_id, start_timestamp, end_timestamp, seconds, start_trigger, stop_trigger FROM sessions")
+            .fetch_one(&total.pool)
+            .await
+            .unwrap();
+        assert_eq!(row.get::<i64, _>(0), 1);
+        assert_eq!(row.get::<i64, _>(1), 1000);
+        assert_eq!(row.get::<i64, _>(2), 1600);
+        assert_eq!(row.get::<i64, _>(3), 600);
+        assert_eq!(row.get::<String, _>(4), "keyboard");
+        assert_eq!(row.get::<String, _>(5), "llm_text");
+    }
+
+    #[tokio::test]
+    async fn test_finishing_untracked_session() {
+        let total = Total::connect(":memory:").await.unwrap();
+        total.finish_session(ChatId(1), 1000, 1600, 300, StopTrigger::Cancel).await.unwrap();
+
+        let row = sqlx::query("SELECT start_trigger, stop_trigger, seconds FROM sessions")
+            .fetch_one(&total.pool)
+            .await
+            .unwrap();
+        assert_eq!(row.get::<Option<String>, _>(0), None);
+        assert_eq!(row.get::<String, _>(1), "cancel");
+        assert_eq!(row.get::<i64, _>(2), 300);
+    }
+
+    #[tokio::test]
+    async fn test_sessions_add_up() {
+        let total = Total::connect(":memory:").await.unwrap();
+        stand_today(&total, ChatId(1), 100).await;
+        assert_eq!(stand_today(&total, ChatId(1), 200).await, 300);
+        assert_eq!(stand_today(&total, ChatId(2), 50).await, 50);
+
+        let sessions: i64 = sqlx::query("SELECT COUNT(*) FROM sessions WHERE end_timestamp IS NOT NULL")
+            .fetch_one(&total.pool)
+            .await
+            .unwrap()
+            .get(0);
+        assert_eq!(sessions, 3);
+
+        // The day totals are exactly the finished sessions
+        for chat_id in [1, 2] {
+            let (session_seconds, total_seconds): (i64, i64) = sqlx::query_as(
+                "SELECT (SELECT SUM(seconds) FROM sessions WHERE chat_id = ?1), (SELECT SUM(total_seconds) FROM total WHERE chat_id = ?1)")
+                .bind(chat_id)
+                .fetch_one(&total.pool)
+                .await
+                .unwrap();
+            assert_eq!(session_seconds, total_seconds);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_session_across_midnight() {
+        let total = Total::connect(":memory:").await.unwrap();
+        let start = Utc.with_ymd_and_hms(2024, 5, 1, 23, 30, 0).unwrap().timestamp();
+        let end = Utc.with_ymd_and_hms(2024, 5, 2, 0, 30, 0).unwrap().timestamp();
+        sqlx::query("INSERT INTO total VALUES (1, '2024-05-01', 1000)")
+            .execute(&total.pool)
+            .await
+            .unwrap();
+
+        total.start_session(ChatId(1), start, StartTrigger::Sticker).await.unwrap();
+        let day_total = total.finish_session(ChatId(1), start, end, end - start, StopTrigger::SitSticker).await.unwrap();
+
+        assert_eq!(day_total, 1800);
+        assert_eq!(total.get_total_timestamp_day(start, ChatId(1)).await.unwrap(), Some(2800));
+        assert_eq!(total.get_total_timestamp_day(end, ChatId(1)).await.unwrap(), Some(1800));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_credit_across_midnight() {
+        let total = Total::connect(":memory:").await.unwrap();
+        let start = Utc.with_ymd_and_hms(2024, 5, 1, 20, 0, 0).unwrap().timestamp();
+        let end = Utc.with_ymd_and_hms(2024, 5, 2, 0, 10, 0).unwrap().timestamp();
+
+        total.finish_session(ChatId(1), start, end, 30 * 60, StopTrigger::Cancel).await.unwrap();
+
+        assert_eq!(total.get_total_timestamp_day(start, ChatId(1)).await.unwrap(), Some(1200));
+        assert_eq!(total.get_total_timestamp_day(end, ChatId(1)).await.unwrap(), Some(600));
+    }
+
+    #[tokio::test]
+    async fn test_undo_after_changing_offset() {
+        let total = Total::connect(":memory:").await.unwrap();
+        let start = Utc.with_ymd_and_hms(2024, 5, 1, 23, 30, 0).unwrap().timestamp();
+        let end = Utc.with_ymd_and_hms(2024, 5, 2, 0, 30, 0).unwrap().timestamp();
+        total.finish_session(ChatId(1), start, end, end - start, StopTrigger::SitSticker).await.unwrap();
+
+        // Now the whole session falls on May 1, but it was credited to both days
+        total.set_utc_offset(ChatId(1), -120).await.unwrap();
+        total.undo_last_session(ChatId(1), None, false, end).await.unwrap();
+
+        let days = total.get_daily_totals(ChatId(1), DateRange::default()).await.unwrap();
+        assert_eq!(days, vec![(NaiveDate::from_ymd_opt(2024, 5, 1).unwrap(), 0), (NaiveDate::from_ymd_opt(2024, 5, 2).unwrap(), 0)]);
+    }
+
+    #[tokio::test]
+    async fn test_undo_never_goes_negative() {
+        let total = Total::connect(":memory:").await.unwrap();
+        let start = Utc.with_ymd_and_hms(2024, 5, 1, 10, 0, 0).unwrap().timestamp();
+        total.finish_session(ChatId(1), start, start + 600, 600, StopTrigger::SitSticker).await.unwrap();
+        total.import_daily_totals(ChatId(1), &[(NaiveDate::from_ymd_opt(2024, 5, 1).unwrap(), 100)], MergePolicy::Replace).await.unwrap();
+
+        total.undo_last_session(ChatId(1), None, false, start + 600).await.unwrap();
+        assert_eq!(total.get_total_timestamp_day(start, ChatId(1)).await.unwrap(), Some(0));
+    }
+
+    #[tokio::test]
+    async fn test_oversized_cancel_credit() {
+        let total = Total::connect(":memory:").await.unwrap();
+        let start = Utc.with_ymd_and_hms(2024, 5, 1, 10, 0, 0).unwrap().timestamp();
+
+        total.finish_session(ChatId(1), start, start + 600, 50_000_000_000, StopTrigger::Cancel).await.unwrap();
+
+        assert_eq!(total.get_total_timestamp_day(start - 86400, ChatId(1)).await.unwrap(), Some(49800));
+        assert_eq!(total.get_total_timestamp_day(start, ChatId(1)).await.unwrap(), Some(36600));
+    }
+
+    #[tokio::test]
+    async fn test_chat_settings() {
+        let total = Total::connect(":memory:").await.unwrap();
+        assert_eq!(total.get_day_boundary(ChatId(1)).await.unwrap(), DayBoundary::default());
+
+        total.set_utc_offset(ChatId(1), 360).await.unwrap();
+        total.set_day_start(ChatId(1), 240).await.unwrap();
+        total.set_utc_offset(ChatId(2), -180).await.unwrap();
+
+        assert_eq!(total.get_day_boundary(ChatId(1)).await.unwrap(), DayBoundary { utc_offset_minutes: 360, day_start_minutes: 240 });
+        assert_eq!(total.get_day_boundary(ChatId(2)).await.unwrap(), DayBoundary { utc_offset_minutes: -180, day_start_minutes: 0 });
+    }
+
+    #[tokio::test]
+    async fn test_session_in_local_time() {
+        let total = Total::connect(":memory:").await.unwrap();
+        total.set_utc_offset(ChatId(1), 360).await.unwrap();
+        // 23:30..00:30 in UTC+6
+        let start = Utc.with_ymd_and_hms(2024, 5, 1, 17, 30, 0).unwrap().timestamp();
+        let end = Utc.with_ymd_and_hms(2024, 5, 1, 18, 30, 0).unwrap().timestamp();
+
+        total.start_session(ChatId(1), start, StartTrigger::Sticker).await.unwrap();
+        total.finish_session(ChatId(1), start, end, end - start, StopTrigger::SitSticker).await.unwrap();
+
+        let rows: Vec<(String, i64)> = sqlx::query_as("SELECT date, total_seconds FROM total ORDER BY date")
+            .fetch_all(&total.pool)
+            .await
+            .unwrap();
+        assert_eq!(rows, vec![("2024-05-01".to_string(), 1800), ("2024-05-02".to_string(), 1800)]);
+        assert_eq!(total.get_total_timestamp_day(end, ChatId(1)).await.unwrap(), Some(1800));
+
+        // With the day starting at 04:00 the end of the session belongs to the first day
+        total.set_day_start(ChatId(1), 240).await.unwrap();
+        assert_eq!(total.get_total_timestamp_day(end - 3600, ChatId(1)).await.unwrap(), Some(1800));
+    }
+
+    #[tokio::test]
+    async fn test_member_sessions() {
+        let total = Total::connect(":memory:").await.unwrap();
+        let start = Utc.with_ymd_and_hms(2024, 5, 1, 10, 0, 0).unwrap().timestamp();
+
+        assert!(total.start_member_session(ChatId(1), UserId(10), "Alice", start).await.unwrap());
+        assert!(total.start_member_session(ChatId(1), UserId(20), "Bob", start + 600).await.unwrap());
+        // Alice is already standing
+        assert!(!total.start_member_session(ChatId(1), UserId(10), "Alice", start + 60).await.unwrap());
+        assert_eq!(total.get_member_session_start(ChatId(1), UserId(10)).await.unwrap(), Some(start));
+
+        assert_eq!(total.finish_member_session(ChatId(1), UserId(10), start + 1800, StopTrigger::SitSticker).await.unwrap(), Some((start, 1800)));
+        assert_eq!(total.finish_member_session(ChatId(1), UserId(10), start + 1900, StopTrigger::SitSticker).await.unwrap(), None);
+        assert_eq!(total.finish_member_session(ChatId(1), UserId(20), start + 1200, StopTrigger::SitSticker).await.unwrap(), Some((start + 600, 600)));
+
+        assert_eq!(total.get_total_timestamp_day(start, ChatId(1)).await.unwrap(), Some(2400));
+        assert_eq!(total.get_member_totals(ChatId(1), DateRange::default()).await.unwrap(), vec![
+            (10, "Alice".to_string(), 1800),
+            (20, "Bob".to_string(), 600),
+        ]);
+        assert!(total.get_member_totals(ChatId(2), DateRange::default()).await.unwrap().is_empty());
+    }
+
     #[tokio::test]
     async fn test_adding_yesterdays_dates() {
-        let total = Total::create_table(":memory:").await.unwrap();
+        let total = Total::connect(":memory:").await.unwrap();
         sqlx::query("INSERT INTO total VALUES (1, date('now', '-5 day'), 100)")
             .execute(&total.pool)
             .await
             .unwrap();
         assert_eq!(total.clone().get_total_timestamp_day(Utc::now().timestamp(),ChatId(1)).await.unwrap(), None);
-        total.clone().set_total_today(ChatId(1), 200).await.unwrap();
+        stand_today(&total, ChatId(1), 200).await;
         assert_eq!(total.clone().get_total_timestamp_day(Utc::now().timestamp(),ChatId(1)).await.unwrap().unwrap(), 200);
     }
 
     #[tokio::test]
     async fn test_total_yesterdays_dates() {
-        let total = Total::create_table(":memory:").await.unwrap();
+        let total = Total::connect(":memory:").await.unwrap();
         sqlx::query("INSERT INTO total VALUES (1, date('now', '-5 day'), 100)")
             .execute(&total.pool)
             .await
@@ -221,10 +1040,10 @@ mod test_management {
 
     #[tokio::test]
     async fn test_average() {
-        let total = Total::create_table(":memory:").await.unwrap();
-        total.clone().set_total_today(ChatId(1), 100).await.unwrap();
-        total.clone().set_total_today(ChatId(1), 200).await.unwrap();
-        total.clone().set_total_today(ChatId(2), 300).await.unwrap();
+        let total = Total::connect(":memory:").await.unwrap();
+        stand_today(&total, ChatId(1), 100).await;
+        stand_today(&total, ChatId(1), 200).await;
+        stand_today(&total, ChatId(2), 300).await;
 
         sqlx::query("INSERT INTO total VALUES (2, date('now', '-1 day'), 100)")
             .execute(&total.pool)
@@ -236,72 +1055,85 @@ mod test_management {
             .await
             .unwrap();
 
-        let averages = total.get_average_total_per_day_by_chat().await.unwrap();
+        // Days without standing count as zero
+        sqlx::query("INSERT INTO total VALUES (3, date('now', '-3 day'), 400)")
+            .execute(&total.pool)
+            .await
+            .unwrap();
+
+        let today = DayBoundary::default().date_of(Utc::now().timestamp());
+        let averages = total.get_chat_totals(DateRange::default()).await.unwrap();
 
-        assert_eq!(averages.len(), 2);
-        assert_eq!(averages[0].0, 1);
-        assert_eq!(averages[0].1, Some(200));
-        assert_eq!(averages[1].0, 2);
-        assert_eq!(averages[1].1, Some(250));
+        assert_eq!(averages.len(), 3);
+        assert_eq!(averages[0].chat_id, 1);
+        assert_eq!(averages[0].average(DateRange::default(), today), 300);
+        assert_eq!(averages[1].chat_id, 2);
+        assert_eq!(averages[1].average(DateRange::default(), today), 200);
+        assert_eq!(averages[2].chat_id, 3);
+        assert_eq!(averages[2].days, 1);
+        assert_eq!(averages[2].average(DateRange::default(), today), 100);
     }
 
     #[tokio::test]
     async fn test_average_month() {
-        let total = Total::create_table(":memory:").await.unwrap();
-        total.clone().set_total_today(ChatId(1), 150).await.unwrap(); // current month
-        total.clone().set_total_today(ChatId(1), 250).await.unwrap(); // current month
+        let total = Total::connect(":memory:").await.unwrap();
+        stand_today(&total, ChatId(1), 150).await; // current month
+        stand_today(&total, ChatId(1), 250).await; // current month
         sqlx::query("INSERT INTO total VALUES (1, date('now', '-1 month'), 100)")
             .execute(&total.pool)
             .await
             .unwrap();
 
-        let averages = total.get_average_total_per_month_by_chat().await.unwrap();
+        let today = DayBoundary::default().date_of(Utc::now().timestamp());
+        let averages = total.get_chat_totals(this(Period::Month)).await.unwrap();
 
         assert_eq!(averages.len(), 1);
-        assert_eq!(averages[0].0, 1);
-        assert_eq!(averages[0].1, Some(200));
+        assert_eq!(averages[0].chat_id, 1);
+        assert_eq!(averages[0].average(this(Period::Month), today), 400 / (today.day() as i64));
     }
 
     #[tokio::test]
     async fn test_average_week() {
-        let total = Total::create_table(":memory:").await.unwrap();
-        total.clone().set_total_today(ChatId(1), 150).await.unwrap(); // current week
-        total.clone().set_total_today(ChatId(1), 250).await.unwrap(); // current week
+        let total = Total::connect(":memory:").await.unwrap();
+        stand_today(&total, ChatId(1), 150).await; // current week
+        stand_today(&total, ChatId(1), 250).await; // current week
         sqlx::query("INSERT INTO total VALUES (1, date('now', '-1 week'), 100)")
             .execute(&total.pool)
             .await
             .unwrap();
 
-        let averages = total.get_average_total_per_week_by_chat().await.unwrap();
+        let today = DayBoundary::default().date_of(Utc::now().timestamp());
+        let averages = total.get_chat_totals(this(Period::Week)).await.unwrap();
 
         assert_eq!(averages.len(), 1);
-        assert_eq!(averages[0].0, 1);
-        assert_eq!(averages[0].1, Some(200));
+        assert_eq!(averages[0].chat_id, 1);
+        assert_eq!(averages[0].average(this(Period::Week), today), 400 / ((today - start_of_week(today, Weekday::Mon)).num_days() + 1));
     }
 
     #[tokio::test]
     async fn test_average_year() {
-        let total = Total::create_table(":memory:").await.unwrap();
-        total.clone().set_total_today(ChatId(1), 150).await.unwrap(); // current year
-        total.clone().set_total_today(ChatId(1), 250).await.unwrap(); // current year
+        let total = Total::connect(":memory:").await.unwrap();
+        stand_today(&total, ChatId(1), 150).await; // current year
+        stand_today(&total, ChatId(1), 250).await; // current year
         sqlx::query("INSERT INTO total VALUES (1, date('now', '-1 year'), 100)")
             .execute(&total.pool)
             .await
             .unwrap();
 
-        let averages = total.get_average_total_per_year_by_chat().await.unwrap();
+        let today = DayBoundary::default().date_of(Utc::now().timestamp());
+        let averages = total.get_chat_totals(this(Period::Year)).await.unwrap();
 
         assert_eq!(averages.len(), 1);
-        assert_eq!(averages[0].0, 1);
-        assert_eq!(averages[0].1, Some(200));
+        assert_eq!(averages[0].chat_id, 1);
+        assert_eq!(averages[0].average(this(Period::Year), today), 400 / (today.ordinal() as i64));
     }
 
     #[tokio::test]
     async fn test_total() {
-        let total = Total::create_table(":memory:").await.unwrap();
-        total.clone().set_total_today(ChatId(1), 100).await.unwrap();
-        total.clone().set_total_today(ChatId(1), 200).await.unwrap();
-        total.clone().set_total_today(ChatId(2), 300).await.unwrap();
+        let total = Total::connect(":memory:").await.unwrap();
+        stand_today(&total, ChatId(1), 100).await;
+        stand_today(&total, ChatId(1), 200).await;
+        stand_today(&total, ChatId(2), 300).await;
 
         sqlx::query("INSERT INTO total VALUES (2, date('now', '-1 day'), 100)")
             .execute(&total.pool)
@@ -313,61 +1145,61 @@ mod test_management {
             .await
             .unwrap();
 
-        let totals = total.get_total_seconds_grouped_by_chat().await.unwrap();
+        let totals = total.get_chat_totals(DateRange::default()).await.unwrap();
 
         assert_eq!(totals.len(), 2);
-        assert_eq!(totals[0].0, 1);
-        assert_eq!(totals[0].1, Some(200));
-        assert_eq!(totals[1].0, 2);
-        assert_eq!(totals[1].1, Some(600));
+        assert_eq!(totals[0].chat_id, 1);
+        assert_eq!(totals[0].seconds, 300);
+        assert_eq!(totals[1].chat_id, 2);
+        assert_eq!(totals[1].seconds, 600);
     }
 
     #[tokio::test]
     async fn test_month_total() {
-        let total = Total::create_table(":memory:").await.unwrap();
+        let total = Total::connect(":memory:").await.unwrap();
         // Insert data for current month and previous month
-        total.clone().set_total_today(ChatId(1), 100).await.unwrap(); // current month
+        stand_today(&total, ChatId(1), 100).await; // current month
         sqlx::query("INSERT INTO total VALUES (1, date('now', '-1 month'), 100)")
             .execute(&total.pool)
             .await
             .unwrap();
 
-        let month_totals = total.get_total_seconds_grouped_by_month().await.unwrap();
+        let month_totals = total.get_chat_totals(this(Period::Month)).await.unwrap();
         assert_eq!(month_totals.len(), 1);
-        assert_eq!(month_totals[0].0, 1);
-        assert_eq!(month_totals[0].1, Some(100));
+        assert_eq!(month_totals[0].chat_id, 1);
+        assert_eq!(month_totals[0].seconds, 100);
     }
 
     #[tokio::test]
     async fn test_week_total() {
-        let total = Total::create_table(":memory:").await.unwrap();
+        let total = Total::connect(":memory:").await.unwrap();
         // Insert data for current week and previous week
-        total.clone().set_total_today(ChatId(1), 100).await.unwrap(); // current week
+        stand_today(&total, ChatId(1), 100).await; // current week
         sqlx::query("INSERT INTO total VALUES (1, date('now', '-1 week'), 100)")
             .execute(&total.pool)
             .await
             .unwrap();
 
-        let week_totals = total.get_total_seconds_grouped_by_week().await.unwrap();
+        let week_totals = total.get_chat_totals(this(Period::Week)).await.unwrap();
         assert_eq!(week_totals.len(), 1);
-        assert_eq!(week_totals[0].0, 1);
-        assert_eq!(week_totals[0].1, Some(100));
+        assert_eq!(week_totals[0].chat_id, 1);
+        assert_eq!(week_totals[0].seconds, 100);
     }
 
     #[tokio::test]
     async fn test_year_total() {
-        let total = Total::create_table(":memory:").await.unwrap();
+        let total = Total::connect(":memory:").await.unwrap();
         // Insert data for current year and previous year
-        total.clone().set_total_today(ChatId(1), 100).await.unwrap(); // current year
+        stand_today(&total, ChatId(1), 100).await; // current year
         sqlx::query("INSERT INTO total VALUES (1, date('now', '-1 year'), 100)")
             .execute(&total.pool)
             .await
             .unwrap();
 
-        let year_totals = total.get_total_seconds_grouped_by_year().await.unwrap();
+        let year_totals = total.get_chat_totals(this(Period::Year)).await.unwrap();
         assert_eq!(year_totals.len(), 1);
-        assert_eq!(year_totals[0].0, 1);
-        assert_eq!(year_totals[0].1, Some(100));
+        assert_eq!(year_totals[0].chat_id, 1);
+        assert_eq!(year_totals[0].seconds, 100);
     }
 
 }